@@ -0,0 +1,25 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use xelis_hash::{c_port, v2, v3};
+
+const INPUT: [u8; c_port::INPUT_LEN] = [0u8; c_port::INPUT_LEN];
+
+// Compare the speed of this crate with the C implementation
+fn bench_v2(c: &mut Criterion) {
+    let mut group = c.benchmark_group("c_port::v2");
+    let mut scratch_pad = v2::ScratchPad::default();
+    group.bench_function("rust", |b| b.iter(|| v2::xelis_hash(&INPUT, &mut scratch_pad)));
+    group.bench_function("c", |b| b.iter(|| c_port::v2::xelis_hash(&INPUT, &mut scratch_pad)));
+    group.finish();
+}
+
+fn bench_v3(c: &mut Criterion) {
+    let mut group = c.benchmark_group("c_port::v3");
+    let mut scratch_pad = v3::ScratchPad::default();
+    group.bench_function("rust", |b| b.iter(|| v3::xelis_hash(&INPUT, &mut scratch_pad)));
+    // SAFETY: the zero input has a valid hash
+    group.bench_function("c", |b| b.iter(|| unsafe { c_port::v3::xelis_hash(&INPUT, &mut scratch_pad) }));
+    group.finish();
+}
+
+criterion_group!(benches, bench_v2, bench_v3);
+criterion_main!(benches);
@@ -0,0 +1,143 @@
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::{Rng, SeedableRng, rngs::StdRng};
+use xelis_hash::math::{div_u128_to_u64, isqrt, modular_power, rem_u128_to_u64};
+
+fn bench_rem(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+    let mut group = c.benchmark_group("math::rem_u128_by_u64");
+
+    group.bench_function("u128", |b| {
+        b.iter_batched(
+            || (rng.gen::<u128>(), (rng.gen::<u64>() | 1) as u128),
+            |(n, d)| n.wrapping_rem(d) as u64,
+            BatchSize::SmallInput
+        )
+    });
+
+    group.bench_function("fast", |b| {
+        b.iter_batched(
+            || (rng.gen::<u128>(), (rng.gen::<u64>() | 1) as u128),
+            |(n, d)| rem_u128_to_u64(n, d),
+            BatchSize::SmallInput
+        )
+    });
+
+    group.finish();
+}
+
+fn bench_div(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+    let mut group = c.benchmark_group("math::div_u128_by_u64");
+
+    group.bench_function("u128", |b| {
+        b.iter_batched(
+            || (rng.gen::<u128>(), (rng.gen::<u64>() | 4) as u128),
+            |(n, d)| n.wrapping_div(d) as u64,
+            BatchSize::SmallInput
+        )
+    });
+
+    group.bench_function("fast", |b| {
+        b.iter_batched(
+            || (rng.gen::<u128>(), (rng.gen::<u64>() | 4) as u128),
+            |(n, d)| div_u128_to_u64(n, d),
+            BatchSize::SmallInput
+        )
+    });
+
+    group.finish();
+}
+
+// Square-and-multiply with a u128 remainder per step
+fn naive_modular_power(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
+    let mut result: u64 = 1;
+    base %= modulus;
+
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = ((result as u128 * base as u128) % modulus as u128) as u64;
+        }
+
+        base = ((base as u128 * base as u128) % modulus as u128) as u64;
+        exp /= 2;
+    }
+
+    result
+}
+
+fn bench_modular_power(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+    let mut group = c.benchmark_group("math::modular_power");
+
+    for (name, mask) in [("odd", 1u64), ("even", 0u64)] {
+        let modulus = |rng: &mut StdRng| if mask == 1 { rng.gen::<u64>() | 1 } else { (rng.gen::<u64>() & !1).max(2) };
+
+        group.bench_function(format!("naive_{}", name), |b| {
+            b.iter_batched(
+                || (rng.gen::<u64>(), rng.gen::<u64>(), modulus(&mut rng)),
+                |(base, exp, m)| naive_modular_power(base, exp, m),
+                BatchSize::SmallInput
+            )
+        });
+
+        group.bench_function(format!("fast_{}", name), |b| {
+            b.iter_batched(
+                || (rng.gen::<u64>(), rng.gen::<u64>(), modulus(&mut rng)),
+                |(base, exp, m)| modular_power(base, exp, m),
+                BatchSize::SmallInput
+            )
+        });
+    }
+
+    group.finish();
+}
+
+// Floating-point implementation previously used by v3
+fn float_isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+
+    let approx = (n as f64).sqrt() as u64;
+    if approx.wrapping_mul(approx) > n {
+        approx.wrapping_sub(1)
+    } else if approx.wrapping_add(1).wrapping_mul(approx.wrapping_add(1)) <= n {
+        approx.wrapping_add(1)
+    } else {
+        approx
+    }
+}
+
+fn bench_isqrt(c: &mut Criterion) {
+    let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+    let mut group = c.benchmark_group("math::isqrt");
+
+    group.bench_function("float", |b| {
+        b.iter_batched(
+            || rng.gen::<u64>() >> rng.gen_range(0..64),
+            float_isqrt,
+            BatchSize::SmallInput
+        )
+    });
+
+    group.bench_function("std", |b| {
+        b.iter_batched(
+            || rng.gen::<u64>() >> rng.gen_range(0..64),
+            u64::isqrt,
+            BatchSize::SmallInput
+        )
+    });
+
+    group.bench_function("fast", |b| {
+        b.iter_batched(
+            || rng.gen::<u64>() >> rng.gen_range(0..64),
+            isqrt,
+            BatchSize::SmallInput
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_rem, bench_div, bench_modular_power, bench_isqrt);
+criterion_main!(benches);
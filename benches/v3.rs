@@ -21,7 +21,7 @@ fn bench_pick_half(c: &mut Criterion) {
     c.bench_function("v3::pick_half", |b| {
         b.iter_batched(
             || rng.gen::<u64>(),
-            |seed| pick_half(seed),
+            pick_half,
             BatchSize::SmallInput
         )
     });
@@ -33,7 +33,7 @@ fn bench_map_index(c: &mut Criterion) {
     c.bench_function("v3::map_index", |b| {
         b.iter_batched(
             || rng.gen::<u64>(),
-            |seed| map_index(seed),
+            map_index,
             BatchSize::SmallInput
         )
     });
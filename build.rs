@@ -0,0 +1,100 @@
+// Build the C implementation of the `C` directory when the `c-port` feature is enabled
+// It is only used to check that it agrees with this crate
+
+fn main() {
+    #[cfg(feature = "c-port")]
+    c_port::build();
+}
+
+#[cfg(feature = "c-port")]
+mod c_port {
+    use std::{env, path::Path};
+
+    // Symbols defined by both the v2 and v3 sources, prefixed to link them together
+    const SHARED_SYMBOLS: [&str; 10] = [
+        "stage1",
+        "stage3",
+        "isqrt",
+        "display_time",
+        "timing_test",
+        "set_thread_affinity",
+        "hash_thread",
+        "hash_test",
+        "print_usage",
+        "main",
+    ];
+
+    // BLAKE3 sources, the SIMD implementations are only available as assembly on x86_64 unix
+    const BLAKE3_SOURCES: [&str; 7] = [
+        "blake3.c",
+        "blake3_dispatch.c",
+        "blake3_portable.c",
+        "blake3_sse2_x86-64_unix.S",
+        "blake3_sse41_x86-64_unix.S",
+        "blake3_avx2_x86-64_unix.S",
+        "blake3_avx512_x86-64_unix.S",
+    ];
+
+    pub fn build() {
+        let arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap();
+        let os = env::var("CARGO_CFG_TARGET_OS").unwrap();
+        if arch != "x86_64" || os != "linux" {
+            panic!("the c-port feature is only supported on x86_64 Linux");
+        }
+
+        let blake3 = Path::new("C/BLAKE3/c");
+        if !blake3.join("blake3.h").exists() {
+            panic!("the BLAKE3 submodule is missing, run `git submodule update --init C/BLAKE3`");
+        }
+
+        println!("cargo:rerun-if-changed=C");
+
+        // Libraries are linked in order: each one before its dependencies
+        for version in ["v2", "v3"] {
+            let mut build = cc::Build::new();
+            build.file(format!("C/xelis_hash_{}.c", version))
+                .include("C")
+                .flag("-maes")
+                .flag("-mpclmul")
+                .opt_level(3)
+                .warnings(false);
+
+            for symbol in SHARED_SYMBOLS {
+                build.define(symbol, Some(format!("xelis_hash_{}_{}", version, symbol).as_str()));
+            }
+
+            build.compile(&format!("xelis_hash_c_{}", version));
+        }
+
+        // Each ChaCha implementation is built with its own ISA flags
+        // and selected at runtime by the dispatcher
+        let chacha20 = Path::new("C/ChaCha20-SIMD");
+        let mut build = cc::Build::new();
+        for (file, flag) in [("chacha20_sse2.c", "-msse2"), ("chacha20_avx2.c", "-mavx2")] {
+            let objects = cc::Build::new()
+                .file(chacha20.join(file))
+                .flag(flag)
+                .opt_level(3)
+                .warnings(false)
+                .compile_intermediates();
+
+            for object in objects {
+                build.object(object);
+            }
+        }
+
+        build.files(["chacha20.c", "chacha20_dispatch.c", "cpu_features.c"].iter().map(|file| chacha20.join(file)))
+            .opt_level(3)
+            .warnings(false)
+            .compile("xelis_hash_c_chacha20");
+
+        cc::Build::new()
+            .files(BLAKE3_SOURCES.iter().map(|file| blake3.join(file)))
+            .opt_level(3)
+            .warnings(false)
+            .compile("xelis_hash_c_blake3");
+
+        println!("cargo:rustc-link-lib=m");
+        println!("cargo:rustc-link-lib=pthread");
+    }
+}
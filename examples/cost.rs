@@ -0,0 +1,61 @@
+// Count the expensive stage 3 operations of random inputs and estimate their critical path latency
+//
+// Usage: cargo run --release --example cost --features tracker-core -- <v2|v3> <hashes>
+//
+// Prints the operations per hash, the critical path with the CPU and GPU latency tables
+// and the share of each branch in it.
+
+use std::{env, process};
+
+use rand::{rngs::OsRng, RngCore};
+use xelis_hash::{tracker::cost::{CostCounter, LatencyTable, Version}, v2, v3};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (Some(version), Some(hashes)) = (
+        args.first(),
+        args.get(1).and_then(|n| n.parse::<usize>().ok()),
+    ) else {
+        eprintln!("Usage: cost <v2|v3> <hashes>");
+        process::exit(1);
+    };
+
+    let tables = vec![LatencyTable::cpu(), LatencyTable::gpu()];
+    let mut input = [0u8; 112];
+    let counter = match version.as_str() {
+        "v2" => {
+            let mut counter = CostCounter::new(Version::V2, tables);
+            let mut scratch_pad = v2::ScratchPad::default();
+            for _ in 0..hashes {
+                OsRng.fill_bytes(&mut input);
+                v2::xelis_hash_observed(&input, &mut scratch_pad, &mut counter).expect("v2 hash");
+            }
+            counter
+        },
+        "v3" => {
+            let mut counter = CostCounter::new(Version::V3, tables);
+            let mut scratch_pad = v3::ScratchPad::default();
+            for _ in 0..hashes {
+                OsRng.fill_bytes(&mut input);
+                // Inputs without a valid hash are not counted
+                let _ = v3::xelis_hash_observed(&input, &mut scratch_pad, &mut counter);
+            }
+            counter
+        },
+        _ => {
+            eprintln!("Unsupported version: {}", version);
+            process::exit(1);
+        },
+    };
+
+    let report = counter.report();
+    println!("{}", report);
+    println!("branch,{}", report.tables.join(","));
+    for branch in 0..16 {
+        let shares: Vec<String> = report.branch_path.iter()
+            .zip(&report.critical_path)
+            .map(|(branch_path, &path)| format!("{:.4}", if path == 0 { 0.0 } else { branch_path[branch] as f64 / path as f64 }))
+            .collect();
+        println!("{},{}", branch, shares.join(","));
+    }
+}
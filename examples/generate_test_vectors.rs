@@ -0,0 +1,176 @@
+// Generate or extend the test vectors using the reference implementations
+//
+// Usage: cargo run --release --example generate_test_vectors -- <v1|v2|v3> [<name> <hex input>]...
+//
+// The schema is documented in `test-vectors/README.md`.
+// The vectors already present in `test-vectors/<version>.json` are kept as is.
+// The default vectors that are missing and the ones given as arguments are appended.
+
+use std::{env, fs, path::PathBuf, process};
+
+use serde_json::{json, Map, Value};
+use xelis_hash::{reference, Error, Hash};
+
+// Version of the test vectors file format
+const FORMAT: u64 = 1;
+
+// Input of the v2 and v3 `test_verify_output` tests
+const VERIFY_OUTPUT_INPUT: &str = "acec6cd4b51f6d2d2cf236e18f85592cb36c27bf2074e5213f822178b9599\
+28d0a4fb76bee7a5cde19865a6b746eec35ff05d67e18d861c794effd66c7b8e8fd9e9156bb70514e46506e21259fe9c6\
+01b26cd2646d9b6a7c7c535932c573e7204a025c2f19dc87f97aacdc898fea44bc";
+
+// Blake3 hash of the scratchpad words in little-endian
+fn digest(words: &[u64]) -> Hash {
+    let bytes: Vec<u8> = words.iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect();
+
+    blake3::hash(&bytes).into()
+}
+
+// Name of the error in the test vectors
+fn error_name(error: &Error) -> &'static str {
+    match error {
+        Error::InvalidInputLength(_) => "invalid_input_length",
+        Error::ZeroModulus => "zero_modulus",
+        _ => panic!("unexpected error: {}", error),
+    }
+}
+
+// Deterministic pseudo random bytes using SplitMix64
+fn pseudo_random(seed: u64, len: usize) -> Vec<u8> {
+    let mut state = seed;
+    let mut bytes = Vec::with_capacity(len + 8);
+    while bytes.len() < len {
+        state = state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        bytes.extend_from_slice(&(z ^ (z >> 31)).to_le_bytes());
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+// Inputs covered by default for each version
+// The input of a vector is its hex pattern repeated `repeat` times
+fn default_inputs(version: &str) -> Vec<(String, Vec<u8>, usize)> {
+    if version == "v1" {
+        let mut custom = b"xelis-hashing-algorithm".to_vec();
+        custom.resize(200, 0);
+
+        return vec![
+            ("zero".into(), vec![0; 200], 1),
+            ("xelis-hashing-algorithm".into(), custom, 1),
+            ("ones".into(), vec![0xFF; 200], 1),
+            ("incrementing".into(), (0..200).map(|i| i as u8).collect(), 1),
+            ("random-0".into(), pseudo_random(0, 200), 1),
+            ("random-1".into(), pseudo_random(1, 200), 1),
+        ];
+    }
+
+    let mut inputs = vec![
+        ("empty".into(), Vec::new(), 1),
+        ("xelis-hashing-algorithm".into(), b"xelis-hashing-algorithm".to_vec(), 1),
+        ("zero-112".into(), vec![0; 112], 1),
+        ("ones-112".into(), vec![0xFF; 112], 1),
+        ("verify-output".into(), hex::decode(VERIFY_OUTPUT_INPUT).unwrap(), 1),
+        ("random-112-0".into(), pseudo_random(0, 112), 1),
+        ("random-112-1".into(), pseudo_random(1, 112), 1),
+    ];
+
+    // Short inputs, around the 32 bytes chunk boundaries, up to 128 bytes:
+    // the longest length below which all inputs are valid.
+    // Above, only some chunk counts are valid.
+    for len in [1, 31, 32, 33, 63, 64, 65, 96, 111, 113, 127, 128, 129, 150, 192, 256, 288, 352] {
+        inputs.push((format!("length-{}", len), pseudo_random(len as u64, len), 1));
+    }
+
+    // Longest valid input, one more byte requires a chunk that is too small
+    let max_length = if version == "v2" { 36608 * 32 } else { 33984 * 32 };
+    inputs.push(("max-length".into(), vec![0xA5], max_length));
+    inputs.push(("max-length-plus-one".into(), vec![0xA5], max_length + 1));
+
+    inputs
+}
+
+// Compute the test vector of the input using the reference implementation
+fn vector(version: &str, name: &str, pattern: &[u8], repeat: usize) -> Value {
+    let input = pattern.repeat(repeat);
+    let result = match version {
+        "v1" => {
+            let input = input.as_slice().try_into().expect("v1 inputs must be 200 bytes");
+            Ok(reference::v1::xelis_hash_with_stages(input))
+        },
+        "v2" => reference::v2::xelis_hash_with_stages(&input),
+        "v3" => reference::v3::xelis_hash_with_stages(&input),
+        _ => unreachable!(),
+    };
+
+    let mut vector = Map::new();
+    vector.insert("name".into(), json!(name));
+    vector.insert("input".into(), json!(hex::encode(pattern)));
+    if repeat != 1 {
+        vector.insert("repeat".into(), json!(repeat));
+    }
+    match result {
+        Ok((hash, stages)) => {
+            vector.insert("hash".into(), json!(hex::encode(hash)));
+            let stages: Map<String, Value> = stages.iter()
+                .map(|(stage, memory)| (stage.to_string(), json!(hex::encode(digest(memory)))))
+                .collect();
+            vector.insert("stages".into(), Value::Object(stages));
+        },
+        Err(e) => {
+            vector.insert("error".into(), json!(error_name(&e)));
+        },
+    }
+
+    Value::Object(vector)
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let Some(version) = args.first().filter(|v| ["v1", "v2", "v3"].contains(&v.as_str())) else {
+        eprintln!("Usage: generate_test_vectors <v1|v2|v3> [<name> <hex input>]...");
+        process::exit(1);
+    };
+
+    let mut inputs = default_inputs(version);
+    for pair in args[1..].chunks(2) {
+        let [name, input] = pair else {
+            eprintln!("Missing hex input for vector {}", pair[0]);
+            process::exit(1);
+        };
+        inputs.push((name.clone(), hex::decode(input).expect("invalid hex input"), 1));
+    }
+
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "test-vectors", &format!("{}.json", version)].iter().collect();
+    let mut vectors = match fs::read_to_string(&path) {
+        Ok(content) => {
+            let file: Value = serde_json::from_str(&content).expect("invalid test vectors file");
+            assert_eq!(file["format"], FORMAT, "unsupported test vectors format");
+            file["vectors"].as_array().expect("missing vectors").clone()
+        },
+        Err(_) => Vec::new(),
+    };
+
+    let mut added = 0;
+    for (name, pattern, repeat) in inputs {
+        if vectors.iter().any(|v| v["name"] == name.as_str()) {
+            continue;
+        }
+
+        vectors.push(vector(version, &name, &pattern, repeat));
+        added += 1;
+    }
+
+    let file = json!({
+        "format": FORMAT,
+        "version": version,
+        "vectors": vectors,
+    });
+
+    fs::write(&path, serde_json::to_string_pretty(&file).unwrap() + "\n").expect("failed to write the test vectors");
+    println!("{} vectors added to {}", added, path.display());
+}
@@ -0,0 +1,102 @@
+// Track the stage 3 operations over random inputs and save them for analysis
+//
+// Usage: cargo run --release --example track --features tracker-core -- [--locality] <v2|v3> <hashes> <output.json> [<saved.json>...]
+//
+// The saved trackers given are merged into the output, so a run can be split
+// or extended, and `<hashes>` can be 0 to only merge them.
+// The output is also written as `<output>.branches.csv`, `<output>.transitions.csv`,
+// `<output>.run_lengths.csv` and `<output>.mem_ops.csv`, and the uniformity report
+// and the branch entropy rate of the tracked operations are printed.
+//
+// With `--locality`, the accesses of the hashed inputs are also logged (several MB per hash)
+// and their locality is printed and written as `<output>.reuse_distances.csv`,
+// `<output>.strides.csv` and `<output>.read_after_write.csv`. They are also run through
+// the cache presets, the GPU ones being shared by a warp or wave of 32 hashes.
+// The accesses of the saved trackers are not saved, so they are not included.
+
+use std::{env, fs::File, io::{BufReader, BufWriter}, path::Path, process};
+
+use rand::{rngs::OsRng, RngCore};
+use xelis_hash::{analysis, tracker::{cache::{CacheConfig, CacheSimulator}, OpsTracker}, v2, v3};
+
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    let locality = args.first().is_some_and(|arg| arg == "--locality");
+    if locality {
+        args.remove(0);
+    }
+
+    let (Some(version), Some(hashes), Some(output)) = (args.first(), args.get(1).and_then(|n| n.parse::<u64>().ok()), args.get(2)) else {
+        eprintln!("Usage: track [--locality] <v2|v3> <hashes> <output.json> [<saved.json>...]");
+        process::exit(1);
+    };
+
+    let (memory_size, known_failures) = match version.as_str() {
+        "v2" => (v2::MEMORY_SIZE, analysis::V2_KNOWN_FAILURES),
+        "v3" => (v3::MEMORY_SIZE, analysis::V3_KNOWN_FAILURES),
+        _ => {
+            eprintln!("Unsupported version: {}", version);
+            process::exit(1);
+        },
+    };
+
+    let mut tracker = OpsTracker::new(memory_size);
+    if locality {
+        tracker = tracker.with_access_log();
+    }
+    let mut input = [0u8; 112];
+    if version == "v2" {
+        let mut scratch_pad = v2::ScratchPad::default();
+        for _ in 0..hashes {
+            OsRng.fill_bytes(&mut input);
+            v2::xelis_hash_observed(&input, &mut scratch_pad, &mut tracker).expect("v2 hash");
+        }
+    } else {
+        let mut scratch_pad = v3::ScratchPad::default();
+        for _ in 0..hashes {
+            OsRng.fill_bytes(&mut input);
+            // Inputs without a valid hash are still tracked up to the error
+            let _ = v3::xelis_hash_observed(&input, &mut scratch_pad, &mut tracker);
+        }
+    }
+
+    for path in &args[3..] {
+        let saved = OpsTracker::read_json(BufReader::new(File::open(path).expect("open saved tracker")))
+            .expect("invalid saved tracker");
+        tracker.merge(&saved).expect("merge saved tracker");
+    }
+
+    let output = Path::new(output);
+    tracker.write_json(BufWriter::new(File::create(output).expect("create output"))).expect("write JSON");
+    tracker.write_branches_csv(BufWriter::new(File::create(output.with_extension("branches.csv")).expect("create branches CSV")))
+        .expect("write branches CSV");
+    tracker.write_transitions_csv(BufWriter::new(File::create(output.with_extension("transitions.csv")).expect("create transitions CSV")))
+        .expect("write transitions CSV");
+    tracker.write_run_lengths_csv(BufWriter::new(File::create(output.with_extension("run_lengths.csv")).expect("create run lengths CSV")))
+        .expect("write run lengths CSV");
+    tracker.write_mem_ops_csv(BufWriter::new(File::create(output.with_extension("mem_ops.csv")).expect("create memory CSV")))
+        .expect("write memory CSV");
+
+    println!("{} hashes tracked", tracker.get_hashes());
+    println!("{}", analysis::analyze_tracker(&tracker, analysis::DEFAULT_ALPHA, known_failures));
+    println!("Branch entropy rate: {:.4} bits", tracker.branch_entropy_rate());
+
+    if let Some(locality) = tracker.locality() {
+        locality.write_reuse_distances_csv(BufWriter::new(File::create(output.with_extension("reuse_distances.csv")).expect("create reuse distances CSV")))
+            .expect("write reuse distances CSV");
+        locality.write_strides_csv(BufWriter::new(File::create(output.with_extension("strides.csv")).expect("create strides CSV")))
+            .expect("write strides CSV");
+        locality.write_read_after_write_csv(BufWriter::new(File::create(output.with_extension("read_after_write.csv")).expect("create read after write CSV")))
+            .expect("write read after write CSV");
+        println!("{}", locality);
+
+        let access_log = tracker.get_access_log().expect("access log");
+        let mut configs = CacheConfig::presets();
+        configs.extend([CacheConfig::ada_sm().per_thread(32), CacheConfig::rdna3_cu().per_thread(32)]);
+        for config in configs {
+            let mut simulator = CacheSimulator::new(config).expect("cache preset");
+            simulator.simulate(access_log, memory_size);
+            println!("{}", simulator.report());
+        }
+    }
+}
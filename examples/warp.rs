@@ -0,0 +1,58 @@
+// Measure the branch divergence and memory coalescing of random inputs hashed in lockstep as GPU lanes
+//
+// Usage: cargo run --release --example warp --features tracker-core -- <v2|v3> <width> <warps>
+//
+// Prints the serialization factor and lane utilization of the warps,
+// the number of steps by number of distinct branches in the warp,
+// and the memory transactions per warp request of each access site.
+
+use std::{env, process};
+
+use rand::{rngs::OsRng, RngCore};
+use xelis_hash::{tracker::{coalescing::CoalescingSimulator, warp::WarpSimulator}, v2, v3};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (Some(version), Some(width), Some(warps)) = (
+        args.first(),
+        args.get(1).and_then(|n| n.parse::<usize>().ok()).filter(|&width| width > 0),
+        args.get(2).and_then(|n| n.parse::<usize>().ok()),
+    ) else {
+        eprintln!("Usage: warp <v2|v3> <width> <warps>");
+        process::exit(1);
+    };
+
+    let mut simulator = (WarpSimulator::new(width), CoalescingSimulator::new(width));
+    let mut input = [0u8; 112];
+    match version.as_str() {
+        "v2" => {
+            let mut scratch_pad = v2::ScratchPad::default();
+            for _ in 0..width * warps {
+                OsRng.fill_bytes(&mut input);
+                v2::xelis_hash_observed(&input, &mut scratch_pad, &mut simulator).expect("v2 hash");
+            }
+        },
+        "v3" => {
+            let mut scratch_pad = v3::ScratchPad::default();
+            for _ in 0..width * warps {
+                OsRng.fill_bytes(&mut input);
+                // Inputs without a valid hash are idle lanes from their error
+                let _ = v3::xelis_hash_observed(&input, &mut scratch_pad, &mut simulator);
+            }
+        },
+        _ => {
+            eprintln!("Unsupported version: {}", version);
+            process::exit(1);
+        },
+    }
+
+    let (warp, coalescing) = simulator;
+    let report = warp.report();
+    println!("{}", report);
+    println!("distinct,steps");
+    for (distinct, steps) in report.distinct_branches.iter().enumerate() {
+        println!("{},{}", distinct + 1, steps);
+    }
+    println!();
+    println!("{}", coalescing.report());
+}
@@ -0,0 +1,89 @@
+// C ABI of the xelis-hash Rust crate
+//
+// `xelis_hash_v2` and `xelis_hash_v3` have the same signature as the functions of the C
+// implementation (`C/xelis_hash_v3.c`), so it can be replaced by linking this library.
+// Unlike the C implementation where they return void, they return an int status code:
+// XELIS_HASH_OK or a negative XELIS_HASH_ERROR_*, in which case `hash` is not written.
+// Callers must check it, as some inputs have no valid hash.
+//
+// Scratchpads are arrays of `xelis_hash_scratchpad_size(version)` uint64_t.
+// They can be allocated by `xelis_hash_scratchpad_new` or by the caller
+// and reused for any number of hashes of the same version, but not concurrently.
+
+#ifndef XELIS_HASH_H
+#define XELIS_HASH_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+// Size of a hash in bytes
+#define XELIS_HASH_SIZE 32
+// Size of the v1 input in bytes
+#define XELIS_HASH_V1_INPUT_SIZE 200
+// Size of the v2 and v3 input of `xelis_hash_v2` and `xelis_hash_v3` in bytes
+#define XELIS_HASH_INPUT_SIZE 112
+
+// Algorithm versions
+#define XELIS_HASH_V1 1
+#define XELIS_HASH_V2 2
+#define XELIS_HASH_V3 3
+
+// Error codes
+#define XELIS_HASH_OK 0
+// A required pointer is NULL
+#define XELIS_HASH_ERROR_NULL_POINTER (-1)
+// The algorithm version is not supported
+#define XELIS_HASH_ERROR_UNSUPPORTED_VERSION (-2)
+// The input length has no valid hash (v2 and v3), or is not 200 bytes (v1)
+#define XELIS_HASH_ERROR_INVALID_INPUT_LENGTH (-3)
+// The input has no valid hash as a stage 3 modulus is zero (v3)
+#define XELIS_HASH_ERROR_ZERO_MODULUS (-4)
+// Any other error
+#define XELIS_HASH_ERROR_INTERNAL (-5)
+// The input has no valid hash as a stage 3 result is 2^64 - 1, which has no integer square root (v2)
+#define XELIS_HASH_ERROR_ISQRT_OVERFLOW (-6)
+
+// Version of the library, such as "0.1.0"
+const char *xelis_hash_library_version(void);
+
+// Name of the AES round implementation used by the stage 3: "aes-ni" or "soft"
+const char *xelis_hash_backend(void);
+
+// Number of uint64_t in a scratchpad of the version, or 0 if the version is not supported
+size_t xelis_hash_scratchpad_size(int version);
+
+// Allocate a zeroed scratchpad of the version, NULL if the version is not supported
+uint64_t *xelis_hash_scratchpad_new(int version);
+
+// Free a scratchpad allocated by `xelis_hash_scratchpad_new` with the same version
+void xelis_hash_scratchpad_free(uint64_t *scratch, int version);
+
+int xelis_hash_v1(const uint8_t in[XELIS_HASH_V1_INPUT_SIZE], uint8_t hash[XELIS_HASH_SIZE], uint64_t scratch[]);
+
+int xelis_hash_v2(const uint8_t in[XELIS_HASH_INPUT_SIZE], uint8_t hash[XELIS_HASH_SIZE], uint64_t scratch[]);
+
+int xelis_hash_v3(const uint8_t in[XELIS_HASH_INPUT_SIZE], uint8_t hash[XELIS_HASH_SIZE], uint64_t scratch[]);
+
+// Same as `xelis_hash_v2` and `xelis_hash_v3` for inputs of any length
+int xelis_hash_v2_len(const uint8_t *in, size_t in_len, uint8_t hash[XELIS_HASH_SIZE], uint64_t scratch[]);
+
+int xelis_hash_v3_len(const uint8_t *in, size_t in_len, uint8_t hash[XELIS_HASH_SIZE], uint64_t scratch[]);
+
+// Hash `count` inputs of `in_len` bytes stored contiguously in `inputs`
+// and compare them with the `count` hashes stored contiguously in `hashes`.
+// `results[i]` is set to 1 if the hash of the input `i` matches, 0 if it does not,
+// or to a negative error code if the input has no valid hash.
+// Returns an error code if the arguments are invalid, XELIS_HASH_OK otherwise:
+// XELIS_HASH_ERROR_INVALID_INPUT_LENGTH if the inputs or the hashes are too large to be addressed.
+int xelis_hash_verify_batch(int version, const uint8_t *inputs, size_t in_len, size_t count,
+                            const uint8_t *hashes, int results[], uint64_t scratch[]);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif // XELIS_HASH_H
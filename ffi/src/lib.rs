@@ -0,0 +1,294 @@
+// C ABI of the xelis-hash crate, declared in `include/xelis_hash.h`
+// The header is maintained by hand: any change here must be reflected there.
+
+use std::{
+    ffi::{c_char, c_int},
+    ptr,
+    slice,
+};
+
+use xelis_hash::{aes_round, v1, Error, Hash};
+
+pub const XELIS_HASH_SIZE: usize = 32;
+pub const XELIS_HASH_V1_INPUT_SIZE: usize = v1::BYTES_ARRAY_INPUT;
+pub const XELIS_HASH_INPUT_SIZE: usize = 112;
+
+pub const XELIS_HASH_V1: c_int = 1;
+pub const XELIS_HASH_V2: c_int = 2;
+pub const XELIS_HASH_V3: c_int = 3;
+
+pub const XELIS_HASH_OK: c_int = 0;
+pub const XELIS_HASH_ERROR_NULL_POINTER: c_int = -1;
+pub const XELIS_HASH_ERROR_UNSUPPORTED_VERSION: c_int = -2;
+pub const XELIS_HASH_ERROR_INVALID_INPUT_LENGTH: c_int = -3;
+pub const XELIS_HASH_ERROR_ZERO_MODULUS: c_int = -4;
+pub const XELIS_HASH_ERROR_INTERNAL: c_int = -5;
+pub const XELIS_HASH_ERROR_ISQRT_OVERFLOW: c_int = -6;
+
+fn error_code(error: Error) -> c_int {
+    match error {
+        Error::InvalidInputLength(_) => XELIS_HASH_ERROR_INVALID_INPUT_LENGTH,
+        Error::ZeroModulus => XELIS_HASH_ERROR_ZERO_MODULUS,
+        Error::IsqrtOverflow => XELIS_HASH_ERROR_ISQRT_OVERFLOW,
+        Error::UnsupportedVersion(_) => XELIS_HASH_ERROR_UNSUPPORTED_VERSION,
+        _ => XELIS_HASH_ERROR_INTERNAL,
+    }
+}
+
+// Hash the input using the scratchpad words of the version
+// Safety: `scratch` must point to `xelis_hash_scratchpad_size(version)` writable u64s
+unsafe fn hash(version: c_int, input: &[u8], scratch: *mut u64) -> Result<Hash, c_int> {
+    let size = xelis_hash_scratchpad_size(version);
+    if size == 0 {
+        return Err(XELIS_HASH_ERROR_UNSUPPORTED_VERSION);
+    }
+
+    // The size is only known for the existing versions, which fit in a u8
+    xelis_hash::hash_any(version as u8, input, slice::from_raw_parts_mut(scratch, size))
+        .map_err(error_code)
+}
+
+// Hash the input and write it to `output`, returning an error code
+// Safety: the pointers must be valid for their sizes, `input` for `input_len` bytes
+unsafe fn hash_to(version: c_int, input: *const u8, input_len: usize, output: *mut u8, scratch: *mut u64) -> c_int {
+    if input.is_null() || output.is_null() || scratch.is_null() {
+        return XELIS_HASH_ERROR_NULL_POINTER;
+    }
+
+    match hash(version, slice::from_raw_parts(input, input_len), scratch) {
+        Ok(hash) => {
+            ptr::copy_nonoverlapping(hash.as_ptr(), output, XELIS_HASH_SIZE);
+            XELIS_HASH_OK
+        },
+        Err(code) => code,
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn xelis_hash_library_version() -> *const c_char {
+    concat!(env!("CARGO_PKG_VERSION"), "\0").as_ptr().cast()
+}
+
+#[no_mangle]
+pub extern "C" fn xelis_hash_backend() -> *const c_char {
+    let backend: &str = if aes_round::has_hardware_support() { "aes-ni\0" } else { "soft\0" };
+    backend.as_ptr().cast()
+}
+
+#[no_mangle]
+pub extern "C" fn xelis_hash_scratchpad_size(version: c_int) -> usize {
+    u8::try_from(version).ok()
+        .and_then(xelis_hash::scratchpad_size)
+        .unwrap_or(0)
+}
+
+#[no_mangle]
+pub extern "C" fn xelis_hash_scratchpad_new(version: c_int) -> *mut u64 {
+    match xelis_hash_scratchpad_size(version) {
+        0 => ptr::null_mut(),
+        size => Box::into_raw(vec![0u64; size].into_boxed_slice()).cast(),
+    }
+}
+
+/// # Safety
+/// `scratch` must be NULL or allocated by `xelis_hash_scratchpad_new` with the same version
+#[no_mangle]
+pub unsafe extern "C" fn xelis_hash_scratchpad_free(scratch: *mut u64, version: c_int) {
+    let size = xelis_hash_scratchpad_size(version);
+    if !scratch.is_null() && size != 0 {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(scratch, size)));
+    }
+}
+
+/// # Safety
+/// `input` must point to 200 bytes, `output` to 32 writable bytes
+/// and `scratch` to a v1 scratchpad
+#[no_mangle]
+pub unsafe extern "C" fn xelis_hash_v1(input: *const u8, output: *mut u8, scratch: *mut u64) -> c_int {
+    hash_to(XELIS_HASH_V1, input, XELIS_HASH_V1_INPUT_SIZE, output, scratch)
+}
+
+/// # Safety
+/// `input` must point to 112 bytes, `output` to 32 writable bytes
+/// and `scratch` to a v2 scratchpad
+#[no_mangle]
+pub unsafe extern "C" fn xelis_hash_v2(input: *const u8, output: *mut u8, scratch: *mut u64) -> c_int {
+    hash_to(XELIS_HASH_V2, input, XELIS_HASH_INPUT_SIZE, output, scratch)
+}
+
+/// # Safety
+/// `input` must point to 112 bytes, `output` to 32 writable bytes
+/// and `scratch` to a v3 scratchpad
+#[no_mangle]
+pub unsafe extern "C" fn xelis_hash_v3(input: *const u8, output: *mut u8, scratch: *mut u64) -> c_int {
+    hash_to(XELIS_HASH_V3, input, XELIS_HASH_INPUT_SIZE, output, scratch)
+}
+
+/// # Safety
+/// `input` must point to `input_len` bytes, `output` to 32 writable bytes
+/// and `scratch` to a v2 scratchpad
+#[no_mangle]
+pub unsafe extern "C" fn xelis_hash_v2_len(input: *const u8, input_len: usize, output: *mut u8, scratch: *mut u64) -> c_int {
+    hash_to(XELIS_HASH_V2, input, input_len, output, scratch)
+}
+
+/// # Safety
+/// `input` must point to `input_len` bytes, `output` to 32 writable bytes
+/// and `scratch` to a v3 scratchpad
+#[no_mangle]
+pub unsafe extern "C" fn xelis_hash_v3_len(input: *const u8, input_len: usize, output: *mut u8, scratch: *mut u64) -> c_int {
+    hash_to(XELIS_HASH_V3, input, input_len, output, scratch)
+}
+
+/// # Safety
+/// `inputs` must point to `count * input_len` bytes, `hashes` to `count * 32` bytes,
+/// `results` to `count` writable ints and `scratch` to a scratchpad of the version
+#[no_mangle]
+pub unsafe extern "C" fn xelis_hash_verify_batch(
+    version: c_int,
+    inputs: *const u8,
+    input_len: usize,
+    count: usize,
+    hashes: *const u8,
+    results: *mut c_int,
+    scratch: *mut u64,
+) -> c_int {
+    if xelis_hash_scratchpad_size(version) == 0 {
+        return XELIS_HASH_ERROR_UNSUPPORTED_VERSION;
+    }
+
+    if count == 0 {
+        return XELIS_HASH_OK;
+    }
+
+    if inputs.is_null() || hashes.is_null() || results.is_null() || scratch.is_null() {
+        return XELIS_HASH_ERROR_NULL_POINTER;
+    }
+
+    // A slice can't be longer than isize::MAX bytes
+    let total_len = |size: usize| count.checked_mul(size).filter(|&len| len <= isize::MAX as usize);
+    let (Some(inputs_len), Some(hashes_len)) = (total_len(input_len), total_len(XELIS_HASH_SIZE)) else {
+        return XELIS_HASH_ERROR_INVALID_INPUT_LENGTH;
+    };
+
+    let inputs = slice::from_raw_parts(inputs, inputs_len);
+    let hashes = slice::from_raw_parts(hashes, hashes_len);
+    let results = slice::from_raw_parts_mut(results, count);
+    for (i, (expected, result)) in hashes.chunks_exact(XELIS_HASH_SIZE).zip(results).enumerate() {
+        let input = &inputs[i * input_len..(i + 1) * input_len];
+        *result = match hash(version, input, scratch) {
+            Ok(hash) => (hash == expected) as c_int,
+            Err(code) => code,
+        };
+    }
+
+    XELIS_HASH_OK
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CStr;
+    use xelis_hash::{v2, v3};
+    use super::*;
+
+    fn hash_v3(input: &[u8], scratch: *mut u64) -> (c_int, Hash) {
+        let mut output = [0u8; XELIS_HASH_SIZE];
+        let code = unsafe { xelis_hash_v3_len(input.as_ptr(), input.len(), output.as_mut_ptr(), scratch) };
+        (code, output)
+    }
+
+    #[test]
+    fn test_queries() {
+        let version = unsafe { CStr::from_ptr(xelis_hash_library_version()) };
+        assert_eq!(version.to_str().unwrap(), env!("CARGO_PKG_VERSION"));
+
+        let backend = unsafe { CStr::from_ptr(xelis_hash_backend()) };
+        assert!(["aes-ni", "soft"].contains(&backend.to_str().unwrap()));
+
+        assert_eq!(xelis_hash_scratchpad_size(XELIS_HASH_V3), v3::MEMORY_SIZE);
+        assert_eq!(xelis_hash_scratchpad_size(4), 0);
+        assert!(xelis_hash_scratchpad_new(0).is_null());
+    }
+
+    #[test]
+    fn test_hashes() {
+        let input = [0u8; XELIS_HASH_INPUT_SIZE];
+        let mut output = [0u8; XELIS_HASH_SIZE];
+
+        for (version, expected) in [
+            (XELIS_HASH_V2, v2::xelis_hash(&input, &mut v2::ScratchPad::default()).unwrap()),
+            (XELIS_HASH_V3, v3::xelis_hash(&input, &mut v3::ScratchPad::default()).unwrap()),
+        ] {
+            let scratch = xelis_hash_scratchpad_new(version);
+            let hash = if version == XELIS_HASH_V2 { xelis_hash_v2 } else { xelis_hash_v3 };
+            unsafe {
+                assert_eq!(hash(input.as_ptr(), output.as_mut_ptr(), scratch), XELIS_HASH_OK);
+                xelis_hash_scratchpad_free(scratch, version);
+            }
+            assert_eq!(output, expected);
+        }
+
+        let input = [0u8; XELIS_HASH_V1_INPUT_SIZE];
+        let mut aligned = v1::AlignedInput::default();
+        let expected = v1::xelis_hash(aligned.as_mut_slice().unwrap(), &mut v1::ScratchPad::default()).unwrap();
+        let scratch = xelis_hash_scratchpad_new(XELIS_HASH_V1);
+        unsafe {
+            assert_eq!(xelis_hash_v1(input.as_ptr(), output.as_mut_ptr(), scratch), XELIS_HASH_OK);
+            xelis_hash_scratchpad_free(scratch, XELIS_HASH_V1);
+        }
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_errors() {
+        let scratch = xelis_hash_scratchpad_new(XELIS_HASH_V3);
+
+        assert_eq!(hash_v3(&[0u8; 150], scratch).0, XELIS_HASH_ERROR_INVALID_INPUT_LENGTH);
+        assert_eq!(hash_v3(&[], ptr::null_mut()).0, XELIS_HASH_ERROR_NULL_POINTER);
+
+        let mut output = [0u8; XELIS_HASH_SIZE];
+        let code = unsafe { xelis_hash_v3(ptr::null(), output.as_mut_ptr(), scratch) };
+        assert_eq!(code, XELIS_HASH_ERROR_NULL_POINTER);
+
+        unsafe { xelis_hash_scratchpad_free(scratch, XELIS_HASH_V3) };
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let scratch = xelis_hash_scratchpad_new(XELIS_HASH_V3);
+
+        let inputs = [[0u8; 128], [1u8; 128]];
+        let mut hashes = inputs.map(|input| hash_v3(&input, scratch).1);
+        hashes[1][0] ^= 1;
+
+        let mut results = [c_int::MAX; 2];
+        let code = unsafe { xelis_hash_verify_batch(XELIS_HASH_V3, inputs.as_flattened().as_ptr(), 128, 2, hashes.as_flattened().as_ptr(), results.as_mut_ptr(), scratch) };
+        assert_eq!(code, XELIS_HASH_OK);
+        assert_eq!(results, [1, 0]);
+
+        let inputs = [[0u8; 150], [1u8; 150]];
+        let code = unsafe { xelis_hash_verify_batch(XELIS_HASH_V3, inputs.as_flattened().as_ptr(), 150, 2, hashes.as_flattened().as_ptr(), results.as_mut_ptr(), scratch) };
+        assert_eq!(code, XELIS_HASH_OK);
+        assert_eq!(results, [XELIS_HASH_ERROR_INVALID_INPUT_LENGTH; 2]);
+
+        let code = unsafe { xelis_hash_verify_batch(4, inputs.as_flattened().as_ptr(), 150, 2, hashes.as_flattened().as_ptr(), results.as_mut_ptr(), scratch) };
+        assert_eq!(code, XELIS_HASH_ERROR_UNSUPPORTED_VERSION);
+
+        unsafe { xelis_hash_scratchpad_free(scratch, XELIS_HASH_V3) };
+    }
+
+    #[test]
+    fn test_verify_batch_count_overflow() {
+        let scratch = xelis_hash_scratchpad_new(XELIS_HASH_V3);
+        let mut results = [c_int::MAX; 1];
+
+        // The inputs are empty but the hashes length overflows
+        for count in [usize::MAX, usize::MAX / XELIS_HASH_SIZE + 1, isize::MAX as usize / XELIS_HASH_SIZE + 1] {
+            let code = unsafe { xelis_hash_verify_batch(XELIS_HASH_V3, [].as_ptr(), 0, count, [0u8; XELIS_HASH_SIZE].as_ptr(), results.as_mut_ptr(), scratch) };
+            assert_eq!(code, XELIS_HASH_ERROR_INVALID_INPUT_LENGTH, "count: {}", count);
+        }
+        assert_eq!(results, [c_int::MAX]);
+
+        unsafe { xelis_hash_scratchpad_free(scratch, XELIS_HASH_V3) };
+    }
+}
@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xelis_hash::v2::combine_u64;
+
+fuzz_target!(|data: (u64, u64)| {
+    let (high, low) = data;
+    let combined = combine_u64(high, low);
+
+    // Reference: concatenation of the big endian bytes
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&high.to_be_bytes());
+    bytes[8..].copy_from_slice(&low.to_be_bytes());
+    assert_eq!(combined, u128::from_be_bytes(bytes));
+
+    assert_eq!((combined >> 64) as u64, high);
+    assert_eq!(combined as u64, low);
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xelis_hash::math::{div_rem_u128_by_u64, div_u128_to_u64, rem_u128_to_u64};
+
+fuzz_target!(|data: (u128, u128)| {
+    let (n, d) = data;
+    if d == 0 {
+        return;
+    }
+
+    // Stage 3 truncates both results to u64
+    assert_eq!(div_u128_to_u64(n, d), (n / d) as u64);
+    assert_eq!(rem_u128_to_u64(n, d), (n % d) as u64);
+
+    let divisor = d as u64;
+    if divisor != 0 {
+        let (quotient, remainder) = div_rem_u128_by_u64(n, divisor);
+        assert_eq!(quotient, (n / divisor as u128) as u64);
+        assert_eq!(remainder, (n % divisor as u128) as u64);
+    }
+});
@@ -0,0 +1,30 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xelis_hash::{math, v3};
+
+// Original floating-point v3 implementation, as deployed (wrapping in release)
+fn legacy_isqrt(n: u64) -> u64 {
+    if n < 2 {
+        return n;
+    }
+
+    let approx = (n as f64).sqrt() as u64;
+    if approx.wrapping_mul(approx) > n {
+        approx.wrapping_sub(1)
+    } else if approx.wrapping_add(1).wrapping_mul(approx.wrapping_add(1)) <= n {
+        approx.wrapping_add(1)
+    } else {
+        approx
+    }
+}
+
+fuzz_target!(|n: u64| {
+    // Exact integer square root
+    let root = math::isqrt(n);
+    assert!(root * root <= n);
+    assert!(root + 1 == 1 << 32 || (root + 1) * (root + 1) > n);
+
+    // v3 consensus isqrt must match the deployed implementation
+    assert_eq!(v3::isqrt(n), legacy_isqrt(n));
+});
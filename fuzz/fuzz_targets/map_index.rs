@@ -0,0 +1,21 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xelis_hash::v3::{map_index, BUFFER_SIZE};
+
+// Reference: finalizer then multiply-high computed on 32 bits limbs
+fn reference_map_index(mut x: u64) -> usize {
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51afd7ed558ccd);
+
+    // BUFFER_SIZE fits in 32 bits
+    let low = (x & 0xFFFF_FFFF) * BUFFER_SIZE as u64;
+    let high = (x >> 32) * BUFFER_SIZE as u64 + (low >> 32);
+    (high >> 32) as usize
+}
+
+fuzz_target!(|x: u64| {
+    let index = map_index(x);
+    assert!(index < BUFFER_SIZE);
+    assert_eq!(index, reference_map_index(x));
+});
@@ -0,0 +1,29 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xelis_hash::math::modular_power;
+
+// Plain square-and-multiply using u128 remainders
+fn naive_modular_power(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
+    let mut result: u64 = 1;
+    base %= modulus;
+
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = ((result as u128 * base as u128) % modulus as u128) as u64;
+        }
+
+        base = ((base as u128 * base as u128) % modulus as u128) as u64;
+        exp /= 2;
+    }
+
+    result
+}
+
+fuzz_target!(|data: (u64, u64, u64)| {
+    let (base, exp, modulus) = data;
+    match modular_power(base, exp, modulus) {
+        Some(result) => assert_eq!(result, naive_modular_power(base, exp, modulus)),
+        None => assert_eq!(modulus, 0),
+    }
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xelis_hash::v3::pick_half;
+
+// Reference: MurmurHash3 fmix64 finalizer, bit 58 selects the half
+fn reference_pick_half(mut seed: u64) -> bool {
+    seed ^= seed >> 55;
+    seed = seed.wrapping_mul(0xff51afd7ed558ccd);
+    seed ^= seed >> 32;
+    seed = seed.wrapping_mul(0xc4ceb9fe1a85ec53);
+    seed ^= seed >> 15;
+
+    (seed >> 58) & 1 == 1
+}
+
+fuzz_target!(|seed: u64| {
+    assert_eq!(pick_half(seed), reference_pick_half(seed));
+});
@@ -0,0 +1,32 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xelis_hash::{reference, v1::{xelis_hash, AlignedInput, ScratchPad, BYTES_ARRAY_INPUT}};
+
+// v1 only accepts a fixed size input: arbitrary data is truncated or zero padded
+fn pad(data: &[u8]) -> [u8; BYTES_ARRAY_INPUT] {
+    let mut input = [0u8; BYTES_ARRAY_INPUT];
+    let len = data.len().min(BYTES_ARRAY_INPUT);
+    input[..len].copy_from_slice(&data[..len]);
+    input
+}
+
+fn hash(data: &[u8], scratch_pad: &mut ScratchPad) -> [u8; 32] {
+    let mut input = AlignedInput::default();
+    let slice = input.as_mut_slice().unwrap();
+    slice.copy_from_slice(&pad(data));
+
+    xelis_hash(slice, scratch_pad).unwrap()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut scratch_pad = ScratchPad::default();
+    let first = hash(data, &mut scratch_pad);
+
+    // Dirty the scratch pad with another input before hashing again
+    hash(b"xelis-hashing-algorithm", &mut scratch_pad);
+    let second = hash(data, &mut scratch_pad);
+
+    assert_eq!(first, second);
+    assert_eq!(first, reference::v1::xelis_hash(&pad(data)));
+});
@@ -0,0 +1,31 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use xelis_hash::{reference, v3::{xelis_hash, ScratchPad}, Error};
+
+fuzz_target!(|data: &[u8]| {
+    let mut scratch_pad = ScratchPad::default();
+    let first = xelis_hash(data, &mut scratch_pad);
+
+    // Dirty the scratch pad with another input before hashing again
+    let _ = xelis_hash(&[0u8; 112], &mut scratch_pad);
+    let second = xelis_hash(data, &mut scratch_pad);
+
+    // The reference implementation must agree on both the hash and the error
+    let expected = reference::v3::xelis_hash(data);
+
+    match (first, second, expected) {
+        (Ok(a), Ok(b), Ok(expected)) => {
+            assert_eq!(a, b);
+            assert_eq!(a, expected);
+        },
+        (Err(Error::InvalidInputLength(a)), Err(Error::InvalidInputLength(b)), Err(Error::InvalidInputLength(expected))) => {
+            assert_eq!(a, data.len());
+            assert_eq!(b, data.len());
+            assert_eq!(expected, data.len());
+        },
+        // A zero modulus has no valid hash, but must be reported consistently
+        (Err(Error::ZeroModulus), Err(Error::ZeroModulus), Err(Error::ZeroModulus)) => {},
+        (a, b, expected) => panic!("unexpected results: {:?} / {:?}, expected {:?}", a, b, expected),
+    }
+});
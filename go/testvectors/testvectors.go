@@ -0,0 +1,86 @@
+package testvectors
+
+import (
+	"bytes"
+	"encoding/hex"
+	"encoding/json"
+	"os"
+	"path/filepath"
+	"runtime"
+	"testing"
+
+	"github.com/xelis-project/xelis-hash/go/hash"
+)
+
+// Format is the supported version of the test vectors file format
+const Format = 1
+
+type Vector struct {
+	Name   string
+	Input  []byte
+	Hash   hash.Hash
+	Stages map[string]hash.Hash
+	// Error is set instead of Hash when the input has no valid hash
+	Error string
+}
+
+type rawVector struct {
+	Name   string            `json:"name"`
+	Input  string            `json:"input"`
+	Repeat int               `json:"repeat"`
+	Hash   string            `json:"hash"`
+	Stages map[string]string `json:"stages"`
+	Error  string            `json:"error"`
+}
+
+type rawFile struct {
+	Format  int         `json:"format"`
+	Version string      `json:"version"`
+	Vectors []rawVector `json:"vectors"`
+}
+
+// Load reads the shared test vectors of the given algorithm version
+func Load(t *testing.T, version string) []Vector {
+	t.Helper()
+
+	_, file, _, _ := runtime.Caller(0)
+	path := filepath.Join(filepath.Dir(file), "..", "..", "test-vectors", version+".json")
+	data, err := os.ReadFile(path)
+	if err != nil {
+		t.Fatalf("Failed to read the test vectors: %v", err)
+	}
+
+	var raw rawFile
+	if err := json.Unmarshal(data, &raw); err != nil {
+		t.Fatalf("Invalid test vectors file: %v", err)
+	}
+	if raw.Format != Format || raw.Version != version {
+		t.Fatalf("Unsupported test vectors file: format %d, version %s", raw.Format, raw.Version)
+	}
+
+	vectors := make([]Vector, 0, len(raw.Vectors))
+	for _, v := range raw.Vectors {
+		input, err := hex.DecodeString(v.Input)
+		if err != nil {
+			t.Fatalf("%s: invalid input: %v", v.Name, err)
+		}
+		if v.Repeat > 1 {
+			input = bytes.Repeat(input, v.Repeat)
+		}
+
+		vector := Vector{Name: v.Name, Input: input, Error: v.Error, Stages: map[string]hash.Hash{}}
+		if v.Error == "" {
+			if vector.Hash, err = hash.FromString(v.Hash); err != nil {
+				t.Fatalf("%s: invalid hash: %v", v.Name, err)
+			}
+		}
+		for stage, digest := range v.Stages {
+			if vector.Stages[stage], err = hash.FromString(digest); err != nil {
+				t.Fatalf("%s: invalid %s digest: %v", v.Name, stage, err)
+			}
+		}
+		vectors = append(vectors, vector)
+	}
+
+	return vectors
+}
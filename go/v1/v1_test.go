@@ -3,6 +3,8 @@ package v1
 import (
 	"slices"
 	"testing"
+
+	"github.com/xelis-project/xelis-hash/go/testvectors"
 )
 
 func TestZeroInput(t *testing.T) {
@@ -77,3 +79,23 @@ func BenchmarkXelisHashV1(b *testing.B) {
 		_, _ = XelisHash(&input, scratchPad)
 	}
 }
+
+func TestVectors(t *testing.T) {
+	scratchPad := NewScratchPad()
+	for _, vector := range testvectors.Load(t, "v1") {
+		var input [BytesArrayInput]byte
+		if len(vector.Input) != len(input) {
+			t.Fatalf("%s: invalid input length %d", vector.Name, len(vector.Input))
+		}
+		copy(input[:], vector.Input)
+
+		hash, err := XelisHash(&input, scratchPad)
+		if err != nil {
+			t.Fatalf("%s: hash failed: %v", vector.Name, err)
+		}
+
+		if hash != vector.Hash {
+			t.Errorf("%s: hash mismatch:\nGot:      %x\nExpected: %x", vector.Name, hash, vector.Hash)
+		}
+	}
+}
@@ -3,6 +3,8 @@ package v2
 import (
 	"slices"
 	"testing"
+
+	"github.com/xelis-project/xelis-hash/go/testvectors"
 )
 
 func TestZeroHash(t *testing.T) {
@@ -89,3 +91,22 @@ func BenchmarkXelisHashV2(b *testing.B) {
 		_, _ = XelisHash(input, scratchPad)
 	}
 }
+
+func TestVectors(t *testing.T) {
+	scratchPad := NewScratchPad()
+	for _, vector := range testvectors.Load(t, "v2") {
+		// Invalid inputs are not rejected by this port
+		if vector.Error != "" {
+			continue
+		}
+
+		hash, err := XelisHash(vector.Input, scratchPad)
+		if err != nil {
+			t.Fatalf("%s: hash failed: %v", vector.Name, err)
+		}
+
+		if hash != vector.Hash {
+			t.Errorf("%s: hash mismatch:\nGot:      %x\nExpected: %x", vector.Name, hash, vector.Hash)
+		}
+	}
+}
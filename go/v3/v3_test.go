@@ -3,6 +3,8 @@ package v3
 import (
 	"slices"
 	"testing"
+
+	"github.com/xelis-project/xelis-hash/go/testvectors"
 )
 
 func TestZeroHash(t *testing.T) {
@@ -144,3 +146,22 @@ func BenchmarkPickHalf(b *testing.B) {
 		_ = pickHalf(uint64(i))
 	}
 }
+
+func TestVectors(t *testing.T) {
+	scratchPad := NewScratchPad()
+	for _, vector := range testvectors.Load(t, "v3") {
+		// Invalid inputs are not rejected by this port
+		if vector.Error != "" {
+			continue
+		}
+
+		hash, err := XelisHash(vector.Input, scratchPad)
+		if err != nil {
+			t.Fatalf("%s: hash failed: %v", vector.Name, err)
+		}
+
+		if hash != vector.Hash {
+			t.Errorf("%s: hash mismatch:\nGot:      %x\nExpected: %x", vector.Name, hash, vector.Hash)
+		}
+	}
+}
@@ -0,0 +1,21 @@
+// Build the addon with cargo and copy it next to `index.js` as `xelis_hash.node`
+// Usage: node build.js [--release]
+
+const { execFileSync } = require('node:child_process');
+const fs = require('node:fs');
+const path = require('node:path');
+
+const release = process.argv.includes('--release');
+const args = ['build', '-p', 'xelis-hash-node'];
+if (release) {
+  args.push('--release');
+}
+execFileSync('cargo', args, { cwd: __dirname, stdio: 'inherit' });
+
+const library = {
+  win32: 'xelis_hash_node.dll',
+  darwin: 'libxelis_hash_node.dylib',
+}[process.platform] ?? 'libxelis_hash_node.so';
+
+const targetDir = process.env.CARGO_TARGET_DIR ?? path.join(__dirname, '..', 'target');
+fs.copyFileSync(path.join(targetDir, release ? 'release' : 'debug', library), path.join(__dirname, 'xelis_hash.node'));
@@ -0,0 +1,29 @@
+/** Size of a hash in bytes */
+export const HASH_SIZE: number
+/** Highest target, as a 256 bits integer */
+export const MAX_TARGET: bigint
+
+/** Result of a share verification */
+export interface Share {
+  /** Hash of the input, null if the input has no valid hash */
+  hash: Buffer | null
+  /** Whether the hash is lower than or equal to the target */
+  valid: boolean
+}
+
+/** Hash the input using the v2 algorithm on the libuv thread pool */
+export function hashV2(input: Buffer): Promise<Buffer>
+/** Hash the input using the v3 algorithm on the libuv thread pool */
+export function hashV3(input: Buffer): Promise<Buffer>
+/** Hash the input using the v2 algorithm on the calling thread */
+export function hashV2Sync(input: Buffer): Buffer
+/** Hash the input using the v3 algorithm on the calling thread */
+export function hashV3Sync(input: Buffer): Buffer
+
+/** Check that the hash of the input meets the big-endian target on the libuv thread pool */
+export function verifyShare(version: 2 | 3, input: Buffer, target: Buffer): Promise<Share>
+/** Check that the hash of the input meets the big-endian target on the calling thread */
+export function verifyShareSync(version: 2 | 3, input: Buffer, target: Buffer): Share
+
+/** Target of a difficulty, as a big-endian buffer to pass to `verifyShare` */
+export function difficultyToTarget(difficulty: bigint | number): Buffer
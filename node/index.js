@@ -0,0 +1,22 @@
+'use strict';
+
+const native = require('./xelis_hash.node');
+
+// Hashes and targets are compared as big-endian 256 bits integers
+const MAX_TARGET = (1n << 256n) - 1n;
+
+// Target of a difficulty, as a big-endian buffer to pass to `verifyShare`
+function difficultyToTarget(difficulty) {
+  difficulty = BigInt(difficulty);
+  if (difficulty <= 0n) {
+    throw new RangeError('difficulty must be positive');
+  }
+
+  return Buffer.from((MAX_TARGET / difficulty).toString(16).padStart(2 * native.HASH_SIZE, '0'), 'hex');
+}
+
+module.exports = {
+  ...native,
+  MAX_TARGET,
+  difficultyToTarget,
+};
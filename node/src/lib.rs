@@ -0,0 +1,151 @@
+// Native part of the `xelis-hash` Node.js package
+// Each thread reuses its own scratchpads: the main thread for the sync functions
+// and the libuv pool workers for the async ones, so they are allocated once per worker.
+
+use std::cell::RefCell;
+
+use napi::{bindgen_prelude::*, Task};
+use napi_derive::napi;
+
+use xelis_hash::{v2, v3, Error as HashError, Hash};
+
+#[napi]
+pub const HASH_SIZE: u32 = 32;
+
+thread_local! {
+    static V2_SCRATCH_PAD: RefCell<v2::ScratchPad> = RefCell::new(v2::ScratchPad::default());
+    static V3_SCRATCH_PAD: RefCell<v3::ScratchPad> = RefCell::new(v3::ScratchPad::default());
+}
+
+#[derive(Clone, Copy)]
+enum Version {
+    V2,
+    V3,
+}
+
+impl Version {
+    fn new(version: u32) -> Result<Self> {
+        match version {
+            2 => Ok(Self::V2),
+            3 => Ok(Self::V3),
+            _ => Err(Error::new(Status::InvalidArg, format!("unsupported version: {}", version))),
+        }
+    }
+
+    // Hash the input using the scratchpad of the current thread
+    fn hash(self, input: &[u8]) -> std::result::Result<Hash, HashError> {
+        match self {
+            Self::V2 => V2_SCRATCH_PAD.with_borrow_mut(|scratch_pad| v2::xelis_hash(input, scratch_pad)),
+            Self::V3 => V3_SCRATCH_PAD.with_borrow_mut(|scratch_pad| v3::xelis_hash(input, scratch_pad)),
+        }
+    }
+}
+
+// Inputs without a valid hash are rejected as invalid arguments
+fn hash_error(e: HashError) -> Error {
+    Error::new(Status::InvalidArg, e.to_string())
+}
+
+pub struct HashTask {
+    version: Version,
+    input: Vec<u8>,
+}
+
+impl Task for HashTask {
+    type Output = Hash;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> Result<Hash> {
+        self.version.hash(&self.input).map_err(hash_error)
+    }
+
+    fn resolve(&mut self, _: Env, hash: Hash) -> Result<Buffer> {
+        Ok(hash.to_vec().into())
+    }
+}
+
+// Hash the input using the v2 algorithm on the calling thread
+#[napi(js_name = "hashV2Sync")]
+pub fn hash_v2_sync(input: Buffer) -> Result<Buffer> {
+    Ok(Version::V2.hash(&input).map_err(hash_error)?.to_vec().into())
+}
+
+// Hash the input using the v3 algorithm on the calling thread
+#[napi(js_name = "hashV3Sync")]
+pub fn hash_v3_sync(input: Buffer) -> Result<Buffer> {
+    Ok(Version::V3.hash(&input).map_err(hash_error)?.to_vec().into())
+}
+
+// Hash the input using the v2 algorithm on the libuv thread pool
+#[napi(js_name = "hashV2", ts_return_type = "Promise<Buffer>")]
+pub fn hash_v2(input: Buffer) -> AsyncTask<HashTask> {
+    AsyncTask::new(HashTask { version: Version::V2, input: input.to_vec() })
+}
+
+// Hash the input using the v3 algorithm on the libuv thread pool
+#[napi(js_name = "hashV3", ts_return_type = "Promise<Buffer>")]
+pub fn hash_v3(input: Buffer) -> AsyncTask<HashTask> {
+    AsyncTask::new(HashTask { version: Version::V3, input: input.to_vec() })
+}
+
+// Result of a share verification
+#[napi(object)]
+pub struct Share {
+    // Hash of the input, null if the input has no valid hash
+    pub hash: Either<Buffer, Null>,
+    // Whether the hash is lower than or equal to the target
+    pub valid: bool,
+}
+
+impl Share {
+    fn new(hash: Option<Hash>, target: &Hash) -> Self {
+        // Hashes and targets are compared as big-endian 256 bits integers
+        Self {
+            valid: hash.is_some_and(|hash| hash <= *target),
+            hash: hash.map_or(Either::B(Null), |hash| Either::A(hash.to_vec().into())),
+        }
+    }
+}
+
+fn target(target: &[u8]) -> Result<Hash> {
+    target.try_into()
+        .map_err(|_| Error::new(Status::InvalidArg, format!("expected a {} bytes target, got {} bytes", HASH_SIZE, target.len())))
+}
+
+pub struct ShareTask {
+    version: Version,
+    input: Vec<u8>,
+    target: Hash,
+}
+
+impl Task for ShareTask {
+    type Output = Option<Hash>;
+    type JsValue = Share;
+
+    fn compute(&mut self) -> Result<Option<Hash>> {
+        Ok(self.version.hash(&self.input).ok())
+    }
+
+    fn resolve(&mut self, _: Env, hash: Option<Hash>) -> Result<Share> {
+        Ok(Share::new(hash, &self.target))
+    }
+}
+
+// Check that the hash of the input meets the big-endian target on the calling thread
+// An input that has no valid hash is never valid
+#[napi(js_name = "verifyShareSync")]
+pub fn verify_share_sync(version: u32, input: Buffer, target: Buffer) -> Result<Share> {
+    let version = Version::new(version)?;
+    let target = self::target(&target)?;
+    Ok(Share::new(version.hash(&input).ok(), &target))
+}
+
+// Check that the hash of the input meets the big-endian target on the libuv thread pool
+#[napi(js_name = "verifyShare", ts_return_type = "Promise<Share>")]
+pub fn verify_share(version: u32, input: Buffer, target: Buffer) -> Result<AsyncTask<ShareTask>> {
+    Ok(AsyncTask::new(ShareTask {
+        version: Version::new(version)?,
+        input: input.to_vec(),
+        target: self::target(&target)?,
+    }))
+}
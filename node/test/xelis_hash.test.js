@@ -0,0 +1,81 @@
+'use strict';
+
+const assert = require('node:assert');
+const fs = require('node:fs');
+const path = require('node:path');
+const { describe, it } = require('node:test');
+
+const xelisHash = require('..');
+
+const VECTORS_DIR = path.join(__dirname, '..', '..', 'test-vectors');
+const HASHES = {
+  2: [xelisHash.hashV2, xelisHash.hashV2Sync],
+  3: [xelisHash.hashV3, xelisHash.hashV3Sync],
+};
+
+function loadVectors(version) {
+  const data = JSON.parse(fs.readFileSync(path.join(VECTORS_DIR, `v${version}.json`), 'utf8'));
+  assert.strictEqual(data.format, 1);
+  assert.strictEqual(data.version, `v${version}`);
+  return data.vectors.map((vector) => ({
+    ...vector,
+    input: Buffer.from(vector.input.repeat(vector.repeat ?? 1), 'hex'),
+  }));
+}
+
+for (const [version, [hash, hashSync]] of Object.entries(HASHES)) {
+  describe(`v${version} test vectors`, () => {
+    for (const vector of loadVectors(version)) {
+      it(vector.name, async () => {
+        if (vector.error) {
+          assert.throws(() => hashSync(vector.input));
+          await assert.rejects(hash(vector.input));
+        } else {
+          const expected = Buffer.from(vector.hash, 'hex');
+          assert.deepStrictEqual(hashSync(vector.input), expected);
+          assert.deepStrictEqual(await hash(vector.input), expected);
+        }
+      });
+    }
+  });
+}
+
+describe('async', () => {
+  it('hashes concurrently on the thread pool', async () => {
+    const inputs = Array.from({ length: 8 }, (_, i) => Buffer.alloc(112, i));
+    const hashes = await Promise.all(inputs.map((input) => xelisHash.hashV3(input)));
+    inputs.forEach((input, i) => assert.deepStrictEqual(hashes[i], xelisHash.hashV3Sync(input)));
+  });
+});
+
+describe('verifyShare', () => {
+  const input = Buffer.alloc(112);
+  const hash = xelisHash.hashV3Sync(input);
+
+  it('compares the hash to the target', async () => {
+    for (const verifyShare of [xelisHash.verifyShare, xelisHash.verifyShareSync]) {
+      assert.deepStrictEqual(await verifyShare(3, input, hash), { hash, valid: true });
+      assert.deepStrictEqual(await verifyShare(3, input, Buffer.alloc(32, 0xff)), { hash, valid: true });
+      assert.deepStrictEqual(await verifyShare(3, input, Buffer.alloc(32)), { hash, valid: false });
+    }
+  });
+
+  it('rejects inputs without a valid hash', async () => {
+    const share = await xelisHash.verifyShare(3, Buffer.alloc(150), Buffer.alloc(32, 0xff));
+    assert.deepStrictEqual(share, { hash: null, valid: false });
+  });
+
+  it('throws on invalid arguments', () => {
+    assert.throws(() => xelisHash.verifyShare(1, input, hash));
+    assert.throws(() => xelisHash.verifyShare(3, input, hash.subarray(1)));
+    assert.throws(() => xelisHash.verifyShareSync(3, input, hash.subarray(1)));
+  });
+});
+
+describe('difficultyToTarget', () => {
+  it('divides the max target', () => {
+    assert.deepStrictEqual(xelisHash.difficultyToTarget(1), Buffer.alloc(32, 0xff));
+    assert.deepStrictEqual(xelisHash.difficultyToTarget(1n << 8n), Buffer.concat([Buffer.alloc(1), Buffer.alloc(31, 0xff)]));
+    assert.throws(() => xelisHash.difficultyToTarget(0));
+  });
+});
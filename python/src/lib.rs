@@ -0,0 +1,141 @@
+// Native part of the `xelis_hash` Python package
+// The hashes are computed without holding the GIL so other Python threads can run.
+
+use pyo3::{
+    exceptions::PyValueError,
+    prelude::*,
+    types::{PyBytes, PyList},
+};
+
+use xelis_hash::{v1, Error, Hash};
+
+// Size of a hash in bytes
+const HASH_SIZE: usize = 32;
+
+// Scratchpad words of an algorithm version
+struct Inner {
+    version: u8,
+    words: Box<[u64]>,
+}
+
+impl Inner {
+    fn new(version: u8) -> PyResult<Self> {
+        let size = xelis_hash::scratchpad_size(version)
+            .ok_or_else(|| PyValueError::new_err(format!("unsupported version: {}", version)))?;
+
+        Ok(Self { version, words: vec![0; size].into_boxed_slice() })
+    }
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn hash(&mut self, input: &[u8]) -> Result<Hash, Error> {
+        xelis_hash::hash_any(self.version, input, &mut self.words)
+    }
+}
+
+// Scratchpad of an algorithm version, reused between hashes to not allocate it each time
+#[pyclass(module = "xelis_hash")]
+struct ScratchPad {
+    inner: Inner,
+}
+
+#[pymethods]
+impl ScratchPad {
+    #[new]
+    fn new(version: u8) -> PyResult<Self> {
+        Ok(Self { inner: Inner::new(version)? })
+    }
+
+    #[getter]
+    fn version(&self) -> u8 {
+        self.inner.version()
+    }
+}
+
+// Run `f` with the given scratchpad, or a new one if none is given, without holding the GIL
+fn with_scratch_pad<T: Send>(py: Python<'_>, version: u8, scratch_pad: Option<&Bound<'_, ScratchPad>>, f: impl Send + FnOnce(&mut Inner) -> T) -> PyResult<T> {
+    match scratch_pad {
+        Some(scratch_pad) => {
+            let mut scratch_pad = scratch_pad.try_borrow_mut()?;
+            if scratch_pad.inner.version() != version {
+                return Err(PyValueError::new_err(format!("expected a v{} scratchpad, got a v{} one", version, scratch_pad.inner.version())));
+            }
+
+            let inner = &mut scratch_pad.inner;
+            Ok(py.allow_threads(|| f(inner)))
+        },
+        None => {
+            let mut inner = Inner::new(version)?;
+            Ok(py.allow_threads(|| f(&mut inner)))
+        },
+    }
+}
+
+fn hash<'py>(py: Python<'py>, version: u8, input: &[u8], scratch_pad: Option<&Bound<'py, ScratchPad>>) -> PyResult<Bound<'py, PyBytes>> {
+    let hash = with_scratch_pad(py, version, scratch_pad, |inner| inner.hash(input))?
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    Ok(PyBytes::new(py, &hash))
+}
+
+// Hash a 200 bytes input using the v1 algorithm
+#[pyfunction]
+#[pyo3(signature = (input, scratch_pad = None))]
+fn hash_v1<'py>(py: Python<'py>, input: &[u8], scratch_pad: Option<&Bound<'py, ScratchPad>>) -> PyResult<Bound<'py, PyBytes>> {
+    hash(py, 1, input, scratch_pad)
+}
+
+// Hash the input using the v2 algorithm
+#[pyfunction]
+#[pyo3(signature = (input, scratch_pad = None))]
+fn hash_v2<'py>(py: Python<'py>, input: &[u8], scratch_pad: Option<&Bound<'py, ScratchPad>>) -> PyResult<Bound<'py, PyBytes>> {
+    hash(py, 2, input, scratch_pad)
+}
+
+// Hash the input using the v3 algorithm
+#[pyfunction]
+#[pyo3(signature = (input, scratch_pad = None))]
+fn hash_v3<'py>(py: Python<'py>, input: &[u8], scratch_pad: Option<&Bound<'py, ScratchPad>>) -> PyResult<Bound<'py, PyBytes>> {
+    hash(py, 3, input, scratch_pad)
+}
+
+// Check that the input hashes to the expected hash
+// An input that has no valid hash is never valid
+#[pyfunction]
+#[pyo3(signature = (version, input, expected, scratch_pad = None))]
+fn verify(py: Python<'_>, version: u8, input: &[u8], expected: &[u8], scratch_pad: Option<&Bound<'_, ScratchPad>>) -> PyResult<bool> {
+    if expected.len() != HASH_SIZE {
+        return Err(PyValueError::new_err(format!("expected a {} bytes hash, got {} bytes", HASH_SIZE, expected.len())));
+    }
+
+    with_scratch_pad(py, version, scratch_pad, |inner| inner.hash(input).is_ok_and(|hash| hash == expected))
+}
+
+// Hash all the inputs using the same scratchpad
+// The result of an input that has no valid hash is None
+#[pyfunction]
+#[pyo3(signature = (version, inputs, scratch_pad = None))]
+fn hash_batch<'py>(py: Python<'py>, version: u8, inputs: Vec<Vec<u8>>, scratch_pad: Option<&Bound<'py, ScratchPad>>) -> PyResult<Bound<'py, PyList>> {
+    let hashes = with_scratch_pad(py, version, scratch_pad, |inner| {
+        inputs.iter()
+            .map(|input| inner.hash(input).ok())
+            .collect::<Vec<_>>()
+    })?;
+
+    PyList::new(py, hashes.into_iter().map(|hash| hash.map(|hash| PyBytes::new(py, &hash))))
+}
+
+#[pymodule]
+fn _native(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("HASH_SIZE", HASH_SIZE)?;
+    m.add("V1_INPUT_SIZE", v1::BYTES_ARRAY_INPUT)?;
+    m.add_class::<ScratchPad>()?;
+    m.add_function(wrap_pyfunction!(hash_v1, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_v2, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_v3, m)?)?;
+    m.add_function(wrap_pyfunction!(verify, m)?)?;
+    m.add_function(wrap_pyfunction!(hash_batch, m)?)?;
+    Ok(())
+}
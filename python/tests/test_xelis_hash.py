@@ -0,0 +1,112 @@
+import json
+import threading
+from pathlib import Path
+
+import pytest
+
+import xelis_hash
+
+VECTORS_DIR = Path(__file__).resolve().parents[2] / "test-vectors"
+HASHES = {1: xelis_hash.hash_v1, 2: xelis_hash.hash_v2, 3: xelis_hash.hash_v3}
+
+
+def load_vectors(version):
+    data = json.loads((VECTORS_DIR / f"v{version}.json").read_text())
+    assert data["format"] == 1
+    assert data["version"] == f"v{version}"
+    for vector in data["vectors"]:
+        vector["input"] = bytes.fromhex(vector["input"]) * vector.get("repeat", 1)
+        yield vector
+
+
+VECTORS = [(version, vector) for version in HASHES for vector in load_vectors(version)]
+
+
+@pytest.mark.parametrize(
+    "version, vector", VECTORS, ids=[f"v{version}-{vector['name']}" for version, vector in VECTORS]
+)
+def test_vectors(version, vector):
+    hash = HASHES[version]
+    if "error" in vector:
+        with pytest.raises(ValueError):
+            hash(vector["input"])
+    else:
+        expected = bytes.fromhex(vector["hash"])
+        assert hash(vector["input"]) == expected
+        assert xelis_hash.verify(version, vector["input"], expected)
+
+
+@pytest.mark.parametrize("version", HASHES)
+def test_batch(version):
+    vectors = list(load_vectors(version))
+    scratch_pad = xelis_hash.ScratchPad(version)
+    hashes = xelis_hash.hash_batch(version, [vector["input"] for vector in vectors], scratch_pad)
+    for vector, hash in zip(vectors, hashes):
+        if "error" in vector:
+            assert hash is None
+        else:
+            assert hash == bytes.fromhex(vector["hash"])
+
+
+def test_verify():
+    input = bytes(112)
+    hash = xelis_hash.hash_v3(input)
+    assert xelis_hash.verify(3, input, hash)
+    assert not xelis_hash.verify(3, input, bytes(32))
+    # No valid hash
+    assert not xelis_hash.verify(3, bytes(150), hash)
+
+    with pytest.raises(ValueError):
+        xelis_hash.verify(3, input, hash[:31])
+    with pytest.raises(ValueError):
+        xelis_hash.verify(4, input, hash)
+
+
+def test_scratch_pad():
+    scratch_pad = xelis_hash.ScratchPad(2)
+    assert scratch_pad.version == 2
+
+    input = bytes(range(112))
+    expected = xelis_hash.hash_v2(input)
+    # Reused dirty scratchpad
+    assert xelis_hash.hash_v2(input, scratch_pad) == expected
+    assert xelis_hash.hash_v2(input, scratch_pad) == expected
+
+    with pytest.raises(ValueError):
+        xelis_hash.hash_v3(input, scratch_pad)
+    with pytest.raises(ValueError):
+        xelis_hash.ScratchPad(0)
+
+
+def test_threads():
+    inputs = [bytes([i]) * 112 for i in range(8)]
+    expected = [xelis_hash.hash_v3(input) for input in inputs]
+    results = [None] * len(inputs)
+
+    def run(i):
+        results[i] = xelis_hash.hash_v3(inputs[i], xelis_hash.ScratchPad(3))
+
+    threads = [threading.Thread(target=run, args=(i,)) for i in range(len(inputs))]
+    for thread in threads:
+        thread.start()
+    for thread in threads:
+        thread.join()
+
+    assert results == expected
+
+
+def test_difficulty():
+    assert xelis_hash.difficulty_target(1) == xelis_hash.MAX_TARGET
+    assert xelis_hash.difficulty_target(2) == xelis_hash.MAX_TARGET // 2
+
+    hash = (1 << 240).to_bytes(32, "big")
+    difficulty = xelis_hash.difficulty_from_hash(hash)
+    assert difficulty == xelis_hash.MAX_TARGET >> 240
+    assert xelis_hash.check_difficulty(hash, difficulty)
+    assert not xelis_hash.check_difficulty(hash, difficulty + 1)
+
+    assert xelis_hash.difficulty_from_hash(bytes(32)) == xelis_hash.MAX_TARGET
+    with pytest.raises(ValueError):
+        xelis_hash.difficulty_target(0)
+    with pytest.raises(ValueError):
+        xelis_hash.check_difficulty(bytes(31), 1)
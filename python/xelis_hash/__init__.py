@@ -0,0 +1,59 @@
+"""XELIS hashing algorithms.
+
+The hashes are computed by the native module without holding the GIL.
+"""
+
+from ._native import (
+    HASH_SIZE,
+    V1_INPUT_SIZE,
+    ScratchPad,
+    hash_batch,
+    hash_v1,
+    hash_v2,
+    hash_v3,
+    verify,
+)
+
+__all__ = [
+    "HASH_SIZE",
+    "V1_INPUT_SIZE",
+    "MAX_TARGET",
+    "ScratchPad",
+    "hash_batch",
+    "hash_v1",
+    "hash_v2",
+    "hash_v3",
+    "verify",
+    "difficulty_target",
+    "difficulty_from_hash",
+    "check_difficulty",
+]
+
+# Hashes are compared to the target as big-endian 256 bits integers
+MAX_TARGET = (1 << 256) - 1
+
+
+def _hash_value(hash: bytes) -> int:
+    if len(hash) != HASH_SIZE:
+        raise ValueError(f"expected a {HASH_SIZE} bytes hash, got {len(hash)} bytes")
+    return int.from_bytes(hash, "big")
+
+
+def difficulty_target(difficulty: int) -> int:
+    """Highest hash value meeting the difficulty."""
+    if difficulty <= 0:
+        raise ValueError("difficulty must be positive")
+    return MAX_TARGET // difficulty
+
+
+def difficulty_from_hash(hash: bytes) -> int:
+    """Highest difficulty met by the hash."""
+    value = _hash_value(hash)
+    if value == 0:
+        return MAX_TARGET
+    return MAX_TARGET // value
+
+
+def check_difficulty(hash: bytes, difficulty: int) -> bool:
+    """Check that the hash meets the difficulty."""
+    return _hash_value(hash) <= difficulty_target(difficulty)
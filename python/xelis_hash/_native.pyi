@@ -0,0 +1,15 @@
+from typing import List, Optional, Sequence
+
+HASH_SIZE: int
+V1_INPUT_SIZE: int
+
+class ScratchPad:
+    def __init__(self, version: int) -> None: ...
+    @property
+    def version(self) -> int: ...
+
+def hash_v1(input: bytes, scratch_pad: Optional[ScratchPad] = None) -> bytes: ...
+def hash_v2(input: bytes, scratch_pad: Optional[ScratchPad] = None) -> bytes: ...
+def hash_v3(input: bytes, scratch_pad: Optional[ScratchPad] = None) -> bytes: ...
+def verify(version: int, input: bytes, expected: bytes, scratch_pad: Optional[ScratchPad] = None) -> bool: ...
+def hash_batch(version: int, inputs: Sequence[bytes], scratch_pad: Optional[ScratchPad] = None) -> List[Optional[bytes]]: ...
@@ -0,0 +1,232 @@
+// Single AES encryption round (SubBytes, ShiftRows, MixColumns, AddRoundKey)
+// This is the equivalent of the `_mm_aesenc_si128` instruction and is used
+// by the stage 3 of each algorithm version.
+//
+// On x86_64, AES-NI is used when the CPU supports it.
+// Otherwise, a constant T-table software implementation is used.
+// The `soft-aes` feature can be enabled to always use the software path.
+
+// Size of an AES block in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+// AES block type alias
+pub type Block = [u8; BLOCK_SIZE];
+
+// AES S-box
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+// Multiply by x (i.e. {02}) in GF(2^8)
+const fn xtime(x: u8) -> u8 {
+    (x << 1) ^ (((x >> 7) & 1) * 0x1b)
+}
+
+// Build a T-table: each entry is the MixColumns output of a S-box substituted byte
+// for the given row, encoded as a little-endian column word.
+const fn build_table(row: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let s = SBOX[i];
+        let s2 = xtime(s);
+        let s3 = s2 ^ s;
+        let word = u32::from_le_bytes([s2, s, s, s3]);
+        table[i] = word.rotate_left(row * 8);
+        i += 1;
+    }
+    table
+}
+
+const TE0: [u32; 256] = build_table(0);
+const TE1: [u32; 256] = build_table(1);
+const TE2: [u32; 256] = build_table(2);
+const TE3: [u32; 256] = build_table(3);
+
+// Software implementation of a single AES round using the T-tables
+#[inline(always)]
+pub fn cipher_round_soft(block: &mut Block, key: &Block) {
+    let mut output = [0u8; BLOCK_SIZE];
+    for col in 0..4 {
+        // ShiftRows is applied by picking the byte of row `n` in column `col + n`
+        let word = TE0[block[4 * col] as usize]
+            ^ TE1[block[4 * ((col + 1) % 4) + 1] as usize]
+            ^ TE2[block[4 * ((col + 2) % 4) + 2] as usize]
+            ^ TE3[block[4 * ((col + 3) % 4) + 3] as usize];
+
+        let k = u32::from_le_bytes([key[4 * col], key[4 * col + 1], key[4 * col + 2], key[4 * col + 3]]);
+        output[4 * col..4 * col + 4].copy_from_slice(&(word ^ k).to_le_bytes());
+    }
+
+    *block = output;
+}
+
+// AES-NI implementation of a single AES round
+// Safety: the caller must ensure that the CPU supports the `aes` target feature
+#[cfg(all(target_arch = "x86_64", not(feature = "soft-aes")))]
+#[target_feature(enable = "aes")]
+unsafe fn cipher_round_aesni(block: &mut Block, key: &Block) {
+    use std::arch::x86_64::{_mm_aesenc_si128, _mm_loadu_si128, _mm_storeu_si128, __m128i};
+
+    let b = _mm_loadu_si128(block.as_ptr() as *const __m128i);
+    let k = _mm_loadu_si128(key.as_ptr() as *const __m128i);
+    _mm_storeu_si128(block.as_mut_ptr() as *mut __m128i, _mm_aesenc_si128(b, k));
+}
+
+// Check if the hardware AES implementation is going to be used
+#[inline(always)]
+pub fn has_hardware_support() -> bool {
+    #[cfg(all(target_arch = "x86_64", not(feature = "soft-aes")))]
+    {
+        cfg!(target_feature = "aes") || std::is_x86_feature_detected!("aes")
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", not(feature = "soft-aes"))))]
+    {
+        false
+    }
+}
+
+// Apply a single AES round on the block using the given round key
+// Hardware acceleration is used when available
+#[inline(always)]
+pub fn cipher_round(block: &mut Block, key: &Block) {
+    #[cfg(all(target_arch = "x86_64", not(feature = "soft-aes")))]
+    if has_hardware_support() {
+        // Safety: AES-NI support has been checked above
+        unsafe { cipher_round_aesni(block, key) };
+        return;
+    }
+
+    cipher_round_soft(block, key);
+}
+
+#[cfg(test)]
+mod tests {
+    use aes::cipher::generic_array::GenericArray;
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use super::*;
+
+    // Reduced under Miri to keep the suite fast enough
+    const ITERATIONS: usize = if cfg!(miri) { 1_000 } else { 1_000_000 };
+
+    fn reference_round(block: &Block, key: &Block) -> Block {
+        let mut b = GenericArray::from(*block);
+        aes::hazmat::cipher_round(&mut b, &GenericArray::from(*key));
+        b.into()
+    }
+
+    #[test]
+    fn test_known_answer() {
+        // FIPS-197 Appendix B, first round: state after AddRoundKey
+        // of round 0 and the round 1 key
+        let mut block: Block = [
+            0x19, 0x3d, 0xe3, 0xbe, 0xa0, 0xf4, 0xe2, 0x2b,
+            0x9a, 0xc6, 0x8d, 0x2a, 0xe9, 0xf8, 0x48, 0x08,
+        ];
+        let key: Block = [
+            0xa0, 0xfa, 0xfe, 0x17, 0x88, 0x54, 0x2c, 0xb1,
+            0x23, 0xa3, 0x39, 0x39, 0x2a, 0x6c, 0x76, 0x05,
+        ];
+        let expected: Block = [
+            0xa4, 0x9c, 0x7f, 0xf2, 0x68, 0x9f, 0x35, 0x2b,
+            0x6b, 0x5b, 0xea, 0x43, 0x02, 0x6a, 0x50, 0x49,
+        ];
+
+        let mut soft = block;
+        cipher_round_soft(&mut soft, &key);
+        assert_eq!(soft, expected);
+
+        cipher_round(&mut block, &key);
+        assert_eq!(block, expected);
+    }
+
+    #[test]
+    fn test_soft_equivalence() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut key = [0u8; BLOCK_SIZE];
+
+        for _ in 0..ITERATIONS {
+            rng.fill_bytes(&mut block);
+            rng.fill_bytes(&mut key);
+
+            let expected = reference_round(&block, &key);
+            cipher_round_soft(&mut block, &key);
+            assert_eq!(block, expected, "key: {:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_dispatch_equivalence() {
+        let mut rng = StdRng::seed_from_u64(0xCAFEBABEDEADBEEF);
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut key = [0u8; BLOCK_SIZE];
+
+        for _ in 0..ITERATIONS {
+            rng.fill_bytes(&mut block);
+            rng.fill_bytes(&mut key);
+
+            let mut soft = block;
+            cipher_round_soft(&mut soft, &key);
+            cipher_round(&mut block, &key);
+            assert_eq!(block, soft, "key: {:?}", key);
+        }
+    }
+
+    #[test]
+    fn test_chained_rounds() {
+        // Feed the output back as input to cover long dependency chains
+        let mut soft = [0u8; BLOCK_SIZE];
+        let mut dispatched = soft;
+        let mut expected = soft;
+        let key = *b"xelishash-pow-v3";
+
+        for _ in 0..ITERATIONS {
+            cipher_round_soft(&mut soft, &key);
+            cipher_round(&mut dispatched, &key);
+            expected = reference_round(&expected, &key);
+        }
+
+        assert_eq!(soft, expected);
+        assert_eq!(dispatched, expected);
+    }
+
+    #[test]
+    #[cfg(all(target_arch = "x86_64", not(feature = "soft-aes")))]
+    fn test_aesni_equivalence() {
+        if !std::is_x86_feature_detected!("aes") {
+            return;
+        }
+
+        let mut rng = StdRng::seed_from_u64(0xBADC0FFEE0DDF00D);
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut key = [0u8; BLOCK_SIZE];
+
+        for _ in 0..ITERATIONS {
+            rng.fill_bytes(&mut block);
+            rng.fill_bytes(&mut key);
+
+            let mut soft = block;
+            cipher_round_soft(&mut soft, &key);
+            // Safety: AES-NI support has been checked above
+            unsafe { cipher_round_aesni(&mut block, &key) };
+            assert_eq!(block, soft, "key: {:?}", key);
+        }
+    }
+}
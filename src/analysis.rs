@@ -0,0 +1,672 @@
+// Statistical uniformity tests of the branches and memory accesses
+// Each test checks that counts over buckets (branches, scratchpad indexes or outputs)
+// are consistent with a uniform distribution and passes if its p-value is at least `alpha`.
+//
+// The accesses of a hash are not independent samples, so the tracker tests are indicative:
+// they catch a biased distribution, not a subtle correlation.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tracker::OpsTracker;
+
+// Significance level of the reports, low as a report runs several tests
+pub const DEFAULT_ALPHA: f64 = 0.001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Test {
+    ChiSquare,
+    KolmogorovSmirnov,
+}
+
+impl fmt::Display for Test {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ChiSquare => write!(f, "chi-square"),
+            Self::KolmogorovSmirnov => write!(f, "kolmogorov-smirnov"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestResult {
+    pub name: String,
+    pub test: Test,
+    // Total count over all the buckets
+    pub samples: u64,
+    pub buckets: usize,
+    pub statistic: f64,
+    pub p_value: f64,
+    pub passed: bool,
+    // Reason of the failure if the test is known to fail
+    #[serde(default)]
+    pub known_failure: Option<String>,
+    // Measured biases causing the known failure
+    #[serde(default)]
+    pub biases: Vec<Bias>,
+}
+
+impl TestResult {
+    // Whether the test failed only because of known biases within their bounds
+    pub fn is_known_failure(&self) -> bool {
+        !self.passed
+            && self.known_failure.is_some()
+            && !self.biases.is_empty()
+            && self.biases.iter().all(Bias::within_bound)
+    }
+}
+
+// Bias measured on the tracked operations
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bias {
+    pub name: String,
+    pub value: f64,
+    pub max: f64,
+}
+
+impl Bias {
+    pub fn within_bound(&self) -> bool {
+        self.value <= self.max
+    }
+}
+
+// Bias of the algorithm and the bound it must stay within
+#[derive(Debug, Clone, Copy)]
+pub struct KnownBias {
+    pub name: &'static str,
+    pub measure: fn(&OpsTracker) -> f64,
+    pub max: f64,
+}
+
+impl KnownBias {
+    pub fn measure(&self, tracker: &OpsTracker) -> Bias {
+        Bias { name: self.name.to_owned(), value: (self.measure)(tracker), max: self.max }
+    }
+}
+
+// Test known to fail because of biases of the algorithm:
+// its failure is only known while all of them are within their bounds
+#[derive(Debug, Clone, Copy)]
+pub struct KnownFailure {
+    pub name: &'static str,
+    pub test: Test,
+    pub reason: &'static str,
+    pub biases: &'static [KnownBias],
+}
+
+// Largest relative deviation from uniform of the branch following each branch
+fn next_branch_deviations(tracker: &OpsTracker) -> [f64; 16] {
+    tracker.get_transitions().map(|next| {
+        let expected = next.iter().sum::<u64>() as f64 / 16.0;
+        next.iter().map(|&count| (count as f64 - expected).abs() / expected).fold(0.0, f64::max)
+    })
+}
+
+fn after_branch_13(tracker: &OpsTracker) -> f64 {
+    next_branch_deviations(tracker)[13]
+}
+
+fn after_other_branches(tracker: &OpsTracker) -> f64 {
+    next_branch_deviations(tracker).iter()
+        .enumerate()
+        .filter(|&(branch, _)| branch != 13)
+        .map(|(_, &deviation)| deviation)
+        .fold(0.0, f64::max)
+}
+
+// Share of the reads in the second half of the scratchpad, the buffer B of v2 and v3
+fn buffer_b_read_share(tracker: &OpsTracker) -> f64 {
+    let mem_ops = tracker.get_mem_ops();
+    let total: u64 = mem_ops.iter().map(|ops| ops.read).sum();
+    let b: u64 = mem_ops[mem_ops.len() / 2..].iter().map(|ops| ops.read).sum();
+    b as f64 / total as f64
+}
+
+// Chi-square statistic per degree of freedom, 1 on average for independent uniform counts
+fn dispersion(counts: &[u64]) -> f64 {
+    chi_square_uniform(counts).0 / (counts.len() - 1) as f64
+}
+
+// Root mean square relative deviation of the counts from uniform, beyond the one of independent samples
+// Unlike the dispersion, it doesn't grow with the samples for a fixed bias
+fn excess_deviation(counts: &[u64]) -> f64 {
+    let mean = counts.iter().sum::<u64>() as f64 / counts.len() as f64;
+    ((dispersion(counts) - 1.0).max(0.0) / mean).sqrt()
+}
+
+// Largest read deviation of the two buffers
+fn buffer_read_deviation(tracker: &OpsTracker) -> f64 {
+    let reads: Vec<u64> = tracker.get_mem_ops().iter().map(|ops| ops.read).collect();
+    let (a, b) = reads.split_at(reads.len() / 2);
+    excess_deviation(a).max(excess_deviation(b))
+}
+
+// The writes are overdispersed by a constant factor, so the dispersion doesn't grow with the samples
+fn write_dispersion(tracker: &OpsTracker) -> f64 {
+    let writes: Vec<u64> = tracker.get_mem_ops().iter().map(|ops| ops.write).collect();
+    dispersion(&writes)
+}
+
+// Per inner iteration, v2 reads each word of the buffer A 8 / 3 times and of the buffer B 7 / 3 times
+fn v2_buffer_b_read_share_error(tracker: &OpsTracker) -> f64 {
+    (buffer_b_read_share(tracker) - 7.0 / 15.0).abs()
+}
+
+// Per inner iteration, v3 reads 2 words of each buffer, r in either and `index_t` in the one picked:
+// 6 reads of which 2.5 + the `index_t` share are in the buffer B
+fn v3_index_t_buffer_b_share(tracker: &OpsTracker) -> f64 {
+    6.0 * buffer_b_read_share(tracker) - 2.5
+}
+
+// Branch 13 mostly returns a small quotient, so the result it's xored with and the next branch
+// selected from its bits barely change: the branch after it is biased by up to 11.4%
+const BRANCH_13_BIAS: &str = "the branch following the branch 13 is biased, its small quotient barely changes the result";
+
+const V2_READS_BIASES: &[KnownBias] = &[
+    KnownBias { name: "buffer B read share error", measure: v2_buffer_b_read_share_error, max: 0.001 },
+    KnownBias { name: "buffer read deviation", measure: buffer_read_deviation, max: 0.0025 },
+];
+
+// Known failures of the tracker tests on collected v2 runs
+pub const V2_KNOWN_FAILURES: &[KnownFailure] = &[
+    KnownFailure {
+        name: "branches",
+        test: Test::ChiSquare,
+        reason: BRANCH_13_BIAS,
+        biases: &[
+            KnownBias { name: "after branch 13", measure: after_branch_13, max: 0.12 },
+            KnownBias { name: "after other branches", measure: after_other_branches, max: 0.02 },
+        ],
+    },
+    KnownFailure {
+        name: "memory reads",
+        test: Test::ChiSquare,
+        reason: "the sequential index of the third read covers the scratchpad 1.5 times per hash",
+        biases: V2_READS_BIASES,
+    },
+    KnownFailure {
+        name: "memory reads",
+        test: Test::KolmogorovSmirnov,
+        reason: "the sequential index of the third read covers the scratchpad 1.5 times per hash",
+        biases: V2_READS_BIASES,
+    },
+];
+
+// `pick_half` is uniform, but the branch values it takes are not
+const V3_PICK_HALF_BIAS: &str = "the branch value given to `pick_half` isn't uniform, 50.3% of the `index_t` reads are in the buffer B";
+
+const V3_READS_BIASES: &[KnownBias] = &[
+    KnownBias { name: "index_t buffer B share", measure: v3_index_t_buffer_b_share, max: 0.505 },
+    KnownBias { name: "buffer read deviation", measure: buffer_read_deviation, max: 0.0025 },
+];
+
+// Known failures of the tracker tests on collected v3 runs
+pub const V3_KNOWN_FAILURES: &[KnownFailure] = &[
+    KnownFailure {
+        name: "branches",
+        test: Test::ChiSquare,
+        reason: BRANCH_13_BIAS,
+        biases: &[
+            KnownBias { name: "after branch 13", measure: after_branch_13, max: 0.12 },
+            // The branch 1 modulus is a small square root
+            KnownBias { name: "after other branches", measure: after_other_branches, max: 0.03 },
+        ],
+    },
+    KnownFailure { name: "memory reads", test: Test::ChiSquare, reason: V3_PICK_HALF_BIAS, biases: V3_READS_BIASES },
+    KnownFailure { name: "memory reads", test: Test::KolmogorovSmirnov, reason: V3_PICK_HALF_BIAS, biases: V3_READS_BIASES },
+    KnownFailure {
+        name: "memory writes",
+        test: Test::ChiSquare,
+        reason: "the index written only depends on the word read at `index_t`, which is often read again before being modified",
+        biases: &[KnownBias { name: "write dispersion", measure: write_dispersion, max: 1.4 }],
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Report {
+    pub alpha: f64,
+    pub results: Vec<TestResult>,
+}
+
+impl Report {
+    pub fn new(alpha: f64) -> Self {
+        Self { alpha, results: Vec::new() }
+    }
+
+    // Whether all the tests passed, except the known failures
+    pub fn passed(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    // Failed tests, except the known failures
+    pub fn failures(&self) -> impl Iterator<Item = &TestResult> {
+        self.results.iter().filter(|result| !result.passed && !result.is_known_failure())
+    }
+
+    fn push(&mut self, name: &str, test: Test, counts: &[u64], (statistic, p_value): (f64, f64)) {
+        let samples = counts.iter().sum();
+        self.results.push(TestResult {
+            name: name.to_owned(),
+            test,
+            samples,
+            buckets: counts.len(),
+            statistic,
+            p_value,
+            // A test without samples proves nothing
+            passed: samples > 0 && p_value >= self.alpha,
+            known_failure: None,
+            biases: Vec::new(),
+        });
+    }
+
+    pub fn chi_square(&mut self, name: &str, counts: &[u64]) {
+        self.push(name, Test::ChiSquare, counts, chi_square_uniform(counts));
+    }
+
+    pub fn kolmogorov_smirnov(&mut self, name: &str, counts: &[u64]) {
+        self.push(name, Test::KolmogorovSmirnov, counts, kolmogorov_smirnov_uniform(counts));
+    }
+
+    // Run both tests on the counts
+    pub fn uniformity(&mut self, name: &str, counts: &[u64]) {
+        self.chi_square(name, counts);
+        self.kolmogorov_smirnov(name, counts);
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for result in &self.results {
+            write!(
+                f,
+                "{} {} {}: statistic = {:.4}, p-value = {:.4} ({} samples in {} buckets)",
+                if result.passed {
+                    "PASS"
+                } else if result.is_known_failure() {
+                    "KNOWN FAIL"
+                } else {
+                    "FAIL"
+                },
+                result.name,
+                result.test,
+                result.statistic,
+                result.p_value,
+                result.samples,
+                result.buckets,
+            )?;
+
+            match &result.known_failure {
+                Some(reason) if !result.passed => writeln!(f, ": {}", reason)?,
+                _ => writeln!(f)?,
+            }
+
+            if !result.passed {
+                for bias in &result.biases {
+                    writeln!(
+                        f,
+                        "  {} = {:.4} (max {}){}",
+                        bias.name,
+                        bias.value,
+                        bias.max,
+                        if bias.within_bound() { "" } else { ", out of bound" },
+                    )?;
+                }
+            }
+        }
+
+        write!(f, "{} (alpha = {})", if self.passed() { "PASSED" } else { "FAILED" }, self.alpha)
+    }
+}
+
+// Chi-square goodness of fit against a uniform distribution
+// Returns the statistic and its p-value with `counts.len() - 1` degrees of freedom
+pub fn chi_square_uniform(counts: &[u64]) -> (f64, f64) {
+    let total: u64 = counts.iter().sum();
+    if total == 0 || counts.len() < 2 {
+        return (0.0, 1.0);
+    }
+
+    let expected = total as f64 / counts.len() as f64;
+    let statistic: f64 = counts.iter()
+        .map(|&count| {
+            let diff = count as f64 - expected;
+            diff * diff / expected
+        })
+        .sum();
+
+    let dof = (counts.len() - 1) as f64;
+    (statistic, gamma_q(dof / 2.0, statistic / 2.0))
+}
+
+// Kolmogorov-Smirnov test of the counts against a discrete uniform distribution
+// Returns the maximum distance between the cumulative distributions and its p-value
+pub fn kolmogorov_smirnov_uniform(counts: &[u64]) -> (f64, f64) {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return (0.0, 1.0);
+    }
+
+    let n = total as f64;
+    let buckets = counts.len() as f64;
+    let mut cumulative = 0u64;
+    let mut distance = 0f64;
+    for (i, &count) in counts.iter().enumerate() {
+        cumulative += count;
+        let diff = (cumulative as f64 / n - (i + 1) as f64 / buckets).abs();
+        distance = distance.max(diff);
+    }
+
+    let sqrt_n = n.sqrt();
+    (distance, kolmogorov_q((sqrt_n + 0.12 + 0.11 / sqrt_n) * distance))
+}
+
+// Tests of the branches and the reads and writes of each scratchpad index
+// `known_failures` are the ones of the tracked version, such as `V3_KNOWN_FAILURES`,
+// their biases are measured on the tracker for the tests that fail
+pub fn analyze_tracker(tracker: &OpsTracker, alpha: f64, known_failures: &[KnownFailure]) -> Report {
+    let mut report = Report::new(alpha);
+
+    let branches: Vec<u64> = tracker.get_branches().iter().map(|&count| count as u64).collect();
+    report.chi_square("branches", &branches);
+
+    let mem_ops = tracker.get_mem_ops();
+    let reads: Vec<u64> = mem_ops.iter().map(|ops| ops.read).collect();
+    report.uniformity("memory reads", &reads);
+
+    let writes: Vec<u64> = mem_ops.iter().map(|ops| ops.write).collect();
+    report.uniformity("memory writes", &writes);
+
+    for result in report.results.iter_mut().filter(|result| !result.passed) {
+        if let Some(known) = known_failures.iter().find(|known| known.name == result.name && known.test == result.test) {
+            result.known_failure = Some(known.reason.to_owned());
+            result.biases = known.biases.iter().map(|bias| bias.measure(tracker)).collect();
+        }
+    }
+
+    report
+}
+
+// Tests of the v3 `map_index` and `pick_half` outputs on pseudo random and sequential inputs
+#[cfg(feature = "v3")]
+pub fn analyze_v3_primitives(samples: u64, seed: u64, alpha: f64) -> Report {
+    use crate::v3::{map_index, pick_half, BUFFER_SIZE};
+
+    let mut report = Report::new(alpha);
+    let mut state = seed;
+
+    let mut random = vec![0u64; BUFFER_SIZE];
+    let mut sequential = vec![0u64; BUFFER_SIZE];
+    for i in 0..samples {
+        random[map_index(split_mix64(&mut state))] += 1;
+        sequential[map_index(i)] += 1;
+    }
+    report.uniformity("map_index random", &random);
+    report.uniformity("map_index sequential", &sequential);
+
+    let mut random = [0u64; 2];
+    let mut sequential = [0u64; 2];
+    for i in 0..samples {
+        random[pick_half(split_mix64(&mut state)) as usize] += 1;
+        sequential[pick_half(i) as usize] += 1;
+    }
+    report.chi_square("pick_half random", &random);
+    report.chi_square("pick_half sequential", &sequential);
+
+    report
+}
+
+// Deterministic pseudo random inputs
+#[cfg(feature = "v3")]
+fn split_mix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+const MAX_ITERATIONS: usize = 100_000;
+const EPSILON: f64 = 1e-15;
+const TINY: f64 = 1e-300;
+
+// Natural logarithm of the gamma function (Lanczos approximation, g = 7)
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..].iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| sum + c / (x + (i + 1) as f64));
+
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
+
+// Regularized upper incomplete gamma function Q(a, x)
+fn gamma_q(a: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+
+    let prefactor = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        // Series of the lower function P(a, x)
+        let mut n = a;
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for _ in 0..MAX_ITERATIONS {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+
+        (1.0 - sum * prefactor).clamp(0.0, 1.0)
+    } else {
+        // Continued fraction of Q(a, x) using the modified Lentz method
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / TINY;
+        let mut d = 1.0 / b;
+        let mut h = d;
+        for i in 1..MAX_ITERATIONS {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+            d = an * d + b;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = b + an / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            let delta = d * c;
+            h *= delta;
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+
+        (h * prefactor).clamp(0.0, 1.0)
+    }
+}
+
+// Survival function of the Kolmogorov distribution
+fn kolmogorov_q(lambda: f64) -> f64 {
+    // The series converges slowly for small values, where it is 1
+    if lambda < 0.2 {
+        return 1.0;
+    }
+
+    let mut sum = 0.0;
+    let mut sign = 2.0;
+    for k in 1..=100 {
+        let k = k as f64;
+        let term = sign * (-2.0 * k * k * lambda * lambda).exp();
+        sum += term;
+        if term.abs() <= EPSILON * sum.abs() {
+            return sum.clamp(0.0, 1.0);
+        }
+        sign = -sign;
+    }
+
+    1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!((actual - expected).abs() < tolerance, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_ln_gamma() {
+        assert_close(ln_gamma(1.0), 0.0, 1e-12);
+        assert_close(ln_gamma(5.0), 24f64.ln(), 1e-12);
+        assert_close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-12);
+        assert_close(ln_gamma(1000.5), 5908.674175848678, 1e-9);
+    }
+
+    #[test]
+    fn test_chi_square_p_values() {
+        // Critical values at 5%
+        assert_close(gamma_q(0.5, 3.841458820694124 / 2.0), 0.05, 1e-9);
+        assert_close(gamma_q(5.0, 18.307038053275146 / 2.0), 0.05, 1e-9);
+        assert_close(gamma_q(7.5, 24.995790139728616 / 2.0), 0.05, 1e-9);
+        // Large degrees of freedom, the median is close to the mean
+        let p = gamma_q(50_000.0, 50_000.0);
+        assert!(p > 0.49 && p < 0.51, "{}", p);
+    }
+
+    #[test]
+    fn test_kolmogorov_p_values() {
+        assert_close(kolmogorov_q(1.3580986393225505), 0.05, 1e-6);
+        assert_close(kolmogorov_q(1.6276236115189502), 0.01, 1e-6);
+        assert_eq!(kolmogorov_q(0.1), 1.0);
+    }
+
+    #[test]
+    fn test_uniform_counts() {
+        let (statistic, p_value) = chi_square_uniform(&[100; 16]);
+        assert_eq!(statistic, 0.0);
+        assert_eq!(p_value, 1.0);
+
+        let (distance, p_value) = kolmogorov_smirnov_uniform(&[100; 16]);
+        assert!(distance < 1e-12);
+        assert_eq!(p_value, 1.0);
+    }
+
+    #[test]
+    fn test_biased_counts() {
+        let mut counts = [1000u64; 16];
+        counts[0] = 1300;
+        let mut report = Report::new(DEFAULT_ALPHA);
+        report.chi_square("biased", &counts);
+        assert!(!report.passed());
+
+        // Skewed towards the first half
+        let counts: Vec<u64> = (0..1000).map(|i| if i < 500 { 110 } else { 90 }).collect();
+        let mut report = Report::new(DEFAULT_ALPHA);
+        report.kolmogorov_smirnov("skewed", &counts);
+        assert!(!report.passed());
+        assert_eq!(report.failures().count(), 1);
+    }
+
+    #[test]
+    fn test_empty_counts() {
+        let mut report = Report::new(DEFAULT_ALPHA);
+        report.uniformity("empty", &[0; 16]);
+        assert!(!report.passed());
+    }
+
+    #[test]
+    #[cfg(feature = "v3")]
+    fn test_v3_primitives() {
+        let report = analyze_v3_primitives(1 << 20, 0xDEADBEEFCAFEBABE, DEFAULT_ALPHA);
+        assert_eq!(report.results.len(), 6);
+        assert!(report.passed(), "{}", report);
+    }
+
+    #[test]
+    #[cfg(feature = "v3")]
+    #[cfg_attr(miri, ignore)]
+    fn test_v3_tracker() {
+        use crate::v3;
+
+        let mut tracker = OpsTracker::new(v3::MEMORY_SIZE);
+        let mut scratch_pad = v3::ScratchPad::default();
+        let mut input = [0u8; 112];
+        for i in 0..4u8 {
+            input[0] = i;
+            v3::xelis_hash_observed(&input, &mut scratch_pad, &mut tracker).unwrap();
+        }
+
+        let report = analyze_tracker(&tracker, DEFAULT_ALPHA, V3_KNOWN_FAILURES);
+        assert_eq!(report.results.len(), 5);
+        assert!(report.passed(), "{}", report);
+    }
+
+    #[test]
+    fn test_known_failures() {
+        use crate::tracker::MemOp;
+
+        fn branch_0_share(tracker: &OpsTracker) -> f64 {
+            tracker.get_branches()[0] as f64 / tracker.get_branches().iter().sum::<usize>() as f64
+        }
+
+        const fn known(biases: &'static [KnownBias]) -> [KnownFailure; 1] {
+            [KnownFailure { name: "branches", test: Test::ChiSquare, reason: "biased on purpose", biases }]
+        }
+
+        // 1300 of the 16300 branches are the branch 0
+        let mut tracker = OpsTracker::new(16);
+        for _ in 0..1000 {
+            for branch in 0..16 {
+                tracker.add_branch(branch);
+            }
+        }
+        for _ in 0..300 {
+            tracker.add_branch(0);
+        }
+        for _ in 0..100 {
+            for index in 0..16 {
+                tracker.add_mem_op(index, MemOp::Read);
+                tracker.add_mem_op(index, MemOp::Write);
+            }
+        }
+
+        let report = analyze_tracker(&tracker, DEFAULT_ALPHA, &known(&[KnownBias { name: "branch 0 share", measure: branch_0_share, max: 0.08 }]));
+        assert!(report.passed(), "{}", report);
+        assert!(report.results[0].is_known_failure());
+        assert_eq!(report.results[0].known_failure.as_deref(), Some("biased on purpose"));
+        assert!(report.to_string().contains("KNOWN FAIL branches chi-square"));
+        assert!(report.to_string().contains("branch 0 share = 0.0798 (max 0.08)\n"));
+
+        // A bias out of its bound fails the test
+        let report = analyze_tracker(&tracker, DEFAULT_ALPHA, &known(&[KnownBias { name: "branch 0 share", measure: branch_0_share, max: 0.07 }]));
+        let failures: Vec<_> = report.failures().map(|result| (result.name.as_str(), result.test)).collect();
+        assert_eq!(failures, [("branches", Test::ChiSquare)]);
+        assert!(report.to_string().contains("FAIL branches chi-square"));
+        assert!(report.to_string().contains("branch 0 share = 0.0798 (max 0.07), out of bound"));
+
+        // So does a known failure without bounds
+        let report = analyze_tracker(&tracker, DEFAULT_ALPHA, &known(&[]));
+        assert!(!report.passed());
+    }
+}
@@ -0,0 +1,153 @@
+// Bindings to the C implementation of the `C` directory, built by `build.rs`
+// The C implementation only supports 112 bytes inputs and divides by zero
+// on a v3 zero modulus instead of rejecting the input.
+// It is only used to check that it agrees with this crate.
+// It requires the `C/BLAKE3` submodule: `cargo test --release --features c-port c_port`
+
+use crate::Hash;
+
+// Input length supported by the C implementation
+pub const INPUT_LEN: usize = 112;
+
+mod ffi {
+    extern "C" {
+        pub fn xelis_hash_v2(input: *const u8, hash: *mut u8, scratch: *mut u64);
+        pub fn xelis_hash_v2_stage1(input: *const u8, input_len: usize, scratch_pad: *mut u8);
+        pub fn xelis_hash_v2_stage3(scratch: *mut u64);
+
+        pub fn xelis_hash_v3(input: *const u8, hash: *mut u8, scratch: *mut u64);
+        pub fn xelis_hash_v3_stage1(input: *const u8, input_len: usize, scratch_pad: *mut u8);
+        pub fn xelis_hash_v3_stage3(scratch: *mut u64);
+    }
+}
+
+pub mod v2 {
+    use crate::v2::ScratchPad;
+    use super::*;
+
+    // The C isqrt returns 2^32 - 1 for 2^64 - 1, so it also hashes the inputs
+    // for which `v2::xelis_hash` returns `Error::IsqrtOverflow`
+    pub fn xelis_hash(input: &[u8; INPUT_LEN], scratch_pad: &mut ScratchPad) -> Hash {
+        let mut hash = [0u8; 32];
+        // SAFETY: the C implementation reads INPUT_LEN bytes and writes the whole scratchpad
+        unsafe { ffi::xelis_hash_v2(input.as_ptr(), hash.as_mut_ptr(), scratch_pad.as_mut_slice().as_mut_ptr()) };
+        hash
+    }
+
+    pub fn stage_1(input: &[u8; INPUT_LEN], scratch_pad: &mut ScratchPad) {
+        // SAFETY: the C implementation reads INPUT_LEN bytes and writes the whole scratchpad
+        unsafe { ffi::xelis_hash_v2_stage1(input.as_ptr(), INPUT_LEN, scratch_pad.as_mut_slice().as_mut_ptr().cast()) };
+    }
+
+    pub fn stage_3(scratch_pad: &mut ScratchPad) {
+        // SAFETY: the C implementation only accesses the scratchpad words
+        unsafe { ffi::xelis_hash_v2_stage3(scratch_pad.as_mut_slice().as_mut_ptr()) };
+    }
+}
+
+pub mod v3 {
+    use crate::v3::ScratchPad;
+    use super::*;
+
+    /// # Safety
+    /// The input must have a valid hash: `v3::xelis_hash` must not return `Error::ZeroModulus` for it
+    pub unsafe fn xelis_hash(input: &[u8; INPUT_LEN], scratch_pad: &mut ScratchPad) -> Hash {
+        let mut hash = [0u8; 32];
+        ffi::xelis_hash_v3(input.as_ptr(), hash.as_mut_ptr(), scratch_pad.as_mut_slice().as_mut_ptr());
+        hash
+    }
+
+    pub fn stage_1(input: &[u8; INPUT_LEN], scratch_pad: &mut ScratchPad) {
+        // SAFETY: the C implementation reads INPUT_LEN bytes and writes the whole scratchpad
+        unsafe { ffi::xelis_hash_v3_stage1(input.as_ptr(), INPUT_LEN, scratch_pad.as_mut_slice().as_mut_ptr().cast()) };
+    }
+
+    /// # Safety
+    /// The v3 stage 3 of this crate must not return `Error::ZeroModulus` for the scratchpad
+    pub unsafe fn stage_3(scratch_pad: &mut ScratchPad) {
+        ffi::xelis_hash_v3_stage3(scratch_pad.as_mut_slice().as_mut_ptr());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::OsRng, RngCore};
+    use crate::{observer::NoopObserver, v2 as rust_v2, v3 as rust_v3, Error};
+    use super::*;
+
+    const INPUTS: usize = 16;
+
+    fn random_input() -> [u8; INPUT_LEN] {
+        let mut input = [0u8; INPUT_LEN];
+        OsRng.fill_bytes(&mut input);
+        input
+    }
+
+    #[test]
+    fn test_v2_hash() {
+        let mut scratch_pad = rust_v2::ScratchPad::default();
+        for _ in 0..INPUTS {
+            let input = random_input();
+            let expected = rust_v2::xelis_hash(&input, &mut scratch_pad).unwrap();
+            assert_eq!(v2::xelis_hash(&input, &mut scratch_pad), expected, "input: {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_v2_stages() {
+        let mut expected = rust_v2::ScratchPad::default();
+        let mut scratch_pad = rust_v2::ScratchPad::default();
+        for _ in 0..INPUTS {
+            let input = random_input();
+            rust_v2::stage_1::<{ rust_v2::MEMORY_SIZE }, { rust_v2::MEMORY_SIZE_BYTES }>(&input, expected.as_mut_slice()).unwrap();
+            v2::stage_1(&input, &mut scratch_pad);
+            assert!(expected.as_mut_slice() == scratch_pad.as_mut_slice(), "stage 1, input: {:?}", input);
+
+            // Use a random scratchpad to not depend on stage 1
+            OsRng.fill_bytes(bytemuck::cast_slice_mut(expected.as_mut_slice()));
+            scratch_pad.as_mut_slice().copy_from_slice(expected.as_mut_slice());
+            rust_v2::stage_3::<{ rust_v2::MEMORY_SIZE }, { rust_v2::BUFFER_SIZE }, { rust_v2::SCRATCHPAD_ITERS }>(expected.as_mut_slice(), &mut NoopObserver).unwrap();
+            v2::stage_3(&mut scratch_pad);
+            assert!(expected.as_mut_slice() == scratch_pad.as_mut_slice(), "stage 3");
+        }
+    }
+
+    #[test]
+    fn test_v3_hash() {
+        let mut scratch_pad = rust_v3::ScratchPad::default();
+        for _ in 0..INPUTS {
+            let input = random_input();
+            match rust_v3::xelis_hash(&input, &mut scratch_pad) {
+                // SAFETY: the input has a valid hash
+                Ok(expected) => assert_eq!(unsafe { v3::xelis_hash(&input, &mut scratch_pad) }, expected, "input: {:?}", input),
+                Err(Error::ZeroModulus) => {},
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+    }
+
+    #[test]
+    fn test_v3_stages() {
+        let mut expected = rust_v3::ScratchPad::default();
+        let mut scratch_pad = rust_v3::ScratchPad::default();
+        for _ in 0..INPUTS {
+            let input = random_input();
+            rust_v2::stage_1::<{ rust_v3::MEMORY_SIZE }, { rust_v3::MEMORY_SIZE_BYTES }>(&input, expected.as_mut_slice()).unwrap();
+            v3::stage_1(&input, &mut scratch_pad);
+            assert!(expected.as_mut_slice() == scratch_pad.as_mut_slice(), "stage 1, input: {:?}", input);
+
+            // Use a random scratchpad to not depend on stage 1
+            OsRng.fill_bytes(bytemuck::cast_slice_mut(expected.as_mut_slice()));
+            scratch_pad.as_mut_slice().copy_from_slice(expected.as_mut_slice());
+            match rust_v3::stage_3::<{ rust_v3::MEMORY_SIZE }, { rust_v3::BUFFER_SIZE }, { rust_v3::SCRATCHPAD_ITERS }>(expected.as_mut_slice(), &mut NoopObserver) {
+                Ok(()) => {
+                    // SAFETY: the scratchpad gives no zero modulus
+                    unsafe { v3::stage_3(&mut scratch_pad) };
+                    assert!(expected.as_mut_slice() == scratch_pad.as_mut_slice(), "stage 3");
+                },
+                Err(Error::ZeroModulus) => {},
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+    }
+}
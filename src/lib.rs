@@ -8,9 +8,25 @@ pub mod v2;
 pub mod v3;
 
 pub mod scratchpad;
+pub mod aes_round;
+pub mod math;
+pub mod observer;
+#[cfg(any(test, feature = "reference"))]
+pub mod reference;
 
-#[cfg(feature = "tracker")]
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "c-port")]
+pub mod c_port;
+
+#[cfg(all(test, any(feature = "v1", feature = "v2", feature = "v3")))]
+mod test_vectors;
+
+#[cfg(feature = "tracker-core")]
 pub mod tracker;
+#[cfg(feature = "tracker-core")]
+pub mod analysis;
 
 // Number of bytes in a hash
 const HASH_SIZE: usize = 32;
@@ -28,5 +44,76 @@ pub enum Error {
     CastError(bytemuck::PodCastError),
     #[error("Error on format")]
     FormatError,
+    #[error("Modular power with a zero modulus")]
+    ZeroModulus,
+    #[error("Integer square root of 2^64 - 1")]
+    IsqrtOverflow,
+    #[error("Invalid input length: {0}")]
+    InvalidInputLength(usize),
+    #[error("Unsupported version: {0}")]
+    UnsupportedVersion(u8),
 }
 
+// Number of u64s of the scratchpad of the algorithm version,
+// None if the version doesn't exist or its feature is disabled
+pub fn scratchpad_size(version: u8) -> Option<usize> {
+    match version {
+        #[cfg(feature = "v1")]
+        1 => Some(v1::MEMORY_SIZE),
+        #[cfg(feature = "v2")]
+        2 => Some(v2::MEMORY_SIZE),
+        #[cfg(feature = "v3")]
+        3 => Some(v3::MEMORY_SIZE),
+        _ => None,
+    }
+}
+
+// Hash the input using the algorithm version selected at runtime,
+// `scratch_pad` must have `scratchpad_size(version)` words
+// The v1 input is copied to be aligned, so it can be any byte slice of the expected length
+#[cfg_attr(not(any(feature = "v1", feature = "v2", feature = "v3")), allow(unused_variables))]
+pub fn hash_any(version: u8, input: &[u8], scratch_pad: &mut [u64]) -> Result<Hash, Error> {
+    match version {
+        #[cfg(feature = "v1")]
+        1 => {
+            let mut aligned = v1::AlignedInput::default();
+            let aligned = aligned.as_mut_slice()?;
+            if input.len() != aligned.len() {
+                return Err(Error::InvalidInputLength(input.len()));
+            }
+            aligned.copy_from_slice(input);
+
+            v1::xelis_hash_words(aligned, scratch_pad.try_into().map_err(|_| Error::FormatError)?)
+        },
+        #[cfg(feature = "v2")]
+        2 => v2::xelis_hash_words(input, scratch_pad.try_into().map_err(|_| Error::FormatError)?),
+        #[cfg(feature = "v3")]
+        3 => v3::xelis_hash_words(input, scratch_pad.try_into().map_err(|_| Error::FormatError)?),
+        _ => Err(Error::UnsupportedVersion(version)),
+    }
+}
+
+
+#[cfg(all(test, feature = "v1", feature = "v3"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_hash_any() {
+        let mut scratch_pad = vec![0; scratchpad_size(3).unwrap()];
+        let expected = v3::xelis_hash(&[0u8; 112], &mut v3::ScratchPad::default()).unwrap();
+        assert_eq!(hash_any(3, &[0u8; 112], &mut scratch_pad).unwrap(), expected);
+
+        // The v1 input doesn't need to be aligned
+        let mut scratch_pad = vec![0; scratchpad_size(1).unwrap()];
+        let buffer = [0u8; v1::BYTES_ARRAY_INPUT + 1];
+        let expected = v1::xelis_hash(&mut [0u8; v1::BYTES_ARRAY_INPUT], &mut v1::ScratchPad::default()).unwrap();
+        assert_eq!(hash_any(1, &buffer[1..], &mut scratch_pad).unwrap(), expected);
+        assert!(matches!(hash_any(1, &buffer, &mut scratch_pad), Err(Error::InvalidInputLength(201))));
+
+        assert!(matches!(hash_any(3, &[0u8; 112], &mut scratch_pad), Err(Error::FormatError)));
+        assert!(matches!(hash_any(4, &[0u8; 112], &mut scratch_pad), Err(Error::UnsupportedVersion(4))));
+        assert_eq!(scratchpad_size(4), None);
+    }
+}
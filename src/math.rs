@@ -0,0 +1,518 @@
+// Integer arithmetic primitives used by the stage 3 of v2 and v3
+//
+// u128 division and remainder are compiled to slow library calls
+// (`__udivti3` / `__umodti3`) on most targets.
+// When the divisor fits in 64 bits, the hardware `div` instruction
+// can be used instead, as done by the C implementation (`udiv`).
+
+// Divide the 128-bit value `high:low` by `divisor`
+// The caller must ensure that `high < divisor` so the quotient fits in 64 bits
+// Returns the quotient and the remainder
+#[inline(always)]
+fn div_rem_narrow(high: u64, low: u64, divisor: u64) -> (u64, u64) {
+    debug_assert!(high < divisor);
+
+    // Miri can't interpret inline assembly
+    #[cfg(all(target_arch = "x86_64", not(miri)))]
+    {
+        let quotient: u64;
+        let remainder: u64;
+        // Safety: `high < divisor` guarantees a non-zero divisor
+        // and a quotient that fits in 64 bits, so `div` can't fault
+        unsafe {
+            std::arch::asm!(
+                "div {divisor}",
+                divisor = in(reg) divisor,
+                inout("rax") low => quotient,
+                inout("rdx") high => remainder,
+                options(pure, nomem, nostack)
+            );
+        }
+        (quotient, remainder)
+    }
+
+    #[cfg(not(all(target_arch = "x86_64", not(miri))))]
+    {
+        let n = (high as u128) << 64 | low as u128;
+        let divisor = divisor as u128;
+        ((n / divisor) as u64, (n % divisor) as u64)
+    }
+}
+
+// Divide a u128 by a u64 divisor
+// Returns the low 64 bits of the quotient and the remainder
+#[inline(always)]
+pub fn div_rem_u128_by_u64(n: u128, divisor: u64) -> (u64, u64) {
+    assert!(divisor != 0, "attempt to divide by zero");
+
+    let high = (n >> 64) as u64;
+    let low = n as u64;
+    if high < divisor {
+        div_rem_narrow(high, low, divisor)
+    } else {
+        // Reduce the high part first, the upper bits of the
+        // quotient are discarded as only the low 64 bits are returned
+        div_rem_narrow(high % divisor, low, divisor)
+    }
+}
+
+// Compute `n / d` truncated to 64 bits
+// Use the 128/64 division when `d` fits in 64 bits,
+// otherwise fallback to the u128 division
+#[inline(always)]
+pub fn div_u128_to_u64(n: u128, d: u128) -> u64 {
+    if d >> 64 == 0 {
+        div_rem_u128_by_u64(n, d as u64).0
+    } else {
+        n.wrapping_div(d) as u64
+    }
+}
+
+// Compute `n % d` truncated to 64 bits
+// Use the 128/64 division when `d` fits in 64 bits,
+// otherwise fallback to the u128 remainder
+#[inline(always)]
+pub fn rem_u128_to_u64(n: u128, d: u128) -> u64 {
+    if d >> 64 == 0 {
+        div_rem_u128_by_u64(n, d as u64).1
+    } else {
+        n.wrapping_rem(d) as u64
+    }
+}
+
+// Integer square root of a u64 computed at compile time, only used to build the table below
+const fn isqrt_const(n: u64) -> u64 {
+    let mut low = 0;
+    let mut high = 1 << 32;
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if mid * mid <= n {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    low
+}
+
+// Seeds of the square root of a normalized value from its top 8 bits `i` (64 to 255):
+// ceil(sqrt((i + 1) << 24)), an upper bound of the square root of its high 32 bits
+const ISQRT_SEEDS: [u64; 192] = {
+    let mut seeds = [0; 192];
+    let mut i = 0;
+    while i < 192 {
+        let n = (i as u64 + 65) << 24;
+        let root = isqrt_const(n);
+        seeds[i] = if root * root < n { root + 1 } else { root };
+        i += 1;
+    }
+    seeds
+};
+
+// Integer square root, rounded down
+// It doesn't rely on floating point, can't overflow and has
+// a constant cost: a table seed, two Newton steps and a final correction
+#[inline(always)]
+pub fn isqrt(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+
+    // Shift by an even amount so that m is in [2^62, 2^64) and sqrt(m) in [2^31, 2^32)
+    let shift = n.leading_zeros() & !1;
+    let m = n << shift;
+    let high = m >> 32;
+
+    // The seed is less than 0.8% above sqrt(high), one Newton step on the high bits
+    // with 16 fractional bits gives sqrt(m) within 2^16
+    let seed = ISQRT_SEEDS[(m >> 56) as usize - 64];
+    let approx = (seed << 15) + (high << 15) / seed;
+
+    // The second step is never below isqrt(m) and overshoots by less than
+    // (2^16)^2 / 2^32 = 1, so a single correction is enough
+    let root = ((approx + m / approx) >> 1).min(u32::MAX as u64);
+    let root = root - (root * root > m) as u64;
+
+    root >> (shift / 2)
+}
+
+// Montgomery arithmetic for an odd 64-bit modulus, using R = 2^64
+struct Montgomery {
+    // The modulus, must be odd
+    modulus: u64,
+    // modulus^-1 mod 2^64
+    inverse: u64,
+    // R^2 mod modulus, used to convert into the Montgomery form
+    r2: u64,
+}
+
+impl Montgomery {
+    #[inline(always)]
+    fn new(modulus: u64) -> Self {
+        debug_assert!(modulus & 1 == 1);
+
+        // Newton iteration, each step doubles the number of correct bits
+        // Starting from the modulus itself gives 3 correct bits for odd values
+        let mut inverse = modulus;
+        for _ in 0..5 {
+            inverse = inverse.wrapping_mul(2u64.wrapping_sub(modulus.wrapping_mul(inverse)));
+        }
+
+        // R mod modulus = (2^64 - modulus) mod modulus
+        let r = modulus.wrapping_neg() % modulus;
+        let r2 = div_rem_u128_by_u64(r as u128 * r as u128, modulus).1;
+
+        Self {
+            modulus,
+            inverse,
+            r2,
+        }
+    }
+
+    // Montgomery reduction: compute t * R^-1 mod modulus
+    // t must be lower than modulus * R
+    #[inline(always)]
+    fn reduce(&self, t: u128) -> u64 {
+        let low = t as u64;
+        let high = (t >> 64) as u64;
+
+        // u * modulus has the same low 64 bits as t
+        let u = low.wrapping_mul(self.inverse);
+        let um_high = ((u as u128 * self.modulus as u128) >> 64) as u64;
+
+        // (t - u * modulus) / R is in ]-modulus, modulus[
+        let (result, borrow) = high.overflowing_sub(um_high);
+        if borrow {
+            result.wrapping_add(self.modulus)
+        } else {
+            result
+        }
+    }
+
+    #[inline(always)]
+    fn mul(&self, a: u64, b: u64) -> u64 {
+        self.reduce(a as u128 * b as u128)
+    }
+
+    // Convert a value lower than the modulus into the Montgomery form
+    #[inline(always)]
+    fn to_montgomery(&self, a: u64) -> u64 {
+        self.mul(a, self.r2)
+    }
+}
+
+// Compute (a * b) % modulus, a and b must be lower than the modulus
+// so the 128/64 division can't overflow
+#[inline(always)]
+fn mul_mod(a: u64, b: u64, modulus: u64) -> u64 {
+    div_rem_u128_by_u64(a as u128 * b as u128, modulus).1
+}
+
+// Modular exponentiation: base^exp % modulus
+// Odd moduli use the Montgomery multiplication, even moduli
+// fallback to the square-and-multiply with a division per step.
+// Returns `None` if the modulus is zero, whatever the exponent is.
+// NOTE: to stay identical to the original square-and-multiply
+// implementation, a zero exponent always returns 1, even for a modulus of 1
+#[inline]
+pub fn modular_power(base: u64, mut exp: u64, modulus: u64) -> Option<u64> {
+    if modulus == 0 {
+        return None;
+    }
+
+    if exp == 0 {
+        return Some(1);
+    }
+
+    let mut base = base % modulus;
+    if modulus & 1 == 1 {
+        let montgomery = Montgomery::new(modulus);
+        let mut base = montgomery.to_montgomery(base);
+        let mut result = montgomery.to_montgomery(1);
+
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = montgomery.mul(result, base);
+            }
+
+            base = montgomery.mul(base, base);
+            exp >>= 1;
+        }
+
+        // Convert back from the Montgomery form
+        Some(montgomery.reduce(result as u128))
+    } else {
+        // Even modulus is at least 2, so 1 is already reduced
+        let mut result = 1;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = mul_mod(result, base, modulus);
+            }
+
+            base = mul_mod(base, base, modulus);
+            exp >>= 1;
+        }
+
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use super::*;
+
+    // Reduced under Miri to keep the suite fast enough
+    const ITERATIONS: usize = if cfg!(miri) { 1_000 } else { 1_000_000 };
+
+    fn edge_values() -> Vec<u64> {
+        let mut values = vec![0, 1, 2, 3, 4, 5, 7, 8, 9, u64::MAX, u64::MAX - 1, u64::MAX / 2, u64::MAX / 3];
+        for shift in 1..64 {
+            let v = 1u64 << shift;
+            values.extend([v - 1, v, v + 1]);
+        }
+        values
+    }
+
+    fn check(n: u128, d: u128) {
+        assert_eq!(div_u128_to_u64(n, d), (n / d) as u64, "{} / {}", n, d);
+        assert_eq!(rem_u128_to_u64(n, d), (n % d) as u64, "{} % {}", n, d);
+    }
+
+    #[test]
+    fn test_edge_cases() {
+        let values = edge_values();
+        for &high in &values {
+            for &low in &values {
+                let n = (high as u128) << 64 | low as u128;
+                for &d_high in &[0, 1, u64::MAX] {
+                    for &d_low in &values {
+                        let d = (d_high as u128) << 64 | d_low as u128;
+                        if d != 0 {
+                            check(n, d);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_divisor_boundaries() {
+        // Dividends around the divisor multiples, where
+        // the high part is equal or close to the divisor
+        for &d in &edge_values() {
+            if d == 0 {
+                continue;
+            }
+
+            for &high in &[d.wrapping_sub(1), d, d.wrapping_add(1)] {
+                for &low in &[0, 1, d.wrapping_sub(1), d, u64::MAX] {
+                    check((high as u128) << 64 | low as u128, d as u128);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_random() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..ITERATIONS {
+            let n: u128 = rng.gen();
+            let d: u64 = rng.gen::<u64>() | 1;
+            check(n, d as u128);
+
+            // Small divisors to exercise the high part reduction
+            let small = rng.gen::<u32>() as u128 | 1;
+            check(n, small);
+
+            let wide: u128 = rng.gen::<u128>() | 1;
+            check(n, wide);
+        }
+    }
+
+    // Perfect squares, their neighbors and powers of two
+    fn isqrt_boundaries() -> Vec<u64> {
+        let mut values = vec![0, 1, 2, 3, u64::MAX, u64::MAX - 1];
+        for shift in 0..64 {
+            let v = 1u64 << shift;
+            values.extend([v - 1, v, v + 1]);
+        }
+
+        let squares = (0..1u64 << 32).step_by(65_537)
+            .chain((1u64 << 32) - 65_536..1u64 << 32);
+        for k in squares {
+            let square = k * k;
+            values.extend([square.wrapping_sub(1), square, square.wrapping_add(1)]);
+        }
+        values
+    }
+
+    fn check_isqrt(n: u64) {
+        let root = isqrt(n);
+        assert!(root < 1 << 32, "isqrt({}) = {}", n, root);
+        assert!(root * root <= n, "isqrt({}) = {}", n, root);
+        assert!(root + 1 == 1 << 32 || (root + 1) * (root + 1) > n, "isqrt({}) = {}", n, root);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_isqrt_boundaries() {
+        for n in isqrt_boundaries() {
+            check_isqrt(n);
+        }
+    }
+
+    #[test]
+    fn test_isqrt_random() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..ITERATIONS {
+            check_isqrt(rng.gen());
+            // Smaller values
+            check_isqrt(rng.gen::<u64>() >> rng.gen_range(0..64));
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_isqrt_newton() {
+        // Newton iteration previously used by v2, with the release wrapping semantics
+        // Returns `None` where it divided by zero
+        fn newton(n: u64) -> Option<u64> {
+            if n < 2 {
+                return Some(n);
+            }
+
+            let mut x = n;
+            let mut y = x.wrapping_add(1) >> 1;
+            while y < x {
+                x = y;
+                y = (x + n.checked_div(x)?) >> 1;
+            }
+            Some(x)
+        }
+
+        // u64::MAX had no root, v2 still rejects it with `Error::IsqrtOverflow`
+        assert_eq!(newton(u64::MAX), None);
+        assert_eq!(isqrt(u64::MAX), (1 << 32) - 1);
+
+        let mut rng = StdRng::seed_from_u64(0xCAFEBABEDEADBEEF);
+        let values = isqrt_boundaries()
+            .into_iter()
+            .chain((0..ITERATIONS).map(|_| rng.gen()))
+            .filter(|&n| n != u64::MAX);
+
+        for n in values {
+            assert_eq!(Some(isqrt(n)), newton(n), "isqrt({})", n);
+        }
+    }
+
+    #[test]
+    fn test_isqrt_std() {
+        let mut rng = StdRng::seed_from_u64(0xBADC0FFEE0DDF00D);
+        for _ in 0..ITERATIONS {
+            // Every normalization shift
+            let n = rng.gen::<u64>() >> rng.gen_range(0..64);
+            assert_eq!(isqrt(n), n.isqrt(), "isqrt({})", n);
+        }
+
+        // Lowest values of each seed, where the seed is the furthest from the root
+        for i in 64..256u64 {
+            for shift in (0..64).step_by(2) {
+                let n = (i << 56) >> shift;
+                for n in [n, n.wrapping_sub(1), n + 1] {
+                    assert_eq!(isqrt(n), n.isqrt(), "isqrt({})", n);
+                }
+            }
+        }
+    }
+
+    // Exhaustive check of every perfect square and its neighbors against the std implementation
+    // Run it with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_isqrt_all_squares() {
+        for k in 0..1u64 << 32 {
+            let square = k * k;
+            for n in [square.wrapping_sub(1), square, square.wrapping_add(1)] {
+                assert_eq!(isqrt(n), n.isqrt(), "isqrt({})", n);
+            }
+        }
+    }
+
+    // Square-and-multiply previously used by v3
+    fn naive_modular_power(mut base: u64, mut exp: u64, modulus: u64) -> u64 {
+        let mut result: u64 = 1;
+        base %= modulus;
+
+        while exp > 0 {
+            if exp & 1 == 1 {
+                result = ((result as u128 * base as u128) % modulus as u128) as u64;
+            }
+
+            base = ((base as u128 * base as u128) % modulus as u128) as u64;
+            exp /= 2;
+        }
+
+        result
+    }
+
+    #[test]
+    fn test_modular_power_edge_cases() {
+        let moduli = [
+            1, 2, 3, 4, 5, 6, 7, 8, 9,
+            (1 << 32) - 1, 1 << 32, (1 << 32) + 1,
+            (1 << 63) - 1, 1 << 63, (1 << 63) + 1,
+            u64::MAX - 2, u64::MAX - 1, u64::MAX,
+        ];
+
+        for modulus in moduli {
+            let bases = [0, 1, 2, modulus - 1, modulus, modulus.wrapping_add(1), u64::MAX - 1, u64::MAX];
+            let exps = [0, 1, 2, 3, 63, 64, 65, u32::MAX as u64, u64::MAX - 1, u64::MAX];
+            for base in bases {
+                for exp in exps {
+                    assert_eq!(
+                        modular_power(base, exp, modulus),
+                        Some(naive_modular_power(base, exp, modulus)),
+                        "{}^{} % {}", base, exp, modulus
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_modular_power_random() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..100_000 {
+            let base: u64 = rng.gen();
+            let exp: u64 = rng.gen();
+            let modulus = rng.gen::<u64>().max(1);
+
+            // Odd, even and small moduli
+            for modulus in [modulus | 1, (modulus & !1).max(2), modulus >> rng.gen_range(32..64) | 1] {
+                assert_eq!(
+                    modular_power(base, exp, modulus),
+                    Some(naive_modular_power(base, exp, modulus)),
+                    "{}^{} % {}", base, exp, modulus
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_modular_power_zero_modulus() {
+        for exp in [0, 1, u64::MAX] {
+            for base in [0, 1, u64::MAX] {
+                assert_eq!(modular_power(base, exp, 0), None);
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_zero_divisor() {
+        div_rem_u128_by_u64(1, 0);
+    }
+}
@@ -0,0 +1,211 @@
+// Hooks called by the stage 3 of v2 and v3 to observe its execution,
+// such as the branches taken and the scratchpad accesses
+// All the hooks do nothing by default: with `NoopObserver` they compile away entirely.
+//
+// Memory indexes are in u64s from the start of the scratchpad,
+// the second half of the scratchpad starting at `BUFFER_SIZE`.
+//
+// The stage 3 calls the `_at` hooks with the access site, which call the plain ones by default.
+pub trait Observer {
+    // Called at the start of the stage 3, before its first scratchpad read
+    #[inline(always)]
+    fn on_start(&mut self) {}
+
+    // Called at the start of each inner iteration `j` of the scratchpad iteration `i`
+    #[inline(always)]
+    fn on_iteration(&mut self, _i: usize, _j: usize) {}
+
+    // Called with the branch id (0 to 15) used by the inner iteration
+    #[inline(always)]
+    fn on_branch(&mut self, _branch: u8) {}
+
+    // Called for each scratchpad word read, in program order
+    #[inline(always)]
+    fn on_mem_read(&mut self, _index: usize) {}
+
+    // Called for each scratchpad word written, in program order
+    #[inline(always)]
+    fn on_mem_write(&mut self, _index: usize) {}
+
+    #[inline(always)]
+    fn on_mem_read_at(&mut self, index: usize, _site: Site) {
+        self.on_mem_read(index)
+    }
+
+    #[inline(always)]
+    fn on_mem_write_at(&mut self, index: usize, _site: Site) {
+        self.on_mem_write(index)
+    }
+
+    // Called with the number of expensive operations executed, after them
+    #[inline(always)]
+    fn on_op(&mut self, _op: Op, _count: u64) {}
+
+    // Called at the end of the stage 3, not called if it fails
+    #[inline(always)]
+    fn on_finish(&mut self) {}
+}
+
+// Place of a scratchpad access in the stage 3, named after the index used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Site {
+    // Words giving the first addresses, read once per hash
+    Start,
+    // `index_a` and `index_b` of each scratchpad iteration
+    IterationA,
+    IterationB,
+    // `index_a` and `index_b` of each inner iteration
+    A,
+    B,
+    // Sequential index of the third operand
+    R,
+    // v2 read-modify-write of `index_t`, v3 read of `index_t` in either half
+    T,
+    // v2 read-modify-write of the inner iteration index in the second half
+    J,
+    // v3 read-modify-write of the second `index_a` and `index_b`
+    UpdateA,
+    UpdateB,
+}
+
+impl Site {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::IterationA => "iteration index_a",
+            Self::IterationB => "iteration index_b",
+            Self::A => "index_a",
+            Self::B => "index_b",
+            Self::R => "r",
+            Self::T => "index_t",
+            Self::J => "j",
+            Self::UpdateA => "update index_a",
+            Self::UpdateB => "update index_b",
+        }
+    }
+}
+
+// Expensive operation of the stage 3
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Op {
+    // u128 division or remainder, with a divisor fitting in 64 bits or not
+    DivRem128,
+    // u64 division or remainder by a variable
+    DivRem64,
+    // u128 multiplication, including the widening ones of the v3 `map_index`
+    Mul128,
+    Isqrt,
+    // Iteration of the modular power loop, one per bit of the exponent
+    ModularPowerStep,
+    AesRound,
+}
+
+// Observer doing nothing, used by `xelis_hash`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {}
+
+impl<O: Observer + ?Sized> Observer for &mut O {
+    #[inline(always)]
+    fn on_start(&mut self) {
+        (**self).on_start()
+    }
+
+    #[inline(always)]
+    fn on_iteration(&mut self, i: usize, j: usize) {
+        (**self).on_iteration(i, j)
+    }
+
+    #[inline(always)]
+    fn on_branch(&mut self, branch: u8) {
+        (**self).on_branch(branch)
+    }
+
+    #[inline(always)]
+    fn on_mem_read(&mut self, index: usize) {
+        (**self).on_mem_read(index)
+    }
+
+    #[inline(always)]
+    fn on_mem_write(&mut self, index: usize) {
+        (**self).on_mem_write(index)
+    }
+
+    #[inline(always)]
+    fn on_mem_read_at(&mut self, index: usize, site: Site) {
+        (**self).on_mem_read_at(index, site)
+    }
+
+    #[inline(always)]
+    fn on_mem_write_at(&mut self, index: usize, site: Site) {
+        (**self).on_mem_write_at(index, site)
+    }
+
+    #[inline(always)]
+    fn on_op(&mut self, op: Op, count: u64) {
+        (**self).on_op(op, count)
+    }
+
+    #[inline(always)]
+    fn on_finish(&mut self) {
+        (**self).on_finish()
+    }
+}
+
+// Both observers are called, the first one first
+impl<A: Observer, B: Observer> Observer for (A, B) {
+    #[inline(always)]
+    fn on_start(&mut self) {
+        self.0.on_start();
+        self.1.on_start();
+    }
+
+    #[inline(always)]
+    fn on_iteration(&mut self, i: usize, j: usize) {
+        self.0.on_iteration(i, j);
+        self.1.on_iteration(i, j);
+    }
+
+    #[inline(always)]
+    fn on_branch(&mut self, branch: u8) {
+        self.0.on_branch(branch);
+        self.1.on_branch(branch);
+    }
+
+    #[inline(always)]
+    fn on_mem_read(&mut self, index: usize) {
+        self.0.on_mem_read(index);
+        self.1.on_mem_read(index);
+    }
+
+    #[inline(always)]
+    fn on_mem_write(&mut self, index: usize) {
+        self.0.on_mem_write(index);
+        self.1.on_mem_write(index);
+    }
+
+    #[inline(always)]
+    fn on_mem_read_at(&mut self, index: usize, site: Site) {
+        self.0.on_mem_read_at(index, site);
+        self.1.on_mem_read_at(index, site);
+    }
+
+    #[inline(always)]
+    fn on_mem_write_at(&mut self, index: usize, site: Site) {
+        self.0.on_mem_write_at(index, site);
+        self.1.on_mem_write_at(index, site);
+    }
+
+    #[inline(always)]
+    fn on_op(&mut self, op: Op, count: u64) {
+        self.0.on_op(op, count);
+        self.1.on_op(op, count);
+    }
+
+    #[inline(always)]
+    fn on_finish(&mut self) {
+        self.0.on_finish();
+        self.1.on_finish();
+    }
+}
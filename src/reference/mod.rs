@@ -0,0 +1,124 @@
+// Reference implementations of each algorithm version
+//
+// They are written as directly as possible from the specification:
+// the scratchpad is a single freshly allocated vector of u64 words,
+// all conversions are explicit little-endian ones and the u128 operations
+// are done with the plain operators.
+// They are slow and are only meant to validate the optimized implementations.
+
+#[cfg(feature = "v1")]
+pub mod v1;
+#[cfg(feature = "v2")]
+pub mod v2;
+#[cfg(feature = "v3")]
+pub mod v3;
+
+#[cfg(any(test, feature = "v1", feature = "v2"))]
+use crate::aes_round;
+
+// Scratchpad words after each stage, by stage name
+pub type Stages = Vec<(&'static str, Vec<u64>)>;
+
+// Apply a single AES round (SubBytes, ShiftRows, MixColumns, AddRoundKey)
+// on the block composed of `low` followed by `high` in little-endian,
+// and return the first 8 bytes of the output as a little-endian u64
+// followed by the last 8 bytes
+#[cfg(any(test, feature = "v1", feature = "v2"))]
+fn cipher_round(low: u64, high: u64, key: &aes_round::Block) -> (u64, u64) {
+    let mut block = [0u8; aes_round::BLOCK_SIZE];
+    block[..8].copy_from_slice(&low.to_le_bytes());
+    block[8..].copy_from_slice(&high.to_le_bytes());
+
+    // The software round is validated against the `aes` crate
+    aes_round::cipher_round_soft(&mut block, key);
+
+    let mut first = [0u8; 8];
+    let mut second = [0u8; 8];
+    first.copy_from_slice(&block[..8]);
+    second.copy_from_slice(&block[8..]);
+    (u64::from_le_bytes(first), u64::from_le_bytes(second))
+}
+
+// Exact integer square root: the largest `x` such that `x * x <= n`
+// Found by a binary search over [0, 2^32)
+#[cfg(feature = "v2")]
+fn isqrt(n: u64) -> u64 {
+    let mut low: u64 = 0;
+    let mut high: u64 = 1 << 32;
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if (mid as u128) * (mid as u128) <= n as u128 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+
+    low
+}
+
+// Combine two u64 into a u128, `high` being the most significant part
+#[cfg(feature = "v2")]
+fn combine(high: u64, low: u64) -> u128 {
+    ((high as u128) << 64) + low as u128
+}
+
+// Upper 64 bits of the 128 bits product, truncated to 128 bits first
+#[cfg(feature = "v2")]
+fn mul_high(a: u128, b: u128) -> u64 {
+    (a.wrapping_mul(b) >> 64) as u64
+}
+
+// Random u64 biased toward the edge cases of the stage 3 operations
+#[cfg(all(test, feature = "v2"))]
+fn random_value<R: rand::Rng>(rng: &mut R) -> u64 {
+    const EDGES: [u64; 8] = [0, 1, 2, 1 << 32, (1 << 32) - 1, u64::MAX - 1024, u64::MAX - 1, u64::MAX];
+
+    match rng.gen_range(0..4) {
+        0 => EDGES[rng.gen_range(0..EDGES.len())],
+        1 => rng.gen::<u32>() as u64,
+        _ => rng.gen(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use super::*;
+
+    #[cfg(feature = "v2")]
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_isqrt() {
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(1), 1);
+        assert_eq!(isqrt(3), 1);
+        assert_eq!(isqrt(4), 2);
+        assert_eq!(isqrt(u64::MAX), (1 << 32) - 1);
+
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..100_000 {
+            let n: u64 = rng.gen();
+            assert_eq!(isqrt(n), crate::math::isqrt(n), "n: {}", n);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_cipher_round() {
+        let mut rng = StdRng::seed_from_u64(0xCAFEBABEDEADBEEF);
+        let key = *b"xelishash-pow-v3";
+        for _ in 0..100_000 {
+            let (low, high): (u64, u64) = rng.gen();
+
+            let mut block = [0u8; aes_round::BLOCK_SIZE];
+            block[..8].copy_from_slice(&low.to_le_bytes());
+            block[8..].copy_from_slice(&high.to_le_bytes());
+            aes_round::cipher_round(&mut block, &key);
+
+            let (first, second) = cipher_round(low, high, &key);
+            assert_eq!(&block[..8], &first.to_le_bytes());
+            assert_eq!(&block[8..], &second.to_le_bytes());
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use tiny_keccak::keccakp;
+
+use crate::{Hash, HASH_SIZE};
+use super::{cipher_round, Stages};
+
+// Scratchpad size in u64s
+pub const MEMORY_SIZE: usize = 32768;
+// Stage 3 iterations
+pub const SCRATCHPAD_ITERS: usize = 5000;
+// Stage 3 buffers size
+pub const BUFFER_SIZE: usize = 42;
+// Stage 2 slots count
+pub const SLOT_LENGTH: usize = 256;
+// Keccak state size in u64s
+pub const KECCAK_WORDS: usize = 25;
+// Input size in bytes
+pub const BYTES_ARRAY_INPUT: usize = KECCAK_WORDS * 8;
+
+// Stage 1: fill the scratchpad with successive Keccak-p permutations of the input
+// Each word of the state is mixed with the previously written word and a branch
+// depending on the two next words of the state
+fn stage_1(state: &mut [u64; KECCAK_WORDS], memory: &mut [u64]) {
+    for block in memory.chunks_mut(KECCAK_WORDS) {
+        keccakp(state);
+
+        let mut previous: u64 = 0;
+        for (j, slot) in block.iter_mut().enumerate() {
+            let left = state[(j + 1) % KECCAK_WORDS];
+            let right = state[(j + 2) % KECCAK_WORDS];
+            let xor = left ^ right;
+            let v = match xor & 0x3 {
+                0 => left & right,
+                1 => !(left & right),
+                2 => !xor,
+                3 => xor,
+                _ => unreachable!(),
+            };
+
+            previous = state[j] ^ previous ^ v;
+            *slot = previous;
+        }
+    }
+}
+
+// Sign of a pad value depending on the slot: positive if its highest bit is unset
+fn signed(slot: u32, pad_value: u32) -> u32 {
+    if slot >> 31 == 0 { pad_value } else { pad_value.wrapping_neg() }
+}
+
+// Stage 2: the scratchpad is seen as u32s (low half of each word first)
+// The slots are initialized with the last SLOT_LENGTH values.
+// For each group of SLOT_LENGTH values, the slots are visited in a pseudo random order
+// and each visited slot is increased by the signed sum of the group values except its own.
+fn stage_2(memory: &mut [u64]) {
+    let mut small_pad: Vec<u32> = memory.iter()
+        .flat_map(|word| [*word as u32, (*word >> 32) as u32])
+        .collect();
+
+    let mut slots = [0u32; SLOT_LENGTH];
+    slots.copy_from_slice(&small_pad[small_pad.len() - SLOT_LENGTH..]);
+
+    for group in small_pad.chunks(SLOT_LENGTH) {
+        let mut indices: Vec<usize> = (0..SLOT_LENGTH).collect();
+        for slot_idx in (0..SLOT_LENGTH).rev() {
+            let picked = group[slot_idx] as usize % (slot_idx + 1);
+            let index = indices[picked];
+            indices[picked] = indices[slot_idx];
+
+            let sum = (0..SLOT_LENGTH)
+                .filter(|k| *k != index)
+                .fold(0u32, |sum, k| sum.wrapping_add(signed(slots[k], group[k])));
+
+            slots[index] = slots[index].wrapping_add(sum);
+        }
+    }
+
+    let len = small_pad.len();
+    small_pad[len - SLOT_LENGTH..].copy_from_slice(&slots);
+
+    for (word, halves) in memory.iter_mut().zip(small_pad.chunks_exact(2)) {
+        *word = halves[0] as u64 | (halves[1] as u64) << 32;
+    }
+}
+
+// Stage 3 branch selected by the 4 bits of `result` at position `2 * j`
+fn branch(result: u64, a: u64, b: u64, j: usize) -> u64 {
+    match (result >> (2 * j)) & 0xf {
+        0 => result.rotate_left(j as u32) ^ b,
+        1 => !(result.rotate_left(j as u32) ^ a),
+        2 => !(result ^ a),
+        3 => result ^ b,
+        4 => result ^ a.wrapping_add(b),
+        5 => result ^ a.wrapping_sub(b),
+        6 => result ^ b.wrapping_sub(a),
+        7 => result ^ a.wrapping_mul(b),
+        8 => result ^ (a & b),
+        9 => result ^ (a | b),
+        10 => result ^ a ^ b,
+        11 => result ^ a.wrapping_sub(result),
+        12 => result ^ b.wrapping_sub(result),
+        13 => result ^ a.wrapping_add(result),
+        14 => result ^ result.wrapping_sub(a),
+        15 => result ^ result.wrapping_sub(b),
+        _ => unreachable!(),
+    }
+}
+
+// Stage 3: two small buffers are loaded from the scratchpad and mixed using AES
+// The last 4 results give the hash in big-endian
+fn stage_3(memory: &mut [u64]) -> Hash {
+    let key = [0u8; 16];
+    let last = memory[MEMORY_SIZE - 1];
+    let addr_a = ((last >> 15) & 0x7FFF) as usize;
+    let addr_b = (last & 0x7FFF) as usize;
+
+    let mut buffer_a: Vec<u64> = (0..BUFFER_SIZE).map(|i| memory[(addr_a + i) % MEMORY_SIZE]).collect();
+    let mut buffer_b: Vec<u64> = (0..BUFFER_SIZE).map(|i| memory[(addr_b + i) % MEMORY_SIZE]).collect();
+
+    let mut hash = [0u8; HASH_SIZE];
+    for i in 0..SCRATCHPAD_ITERS {
+        let mem_a = buffer_a[i % BUFFER_SIZE];
+        let mem_b = buffer_b[i % BUFFER_SIZE];
+
+        let (hash1, _) = cipher_round(mem_b, mem_a, &key);
+        let mut result = !(hash1 ^ mem_a ^ mem_b);
+
+        for j in 0..HASH_SIZE {
+            let a = buffer_a[(i + j) % BUFFER_SIZE];
+            let b = buffer_b[(i + j) % BUFFER_SIZE];
+            result = branch(result, a, b, j);
+        }
+
+        buffer_a[i % BUFFER_SIZE] = result;
+        buffer_b[i % BUFFER_SIZE] = memory[(result & 0x7FFF) as usize];
+        memory[((result >> 15) & 0x7FFF) as usize] = result;
+
+        let index = SCRATCHPAD_ITERS - i - 1;
+        if index < 4 {
+            hash[index * 8..(index + 1) * 8].copy_from_slice(&result.to_be_bytes());
+        }
+    }
+
+    hash
+}
+
+// Convert the input bytes to the initial Keccak state in little-endian
+fn initial_state(input: &[u8; BYTES_ARRAY_INPUT]) -> [u64; KECCAK_WORDS] {
+    let mut state = [0u64; KECCAK_WORDS];
+    for (word, bytes) in state.iter_mut().zip(input.chunks_exact(8)) {
+        let mut le = [0u8; 8];
+        le.copy_from_slice(bytes);
+        *word = u64::from_le_bytes(le);
+    }
+    state
+}
+
+// Hash the input using a newly allocated scratchpad
+pub fn xelis_hash(input: &[u8; BYTES_ARRAY_INPUT]) -> Hash {
+    let mut state = initial_state(input);
+    let mut memory = vec![0u64; MEMORY_SIZE];
+    stage_1(&mut state, &mut memory);
+    stage_2(&mut memory);
+    stage_3(&mut memory)
+}
+
+// Hash the input and return the scratchpad after each stage
+// Used to give the per-stage digests of the test vectors
+pub fn xelis_hash_with_stages(input: &[u8; BYTES_ARRAY_INPUT]) -> (Hash, Stages) {
+    let mut state = initial_state(input);
+    let mut memory = vec![0u64; MEMORY_SIZE];
+    let mut stages = Vec::new();
+
+    stage_1(&mut state, &mut memory);
+    stages.push(("stage_1", memory.clone()));
+    stage_2(&mut memory);
+    stages.push(("stage_2", memory.clone()));
+    let hash = stage_3(&mut memory);
+    stages.push(("stage_3", memory));
+
+    (hash, stages)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
+    use crate::v1::{self, AlignedInput, ScratchPad};
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_zero_input() {
+        let expected_hash = [
+            0x0e, 0xbb, 0xbd, 0x8a, 0x31, 0xed, 0xad, 0xfe, 0x09, 0x8f, 0x2d, 0x77, 0x0d, 0x84,
+            0xb7, 0x19, 0x58, 0x86, 0x75, 0xab, 0x88, 0xa0, 0xa1, 0x70, 0x67, 0xd0, 0x0a, 0x8f,
+            0x36, 0x18, 0x22, 0x65,
+        ];
+
+        assert_eq!(xelis_hash(&[0u8; BYTES_ARRAY_INPUT]), expected_hash);
+    }
+
+    // Compare the hashes of `count` random inputs
+    fn differential(count: usize) {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        // The scratchpad is reused to also cover a dirty one
+        let mut scratch_pad = ScratchPad::default();
+        let mut input = AlignedInput::default();
+
+        for _ in 0..count {
+            let bytes = input.as_mut_slice().unwrap();
+            rng.fill_bytes(bytes);
+            let expected = xelis_hash(bytes);
+
+            let hash = v1::xelis_hash(bytes, &mut scratch_pad).unwrap();
+            assert_eq!(hash, expected);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_differential() {
+        differential(16);
+    }
+
+    // cargo test --release -- --ignored differential_large
+    #[test]
+    #[ignore]
+    fn test_differential_large() {
+        differential(20_000);
+    }
+}
@@ -0,0 +1,237 @@
+use blake3::hash as blake3_hash;
+use chacha20::{
+    cipher::{KeyIvInit, StreamCipher},
+    ChaCha8,
+};
+
+use crate::{Error, Hash, HASH_SIZE};
+use super::{cipher_round, combine, isqrt, mul_high, Stages};
+
+// Scratchpad size in u64s
+pub const MEMORY_SIZE: usize = 429 * 128;
+// Stage 3 outer iterations
+pub const SCRATCHPAD_ITERS: usize = 3;
+// Each half of the scratchpad used in stage 3
+pub const BUFFER_SIZE: usize = MEMORY_SIZE / 2;
+
+// Stage 1 input chunk size
+const CHUNK_SIZE: usize = 32;
+// ChaCha8 nonce size
+const NONCE_SIZE: usize = 12;
+
+// Stage 3 AES key
+const KEY: [u8; 16] = *b"xelishash-pow-v2";
+
+// Stage 1: fill a scratchpad of `memory_size` u64s using ChaCha8
+// The input is split in chunks of 32 bytes, each one is hashed with the previous
+// key using Blake3 to give the next key. The first nonce is the beginning of the
+// input Blake3 hash, the next ones are the last bytes generated by the previous chunk.
+// The output is split evenly between the remaining chunks.
+pub(super) fn stage_1(input: &[u8], memory_size: usize) -> Result<Vec<u64>, Error> {
+    let output_size = memory_size * 8;
+    let mut output = vec![0u8; output_size];
+
+    let mut key: Hash = blake3_hash(input).into();
+    let mut nonce = [0u8; NONCE_SIZE];
+    nonce.copy_from_slice(&key[..NONCE_SIZE]);
+
+    let num_chunks = input.len().div_ceil(CHUNK_SIZE);
+    let mut generated = 0;
+    for (index, chunk) in input.chunks(CHUNK_SIZE).enumerate() {
+        // The chunk is zero padded
+        let mut data = [0u8; HASH_SIZE + CHUNK_SIZE];
+        data[..HASH_SIZE].copy_from_slice(&key);
+        data[HASH_SIZE..HASH_SIZE + chunk.len()].copy_from_slice(chunk);
+        key = blake3_hash(&data).into();
+
+        let size = (output_size - generated) / (num_chunks - index);
+        // The part is written at `index * size` and not after the previous one
+        // The input has no valid hash if the part doesn't fit or can't give a nonce
+        let offset = index * size;
+        if size < NONCE_SIZE || offset + size > output_size {
+            return Err(Error::InvalidInputLength(input.len()));
+        }
+
+        let part = &mut output[offset..offset + size];
+        ChaCha8::new(&key.into(), &nonce.into()).apply_keystream(part);
+        nonce.copy_from_slice(&part[size - NONCE_SIZE..]);
+
+        generated += size;
+    }
+
+    Ok(output.chunks_exact(8)
+        .map(|bytes| {
+            let mut word = [0u8; 8];
+            word.copy_from_slice(bytes);
+            u64::from_le_bytes(word)
+        })
+        .collect())
+}
+
+// Stage 4: Blake3 hash of the whole scratchpad in little-endian
+pub(super) fn stage_4(memory: &[u64]) -> Hash {
+    let bytes: Vec<u8> = memory.iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect();
+
+    blake3_hash(&bytes).into()
+}
+
+// Stage 3 branch selected by `index`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn branch(index: u8, a: u64, b: u64, c: u64, result: u64, r: usize, i: usize, j: usize) -> u64 {
+    match index {
+        0 => c.rotate_left((i * j) as u32) ^ b,
+        1 => c.rotate_right((i * j) as u32) ^ a,
+        2 => a ^ b ^ c,
+        3 => a.wrapping_add(b).wrapping_mul(c),
+        4 => b.wrapping_sub(c).wrapping_mul(a),
+        5 => c.wrapping_sub(a).wrapping_add(b),
+        6 => a.wrapping_sub(b).wrapping_add(c),
+        7 => b.wrapping_mul(c).wrapping_add(a),
+        8 => c.wrapping_mul(a).wrapping_add(b),
+        9 => a.wrapping_mul(b).wrapping_mul(c),
+        10 => (combine(a, b) % (c | 1) as u128) as u64,
+        11 => (combine(b, c) % combine(result.rotate_left(r as u32), a | 2)) as u64,
+        12 => (combine(c, a) / (b | 4) as u128) as u64,
+        13 => {
+            let t1 = combine(result.rotate_left(r as u32), b);
+            let t2 = combine(a, c | 8);
+            if t1 > t2 { (t1 / t2) as u64 } else { a ^ b }
+        },
+        14 => mul_high(combine(b, a), c as u128),
+        15 => mul_high(combine(a, c), combine(result.rotate_right(r as u32), b)),
+        _ => unreachable!(),
+    }
+}
+
+// Stage 3: the first half of the scratchpad is the buffer A, the second half the buffer B
+fn stage_3(memory: &mut [u64]) -> Result<(), Error> {
+    let mut addr_a = memory[MEMORY_SIZE - 1];
+    let mut addr_b = memory[BUFFER_SIZE - 1] >> 32;
+    let mut r = 0;
+
+    for i in 0..SCRATCHPAD_ITERS {
+        let mem_a = memory[(addr_a % BUFFER_SIZE as u64) as usize];
+        let mem_b = memory[BUFFER_SIZE + (addr_b % BUFFER_SIZE as u64) as usize];
+
+        let (hash1, _) = cipher_round(mem_b, mem_a, &KEY);
+        let mut result = !(hash1 ^ mem_a ^ mem_b);
+
+        for j in 0..BUFFER_SIZE {
+            let a = memory[(result % BUFFER_SIZE as u64) as usize];
+            let b = memory[BUFFER_SIZE + (!result.rotate_right(r as u32) % BUFFER_SIZE as u64) as usize];
+            let c = memory[r];
+            r = (r + 1) % MEMORY_SIZE;
+
+            let index = (result.rotate_left(c as u32) & 0xf) as u8;
+            result = (result ^ branch(index, a, b, c, result, r, i, j)).rotate_left(1);
+
+            let t = memory[BUFFER_SIZE - j - 1] ^ result;
+            memory[BUFFER_SIZE - j - 1] = t;
+            memory[BUFFER_SIZE + j] ^= t.rotate_right(result as u32);
+        }
+
+        // 2^64 - 1 has no root in the other ports, which divide by zero
+        if result == u64::MAX {
+            return Err(Error::IsqrtOverflow);
+        }
+        addr_a = result;
+        addr_b = isqrt(result);
+    }
+
+    Ok(())
+}
+
+// Hash the input using a newly allocated scratchpad
+pub fn xelis_hash(input: &[u8]) -> Result<Hash, Error> {
+    let mut memory = stage_1(input, MEMORY_SIZE)?;
+    stage_3(&mut memory)?;
+    Ok(stage_4(&memory))
+}
+
+// Hash the input and return the scratchpad after the stage 1
+// The scratchpad after the stage 3 is not returned as the hash is its digest
+// Used to give the per-stage digests of the test vectors
+pub fn xelis_hash_with_stages(input: &[u8]) -> Result<(Hash, Stages), Error> {
+    let mut memory = stage_1(input, MEMORY_SIZE)?;
+    let stages = vec![("stage_1", memory.clone())];
+    stage_3(&mut memory)?;
+
+    Ok((stage_4(&memory), stages))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+    use crate::{observer::NoopObserver, v2::{self, ScratchPad}};
+    use super::super::random_value;
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_zero_hash() {
+        let expected_hash = [
+            126, 219, 112, 240, 116, 133, 115, 144, 39, 40, 164,
+            105, 30, 158, 45, 126, 64, 67, 238, 52, 200, 35,
+            161, 19, 144, 211, 214, 225, 95, 190, 146, 27
+        ];
+
+        assert_eq!(xelis_hash(&[0u8; 112]).unwrap(), expected_hash);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_differential_branches() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..1_000_000 {
+            let (a, b, c, result) = (random_value(&mut rng), random_value(&mut rng), random_value(&mut rng), random_value(&mut rng));
+            let r = rng.gen_range(0..MEMORY_SIZE);
+            let i = rng.gen_range(0..SCRATCHPAD_ITERS);
+            let j = rng.gen_range(0..BUFFER_SIZE);
+
+            for index in 0..16 {
+                assert_eq!(
+                    v2::branch(index, a, b, c, result, r, i, j, &mut NoopObserver),
+                    branch(index, a, b, c, result, r, i, j),
+                    "branch: {}, a: {}, b: {}, c: {}, result: {}, r: {}, i: {}, j: {}", index, a, b, c, result, r, i, j
+                );
+            }
+        }
+    }
+
+    // Compare the hashes of `count` random inputs
+    fn differential(count: usize) {
+        let mut rng = StdRng::seed_from_u64(0xCAFEBABEDEADBEEF);
+        // The scratchpad is reused to also cover a dirty one
+        let mut scratch_pad = ScratchPad::default();
+
+        for _ in 0..count {
+            // Most inputs have the miner work size, others a random length
+            let len = if rng.gen_bool(0.5) { 112 } else { rng.gen_range(0..=512) };
+            let mut input = vec![0u8; len];
+            rng.fill_bytes(&mut input);
+
+            let expected = xelis_hash(&input);
+            let hash = v2::xelis_hash(&input, &mut scratch_pad);
+            match (hash, expected) {
+                (Ok(hash), Ok(expected)) => assert_eq!(hash, expected, "input: {:?}", input),
+                (Err(e), Err(expected)) => assert_eq!(e.to_string(), expected.to_string()),
+                (hash, expected) => panic!("input: {:?}, got {:?}, expected {:?}", input, hash, expected),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_differential() {
+        differential(32);
+    }
+
+    // cargo test --release -- --ignored differential_large
+    #[test]
+    #[ignore]
+    fn test_differential_large() {
+        differential(20_000);
+    }
+}
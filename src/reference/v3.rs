@@ -0,0 +1,257 @@
+use crate::{Error, Hash};
+use super::{cipher_round, combine, mul_high, v2, Stages};
+
+// Scratchpad size in u64s
+pub const MEMORY_SIZE: usize = 531 * 128;
+// Stage 3 outer iterations
+pub const SCRATCHPAD_ITERS: usize = 2;
+// Each half of the scratchpad used in stage 3
+pub const BUFFER_SIZE: usize = MEMORY_SIZE / 2;
+
+// Stage 3 AES key
+const KEY: [u8; 16] = *b"xelishash-pow-v3";
+
+// MurmurHash3 64 bits finalizer
+pub(crate) fn murmurhash3(mut seed: u64) -> u64 {
+    seed ^= seed >> 55;
+    seed = seed.wrapping_mul(0xff51afd7ed558ccd);
+    seed ^= seed >> 32;
+    seed = seed.wrapping_mul(0xc4ceb9fe1a85ec53);
+    seed ^= seed >> 15;
+    seed
+}
+
+// Map a value into [0, BUFFER_SIZE) using the upper part of its product with BUFFER_SIZE
+pub fn map_index(x: u64) -> usize {
+    let x = (x ^ (x >> 33)).wrapping_mul(0xff51afd7ed558ccd);
+    ((x as u128 * BUFFER_SIZE as u128) >> 64) as usize
+}
+
+// Select the buffer B if the bit 58 of the seed hash is set
+pub fn pick_half(seed: u64) -> bool {
+    (murmurhash3(seed) >> 58) & 1 == 1
+}
+
+// Integer square root as defined by the consensus
+// It is the exact one below (2^32 - 1)^2
+pub fn isqrt(n: u64) -> u64 {
+    if n >= u64::MAX - 1023 {
+        (1 << 32) + 1
+    } else if n >= ((1u64 << 32) - 1) * ((1u64 << 32) - 1) {
+        1 << 32
+    } else {
+        super::isqrt(n)
+    }
+}
+
+// `base ^ exp % modulus` using the square-and-multiply method
+// There is no result for a zero modulus
+pub fn modular_power(base: u64, exp: u64, modulus: u64) -> Option<u64> {
+    if modulus == 0 {
+        return None;
+    }
+
+    let mut result: u64 = 1;
+    let mut base = base % modulus;
+    let mut exp = exp;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = (result as u128 * base as u128 % modulus as u128) as u64;
+        }
+        base = (base as u128 * base as u128 % modulus as u128) as u64;
+        exp >>= 1;
+    }
+
+    Some(result)
+}
+
+// Stage 3 branch selected by `index`
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn branch(index: u8, a: u64, b: u64, c: u64, result: u64, r: usize, i: usize, j: usize) -> u64 {
+    let (i, j) = (i as u64, j as u64);
+    match index {
+        0 => {
+            let t = combine(a.wrapping_add(i), isqrt(b.wrapping_add(j)));
+            (t % (murmurhash3(c ^ result ^ i ^ j) | 1) as u128) as u64
+        },
+        1 => {
+            let t = c.wrapping_add(i) % isqrt(b | 2);
+            t.rotate_left((i + j) as u32).wrapping_mul(isqrt(a.wrapping_add(j)))
+        },
+        2 => isqrt(a.wrapping_add(i)).wrapping_mul(isqrt(c.wrapping_add(j))) ^ b.wrapping_add(i + j),
+        3 => a.wrapping_add(b).wrapping_mul(c),
+        4 => b.wrapping_sub(c).wrapping_mul(a),
+        5 => c.wrapping_sub(a).wrapping_add(b),
+        6 => a.wrapping_sub(b).wrapping_add(c),
+        7 => b.wrapping_mul(c).wrapping_add(a),
+        8 => c.wrapping_mul(a).wrapping_add(b),
+        9 => a.wrapping_mul(b).wrapping_mul(c),
+        10 => (combine(a, b) % (c | 1) as u128) as u64,
+        11 => {
+            let t1 = combine(b, c);
+            let t2 = combine(result.rotate_left(r as u32), a | 2);
+            if t2 > t1 { c } else { (t1 % t2) as u64 }
+        },
+        12 => (combine(c, a) / (b | 4) as u128) as u64,
+        13 => {
+            let t1 = combine(result.rotate_left(r as u32), b);
+            let t2 = combine(a, c | 8);
+            if t1 > t2 { (t1 / t2) as u64 } else { a ^ b }
+        },
+        14 => mul_high(combine(b, a), c as u128),
+        15 => mul_high(combine(a, c), combine(result.rotate_right(r as u32), b)),
+        _ => unreachable!(),
+    }
+}
+
+// Stage 3: the first half of the scratchpad is the buffer A, the second half the buffer B
+fn stage_3(memory: &mut [u64]) -> Result<(), Error> {
+    let mut addr_a = memory[MEMORY_SIZE - 1];
+    let mut addr_b = memory[BUFFER_SIZE - 1] >> 32;
+    let mut r = 0;
+
+    for i in 0..SCRATCHPAD_ITERS {
+        let mem_a = memory[map_index(addr_a)];
+        let mem_b = memory[BUFFER_SIZE + map_index(mem_a ^ addr_b)];
+
+        let (hash1, hash2) = cipher_round(mem_b, mem_a, &KEY);
+        let mut result = !(hash1 ^ hash2);
+
+        for j in 0..BUFFER_SIZE {
+            let a = memory[map_index(result)];
+            let b = memory[BUFFER_SIZE + map_index(a ^ !result.rotate_right(r as u32))];
+            let c = memory[r];
+            r = (r + 1) % MEMORY_SIZE;
+
+            let index = (result.rotate_left(c as u32) & 0xf) as u8;
+            let v = branch(index, a, b, c, result, r, i, j);
+
+            let seed = v ^ result;
+            result = seed.rotate_left(r as u32);
+
+            let half = if pick_half(v) { BUFFER_SIZE } else { 0 };
+            let t = memory[half + map_index(seed)] ^ result;
+
+            let index_a = map_index(t ^ result ^ 0x9e3779b97f4a7c15);
+            let index_b = map_index(index_a as u64 ^ !result ^ 0xd2b74407b1ce6e93);
+
+            let previous = memory[index_a];
+            memory[index_a] = t;
+            memory[BUFFER_SIZE + index_b] ^= previous ^ t.rotate_right((i + j) as u32);
+        }
+
+        let next_a = modular_power(addr_a, addr_b, result).ok_or(Error::ZeroModulus)?;
+        addr_b = isqrt(result).wrapping_mul(r as u64 + 1).wrapping_mul(isqrt(next_a));
+        addr_a = next_a;
+    }
+
+    Ok(())
+}
+
+// Hash the input using a newly allocated scratchpad
+pub fn xelis_hash(input: &[u8]) -> Result<Hash, Error> {
+    let mut memory = v2::stage_1(input, MEMORY_SIZE)?;
+    stage_3(&mut memory)?;
+    Ok(v2::stage_4(&memory))
+}
+
+// Hash the input and return the scratchpad after the stage 1
+// The scratchpad after the stage 3 is not returned as the hash is its digest
+// Used to give the per-stage digests of the test vectors
+pub fn xelis_hash_with_stages(input: &[u8]) -> Result<(Hash, Stages), Error> {
+    let mut memory = v2::stage_1(input, MEMORY_SIZE)?;
+    let stages = vec![("stage_1", memory.clone())];
+    stage_3(&mut memory)?;
+
+    Ok((v2::stage_4(&memory), stages))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, RngCore, SeedableRng};
+    use crate::{observer::NoopObserver, v3::{self, ScratchPad}};
+    use super::super::random_value;
+    use super::*;
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_zero_hash() {
+        let expected_hash = [
+            105, 172, 103, 40, 94, 253, 92, 162,
+            42, 252, 5, 196, 236, 238, 91, 218,
+            22, 157, 228, 233, 239, 8, 250, 57,
+            212, 166, 121, 132, 148, 205, 103, 163
+        ];
+
+        assert_eq!(xelis_hash(&[0u8; 112]).unwrap(), expected_hash);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_differential_branches() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..1_000_000 {
+            let (a, b, c, result) = (random_value(&mut rng), random_value(&mut rng), random_value(&mut rng), random_value(&mut rng));
+            let r = rng.gen_range(0..MEMORY_SIZE);
+            let i = rng.gen_range(0..SCRATCHPAD_ITERS);
+            let j = rng.gen_range(0..BUFFER_SIZE);
+
+            for index in 0..16 {
+                assert_eq!(
+                    v3::branch(index, a, b, c, result, r, i, j, &mut NoopObserver),
+                    branch(index, a, b, c, result, r, i, j),
+                    "branch: {}, a: {}, b: {}, c: {}, result: {}, r: {}, i: {}, j: {}", index, a, b, c, result, r, i, j
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_differential_primitives() {
+        let mut rng = StdRng::seed_from_u64(0xBADC0FFEE0DDF00D);
+        for _ in 0..1_000_000 {
+            let (x, y, z) = (random_value(&mut rng), random_value(&mut rng), random_value(&mut rng));
+
+            assert_eq!(v3::map_index(x), map_index(x), "x: {}", x);
+            assert_eq!(v3::pick_half(x), pick_half(x), "x: {}", x);
+            assert_eq!(v3::isqrt(x), isqrt(x), "x: {}", x);
+            assert_eq!(crate::math::modular_power(x, y, z), modular_power(x, y, z), "x: {}, y: {}, z: {}", x, y, z);
+        }
+    }
+
+    // Compare the hashes of `count` random inputs
+    fn differential(count: usize) {
+        let mut rng = StdRng::seed_from_u64(0xCAFEBABEDEADBEEF);
+        // The scratchpad is reused to also cover a dirty one
+        let mut scratch_pad = ScratchPad::default();
+
+        for _ in 0..count {
+            // Most inputs have the miner work size, others a random length
+            let len = if rng.gen_bool(0.5) { 112 } else { rng.gen_range(0..=512) };
+            let mut input = vec![0u8; len];
+            rng.fill_bytes(&mut input);
+
+            let expected = xelis_hash(&input);
+            let hash = v3::xelis_hash(&input, &mut scratch_pad);
+            match (hash, expected) {
+                (Ok(hash), Ok(expected)) => assert_eq!(hash, expected, "input: {:?}", input),
+                (Err(e), Err(expected)) => assert_eq!(e.to_string(), expected.to_string()),
+                (hash, expected) => panic!("input: {:?}, got {:?}, expected {:?}", input, hash, expected),
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_differential() {
+        differential(32);
+    }
+
+    // cargo test --release -- --ignored differential_large
+    #[test]
+    #[ignore]
+    fn test_differential_large() {
+        differential(20_000);
+    }
+}
@@ -23,13 +23,19 @@ impl<const M: usize> ScratchPad<M> {
     // Retrieve the scratch pad as a mutable bytes slice
     #[inline(always)]
     pub fn as_mut_bytes<const M_BYTES: usize>(&mut self) -> Result<&mut [u8; M_BYTES], Error> {
-        bytemuck::try_cast_slice_mut(self.as_mut_slice())
-            .map_err(|e| Error::CastError(e))?
-            .try_into()
-            .map_err(|_| Error::FormatError)
+        as_mut_bytes(self.as_mut_slice())
     }
 }
 
+// Retrieve the scratch pad words as a mutable bytes slice
+#[inline(always)]
+pub(crate) fn as_mut_bytes<const M_BYTES: usize>(words: &mut [u64]) -> Result<&mut [u8; M_BYTES], Error> {
+    bytemuck::try_cast_slice_mut(words)
+        .map_err(|e| Error::CastError(e))?
+        .try_into()
+        .map_err(|_| Error::FormatError)
+}
+
 impl<const M: usize> Default for ScratchPad<M> {
     fn default() -> Self {
         Self(
@@ -40,3 +46,79 @@ impl<const M: usize> Default for ScratchPad<M> {
         )
     }
 }
+
+// Convert the words from their little-endian representation to the native one
+// Words generated from bytes must go through it to give the same hash on all targets
+// This is a no-op on little-endian targets, the tests with reduced parameters check the
+// big-endian ones under Miri: cargo +nightly miri test --target s390x-unknown-linux-gnu
+#[cfg(any(test, feature = "v1", feature = "v2"))]
+#[inline(always)]
+pub(crate) fn from_le_words(words: &mut [u64]) {
+    if cfg!(target_endian = "big") {
+        for word in words.iter_mut() {
+            *word = u64::from_le(*word);
+        }
+    }
+}
+
+// Convert the words to their little-endian representation before reading them as bytes
+// This is a no-op on little-endian targets
+#[cfg(any(test, feature = "v2"))]
+#[inline(always)]
+pub(crate) fn to_le_words(words: &mut [u64]) {
+    if cfg!(target_endian = "big") {
+        for word in words.iter_mut() {
+            *word = word.to_le();
+        }
+    }
+}
+
+// Fill the words with values stressing the stage 3 arithmetic: extreme values,
+// powers of two and their neighbors, repeated words and random ones
+#[cfg(all(test, any(feature = "v1", feature = "v2", feature = "v3")))]
+pub(crate) fn fill_adversarial(words: &mut [u64], rng: &mut impl rand::Rng) {
+    const EDGES: [u64; 8] = [0, 1, 2, u64::MAX, u64::MAX - 1, 1 << 63, (1 << 32) - 1, 1 << 32];
+
+    // Share of random words, from none to all of them
+    let random = rng.gen_range(0..=4);
+    let mut previous = 0;
+    for word in words.iter_mut() {
+        *word = match rng.gen_range(0..8) {
+            n if n < random => rng.gen(),
+            4 => previous,
+            5 => (1u64 << rng.gen_range(0..64)).wrapping_add(rng.gen_range(0..3)).wrapping_sub(1),
+            _ => EDGES[rng.gen_range(0..EDGES.len())],
+        };
+        previous = *word;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_as_mut_bytes() {
+        let mut scratch_pad = ScratchPad::<4>::default();
+        let bytes = scratch_pad.as_mut_bytes::<32>().unwrap();
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = i as u8;
+        }
+
+        let words = scratch_pad.as_mut_slice();
+        from_le_words(words);
+        assert_eq!(words[0], 0x0706050403020100);
+        assert_eq!(words[3], 0x1f1e1d1c1b1a1918);
+
+        to_le_words(words);
+        let bytes = scratch_pad.as_mut_bytes::<32>().unwrap();
+        assert!(bytes.iter().enumerate().all(|(i, byte)| *byte == i as u8));
+    }
+
+    #[test]
+    fn test_as_mut_bytes_invalid_size() {
+        let mut scratch_pad = ScratchPad::<4>::default();
+        assert!(matches!(scratch_pad.as_mut_bytes::<16>(), Err(Error::FormatError)));
+        assert!(matches!(scratch_pad.as_mut_bytes::<64>(), Err(Error::FormatError)));
+    }
+}
@@ -0,0 +1,158 @@
+// Check the optimized and the reference implementations
+// against the shared test vectors of the `test-vectors` directory
+
+use serde_json::Value;
+
+use crate::Hash;
+
+// Version of the test vectors file format
+const FORMAT: u64 = 1;
+
+struct Vector {
+    name: String,
+    input: Vec<u8>,
+    // Expected hash or error name
+    expected: Result<Hash, String>,
+    // Expected digest of the scratchpad after each stage
+    stages: Vec<(String, Hash)>,
+}
+
+fn parse_hash(value: &Value) -> Hash {
+    hex::decode(value.as_str().expect("hash must be a string"))
+        .expect("invalid hex hash")
+        .try_into()
+        .expect("invalid hash length")
+}
+
+fn load(content: &str, version: &str) -> Vec<Vector> {
+    let file: Value = serde_json::from_str(content).expect("invalid test vectors file");
+    assert_eq!(file["format"], FORMAT);
+    assert_eq!(file["version"], version);
+
+    file["vectors"].as_array()
+        .expect("missing vectors")
+        .iter()
+        .map(|vector| Vector {
+            name: vector["name"].as_str().expect("missing name").to_owned(),
+            input: hex::decode(vector["input"].as_str().expect("missing input"))
+                .expect("invalid hex input")
+                .repeat(vector.get("repeat").map_or(1, |repeat| repeat.as_u64().expect("repeat must be an integer") as usize)),
+            expected: match vector.get("error") {
+                Some(error) => Err(error.as_str().expect("error must be a string").to_owned()),
+                None => Ok(parse_hash(&vector["hash"])),
+            },
+            stages: vector.get("stages")
+                .and_then(Value::as_object)
+                .map(|stages| stages.iter().map(|(stage, digest)| (stage.clone(), parse_hash(digest))).collect())
+                .unwrap_or_default(),
+        })
+        .collect()
+}
+
+#[cfg(feature = "v2")]
+fn error_name(error: &crate::Error) -> String {
+    use crate::Error;
+
+    match error {
+        Error::InvalidInputLength(_) => "invalid_input_length".to_owned(),
+        Error::ZeroModulus => "zero_modulus".to_owned(),
+        Error::IsqrtOverflow => "isqrt_overflow".to_owned(),
+        _ => error.to_string(),
+    }
+}
+
+// Blake3 hash of the scratchpad words in little-endian
+#[cfg(feature = "v2")]
+fn digest(words: &[u64]) -> Hash {
+    let bytes: Vec<u8> = words.iter()
+        .flat_map(|word| word.to_le_bytes())
+        .collect();
+
+    blake3::hash(&bytes).into()
+}
+
+fn check_stages(vector: &Vector, stages: &[(&str, Vec<u64>)]) {
+    #[cfg(feature = "v2")]
+    for (stage, expected) in &vector.stages {
+        let (_, memory) = stages.iter()
+            .find(|(name, _)| name == stage)
+            .unwrap_or_else(|| panic!("{}: unknown stage {}", vector.name, stage));
+        assert_eq!(&digest(memory), expected, "{}: {}", vector.name, stage);
+    }
+
+    #[cfg(not(feature = "v2"))]
+    let _ = (&vector.stages, stages);
+}
+
+#[cfg(feature = "v1")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn test_v1_vectors() {
+    use crate::{reference, v1::{self, AlignedInput, ScratchPad, BYTES_ARRAY_INPUT}};
+
+    let vectors = load(include_str!("../test-vectors/v1.json"), "v1");
+    assert!(!vectors.is_empty());
+
+    let mut scratch_pad = ScratchPad::default();
+    for vector in vectors {
+        let input: &[u8; BYTES_ARRAY_INPUT] = vector.input.as_slice().try_into().expect("v1 inputs are 200 bytes");
+        let expected = vector.expected.as_ref().expect("v1 has no invalid input");
+
+        let (hash, stages) = reference::v1::xelis_hash_with_stages(input);
+        assert_eq!(&hash, expected, "{}: reference", vector.name);
+        check_stages(&vector, &stages);
+
+        let mut aligned = AlignedInput::default();
+        let slice = aligned.as_mut_slice().unwrap();
+        slice.copy_from_slice(input);
+        assert_eq!(&v1::xelis_hash(slice, &mut scratch_pad).unwrap(), expected, "{}", vector.name);
+    }
+}
+
+#[cfg(feature = "v2")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn test_v2_vectors() {
+    use crate::{reference, v2::{self, ScratchPad}};
+
+    let vectors = load(include_str!("../test-vectors/v2.json"), "v2");
+    assert!(!vectors.is_empty());
+
+    let mut scratch_pad = ScratchPad::default();
+    for vector in vectors {
+        let result = reference::v2::xelis_hash_with_stages(&vector.input);
+        match &result {
+            Ok((_, stages)) => check_stages(&vector, stages),
+            Err(_) => assert!(vector.stages.is_empty()),
+        }
+        let reference = result.map(|(hash, _)| hash).map_err(|e| error_name(&e));
+        assert_eq!(reference, vector.expected, "{}: reference", vector.name);
+
+        let hash = v2::xelis_hash(&vector.input, &mut scratch_pad);
+        assert_eq!(hash.map_err(|e| error_name(&e)), vector.expected, "{}", vector.name);
+    }
+}
+
+#[cfg(feature = "v3")]
+#[test]
+#[cfg_attr(miri, ignore)]
+fn test_v3_vectors() {
+    use crate::{reference, v3::{self, ScratchPad}};
+
+    let vectors = load(include_str!("../test-vectors/v3.json"), "v3");
+    assert!(!vectors.is_empty());
+
+    let mut scratch_pad = ScratchPad::default();
+    for vector in vectors {
+        let result = reference::v3::xelis_hash_with_stages(&vector.input);
+        match &result {
+            Ok((_, stages)) => check_stages(&vector, stages),
+            Err(_) => assert!(vector.stages.is_empty()),
+        }
+        let reference = result.map(|(hash, _)| hash).map_err(|e| error_name(&e));
+        assert_eq!(reference, vector.expected, "{}: reference", vector.name);
+
+        let hash = v3::xelis_hash(&vector.input, &mut scratch_pad);
+        assert_eq!(hash.map_err(|e| error_name(&e)), vector.expected, "{}", vector.name);
+    }
+}
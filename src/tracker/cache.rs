@@ -0,0 +1,514 @@
+// Set-associative cache hierarchy simulator fed by the logged scratchpad accesses
+//
+// Each level has its own size and associativity, all levels use the same line size.
+// A miss fills the line in all the levels above the one serving it, levels are not
+// strictly inclusive and writes allocate like reads.
+// Latencies are load-to-use cycles: the stall of a read is the latency of the level
+// serving it minus the L1 latency, which the pipeline hides. Writes are buffered and
+// don't stall. The stage 3 reads depend on each other, so their latencies add up.
+
+use std::fmt;
+
+use super::{locality::AccessLog, Error, MemOp};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Replacement {
+    Lru,
+    // Seeded, so the simulations are reproducible
+    Random,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheLevel {
+    pub name: String,
+    // In bytes
+    pub size: usize,
+    pub associativity: usize,
+    // Load-to-use latency in cycles
+    pub latency: u64,
+}
+
+impl CacheLevel {
+    pub fn new(name: &str, size: usize, associativity: usize, latency: u64) -> Self {
+        Self { name: name.to_owned(), size, associativity, latency }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheConfig {
+    pub name: String,
+    // In bytes
+    pub line_size: usize,
+    pub replacement: Replacement,
+    // From the closest to the core
+    pub levels: Vec<CacheLevel>,
+    // Latency of a read missing all the levels
+    pub memory_latency: u64,
+}
+
+const KIB: usize = 1024;
+const MIB: usize = 1024 * KIB;
+
+// Presets of typical hardware, the sizes and latencies are approximate.
+// CPU presets give the caches available to a single core, the last level being
+// shared with the other cores. GPU presets give a whole SM or CU, whose caches are
+// shared by all its threads: use `per_thread` to get the share of each hash.
+impl CacheConfig {
+    // AMD Zen 4 core, such as a Ryzen 9 7950X
+    pub fn zen4() -> Self {
+        Self {
+            name: "AMD Zen 4".to_owned(),
+            line_size: 64,
+            replacement: Replacement::Lru,
+            levels: vec![
+                CacheLevel::new("L1", 32 * KIB, 8, 4),
+                CacheLevel::new("L2", MIB, 8, 14),
+                CacheLevel::new("L3", 32 * MIB, 16, 50),
+            ],
+            memory_latency: 350,
+        }
+    }
+
+    // Intel Raptor Lake performance core, such as a Core i9-13900K
+    pub fn raptor_lake() -> Self {
+        Self {
+            name: "Intel Raptor Lake".to_owned(),
+            line_size: 64,
+            replacement: Replacement::Lru,
+            levels: vec![
+                CacheLevel::new("L1", 48 * KIB, 12, 5),
+                CacheLevel::new("L2", 2 * MIB, 16, 16),
+                CacheLevel::new("L3", 36 * MIB, 12, 70),
+            ],
+            memory_latency: 400,
+        }
+    }
+
+    // Arm Cortex-A78 core, as found in phones and single board computers
+    pub fn cortex_a78() -> Self {
+        Self {
+            name: "Arm Cortex-A78".to_owned(),
+            line_size: 64,
+            replacement: Replacement::Random,
+            levels: vec![
+                CacheLevel::new("L1", 64 * KIB, 4, 4),
+                CacheLevel::new("L2", 512 * KIB, 8, 11),
+                CacheLevel::new("L3", 4 * MIB, 16, 35),
+            ],
+            memory_latency: 250,
+        }
+    }
+
+    // NVIDIA Ada SM, such as in a RTX 4090
+    // Its combined L1 and shared memory is modeled as a cache with 32 bytes sectors
+    pub fn ada_sm() -> Self {
+        Self {
+            name: "NVIDIA Ada SM".to_owned(),
+            line_size: 32,
+            replacement: Replacement::Lru,
+            levels: vec![
+                CacheLevel::new("L1/shared", 128 * KIB, 4, 30),
+                CacheLevel::new("L2", 72 * MIB, 16, 250),
+            ],
+            memory_latency: 550,
+        }
+    }
+
+    // AMD RDNA 3 CU, such as in a RX 7900 XTX
+    pub fn rdna3_cu() -> Self {
+        Self {
+            name: "AMD RDNA 3 CU".to_owned(),
+            line_size: 128,
+            replacement: Replacement::Lru,
+            levels: vec![
+                CacheLevel::new("L0", 32 * KIB, 4, 35),
+                CacheLevel::new("L1", 256 * KIB, 16, 80),
+                CacheLevel::new("L2", 6 * MIB, 16, 160),
+                CacheLevel::new("Infinity Cache", 96 * MIB, 16, 300),
+            ],
+            memory_latency: 600,
+        }
+    }
+
+    pub fn presets() -> Vec<Self> {
+        vec![Self::zen4(), Self::raptor_lake(), Self::cortex_a78(), Self::ada_sm(), Self::rdna3_cu()]
+    }
+
+    // Share of each level for a thread when `threads` threads hash at the same time,
+    // rounded down to whole sets and keeping at least one set per level
+    pub fn per_thread(mut self, threads: usize) -> Self {
+        self.name = format!("{} ({} threads)", self.name, threads);
+        for level in &mut self.levels {
+            let set = self.line_size * level.associativity;
+            level.size = (level.size / threads / set).max(1) * set;
+        }
+        self
+    }
+}
+
+// Level being simulated, each set lists its line tags from the most recently used
+struct Level {
+    sets: Vec<Vec<u64>>,
+    associativity: usize,
+    hits: u64,
+    misses: u64,
+}
+
+impl Level {
+    fn set(&mut self, line: u64) -> &mut Vec<u64> {
+        let sets = self.sets.len() as u64;
+        &mut self.sets[(line % sets) as usize]
+    }
+
+    // Look for the line and update its recency, returns true on a hit
+    fn lookup(&mut self, line: u64) -> bool {
+        let set = self.set(line);
+        match set.iter().position(|&tag| tag == line) {
+            Some(position) => {
+                set[..=position].rotate_right(1);
+                true
+            },
+            None => false,
+        }
+    }
+
+    fn insert(&mut self, line: u64, replacement: Replacement, rng: &mut u64) {
+        let associativity = self.associativity;
+        let set = self.set(line);
+        if set.len() == associativity {
+            let victim = match replacement {
+                Replacement::Lru => associativity - 1,
+                Replacement::Random => (xorshift(rng) % associativity as u64) as usize,
+            };
+            set.remove(victim);
+        }
+        set.insert(0, line);
+    }
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+pub struct CacheSimulator {
+    config: CacheConfig,
+    levels: Vec<Level>,
+    rng: u64,
+    hashes: u64,
+    reads: u64,
+    writes: u64,
+    memory_reads: u64,
+    stall_cycles: u64,
+}
+
+impl CacheSimulator {
+    pub fn new(config: CacheConfig) -> Result<Self, Error> {
+        if config.line_size == 0 || config.levels.is_empty() {
+            return Err(Error::InvalidCacheConfig(format!("{}: no line size or level", config.name)));
+        }
+
+        let mut levels = Vec::with_capacity(config.levels.len());
+        for level in &config.levels {
+            let sets = level.size / config.line_size / level.associativity.max(1);
+            if level.associativity == 0 || sets == 0 || level.size % (config.line_size * level.associativity) != 0 {
+                return Err(Error::InvalidCacheConfig(format!(
+                    "{} {}: {} bytes are not a multiple of {}-way sets of {} bytes lines",
+                    config.name, level.name, level.size, level.associativity, config.line_size
+                )));
+            }
+
+            levels.push(Level {
+                sets: (0..sets).map(|_| Vec::with_capacity(level.associativity)).collect(),
+                associativity: level.associativity,
+                hits: 0,
+                misses: 0,
+            });
+        }
+
+        Ok(Self {
+            config,
+            levels,
+            rng: 0x2545_F491_4F6C_DD1D,
+            hashes: 0,
+            reads: 0,
+            writes: 0,
+            memory_reads: 0,
+            stall_cycles: 0,
+        })
+    }
+
+    // Empty all the levels
+    pub fn flush(&mut self) {
+        for set in self.levels.iter_mut().flat_map(|level| level.sets.iter_mut()) {
+            set.clear();
+        }
+    }
+
+    // Load the lines of the words in order without counting them,
+    // such as the scratchpad written by the stage 1 before the stage 3
+    pub fn fill(&mut self, words: usize) {
+        let words_per_line = (self.config.line_size / 8).max(1);
+        for word in (0..words).step_by(words_per_line) {
+            let line = self.line(word);
+            for level in &mut self.levels {
+                if !level.lookup(line) {
+                    level.insert(line, self.config.replacement, &mut self.rng);
+                }
+            }
+        }
+    }
+
+    fn line(&self, index: usize) -> u64 {
+        (index * 8 / self.config.line_size) as u64
+    }
+
+    // Access the word at `index` of the scratchpad, which is assumed aligned on a line
+    pub fn access(&mut self, index: usize, op: MemOp) {
+        let line = self.line(index);
+        let served = self.levels.iter_mut().position(|level| {
+            let hit = level.lookup(line);
+            if hit {
+                level.hits += 1;
+            } else {
+                level.misses += 1;
+            }
+            hit
+        });
+
+        let filled = served.unwrap_or(self.levels.len());
+        for level in &mut self.levels[..filled] {
+            level.insert(line, self.config.replacement, &mut self.rng);
+        }
+
+        match op {
+            MemOp::Read => {
+                self.reads += 1;
+                let latency = match served {
+                    Some(level) => self.config.levels[level].latency,
+                    None => {
+                        self.memory_reads += 1;
+                        self.config.memory_latency
+                    },
+                };
+                self.stall_cycles += latency.saturating_sub(self.config.levels[0].latency);
+            },
+            MemOp::Write => self.writes += 1,
+        }
+    }
+
+    // Simulate the hashes of the log, each one starting from a flushed cache
+    // filled with the `scratchpad` words, as left by its stage 1
+    pub fn simulate(&mut self, log: &AccessLog, scratchpad: usize) {
+        for accesses in log.hashes() {
+            self.hashes += 1;
+            self.flush();
+            self.fill(scratchpad);
+            for access in accesses {
+                self.access(access.index as usize, access.op);
+            }
+        }
+    }
+
+    pub fn report(&self) -> CacheReport {
+        CacheReport {
+            name: self.config.name.clone(),
+            hashes: self.hashes,
+            reads: self.reads,
+            writes: self.writes,
+            levels: self.config.levels.iter()
+                .zip(&self.levels)
+                .map(|(config, level)| LevelStats { name: config.name.clone(), hits: level.hits, misses: level.misses })
+                .collect(),
+            memory_reads: self.memory_reads,
+            stall_cycles: self.stall_cycles,
+        }
+    }
+}
+
+fn rate(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    count as f64 / total as f64
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LevelStats {
+    pub name: String,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl LevelStats {
+    // Hit rate of the accesses reaching this level, 0 if none does
+    pub fn hit_rate(&self) -> f64 {
+        rate(self.hits, self.hits + self.misses)
+    }
+
+    pub fn miss_rate(&self) -> f64 {
+        rate(self.misses, self.hits + self.misses)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheReport {
+    pub name: String,
+    pub hashes: u64,
+    pub reads: u64,
+    pub writes: u64,
+    pub levels: Vec<LevelStats>,
+    // Reads missing all the levels
+    pub memory_reads: u64,
+    pub stall_cycles: u64,
+}
+
+impl CacheReport {
+    pub fn stall_cycles_per_hash(&self) -> f64 {
+        if self.hashes == 0 {
+            return 0.0;
+        }
+        self.stall_cycles as f64 / self.hashes as f64
+    }
+}
+
+impl fmt::Display for CacheReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {} reads and {} writes in {} hashes", self.name, self.reads, self.writes, self.hashes)?;
+        for level in &self.levels {
+            writeln!(f, "  {}: hit rate {:.2}%, miss rate {:.2}%", level.name, level.hit_rate() * 100.0, level.miss_rate() * 100.0)?;
+        }
+        write!(f, "  {} memory reads, {:.0} stall cycles per hash", self.memory_reads, self.stall_cycles_per_hash())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+    use crate::tracker::locality::Locality;
+
+    fn single_level(size: usize, associativity: usize, replacement: Replacement) -> CacheConfig {
+        CacheConfig {
+            name: "test".to_owned(),
+            line_size: 8,
+            replacement,
+            levels: vec![CacheLevel::new("L1", size, associativity, 1)],
+            memory_latency: 11,
+        }
+    }
+
+    fn random_log(accesses: usize, words: usize) -> AccessLog {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut log = AccessLog::new();
+        log.start_hash();
+        for _ in 0..accesses {
+            log.push(rng.gen_range(0..words), MemOp::Read);
+        }
+        log
+    }
+
+    #[test]
+    fn test_fully_associative_lru() {
+        // A single set of one word lines hits exactly on the reuse distances below its size
+        let log = random_log(5000, 256);
+        let locality = Locality::from_log(&log);
+        for words in [1, 16, 100, 256] {
+            let mut simulator = CacheSimulator::new(single_level(words * 8, words, Replacement::Lru)).unwrap();
+            simulator.simulate(&log, 0);
+            let report = simulator.report();
+            assert_eq!(report.levels[0].hit_rate(), locality.lru_hit_rate(words as u64));
+            assert_eq!(report.stall_cycles, report.memory_reads * 10);
+        }
+    }
+
+    #[test]
+    fn test_direct_mapped_conflicts() {
+        // 0 and 4 map to the same set of a 4 lines direct-mapped cache
+        let mut simulator = CacheSimulator::new(single_level(32, 1, Replacement::Lru)).unwrap();
+        for index in [0, 4, 0, 4, 1, 1] {
+            simulator.access(index, MemOp::Read);
+        }
+        assert_eq!(simulator.report().levels[0], LevelStats { name: "L1".to_owned(), hits: 1, misses: 5 });
+
+        // Both fit in a 2-way cache of the same size
+        let mut simulator = CacheSimulator::new(single_level(32, 2, Replacement::Lru)).unwrap();
+        for index in [0, 4, 0, 4, 1, 1] {
+            simulator.access(index, MemOp::Read);
+        }
+        assert_eq!(simulator.report().levels[0].hits, 3);
+    }
+
+    #[test]
+    fn test_hierarchy() {
+        let config = CacheConfig {
+            name: "test".to_owned(),
+            line_size: 64,
+            replacement: Replacement::Lru,
+            levels: vec![CacheLevel::new("L1", 64, 1, 4), CacheLevel::new("L2", 256, 4, 12)],
+            memory_latency: 100,
+        };
+        let mut simulator = CacheSimulator::new(config).unwrap();
+        // 8 words per line: memory, L1, L1 for the second word of the line, then L2 after an eviction
+        for (index, op) in [(0, MemOp::Read), (1, MemOp::Read), (8, MemOp::Write), (0, MemOp::Read)] {
+            simulator.access(index, op);
+        }
+
+        let report = simulator.report();
+        assert_eq!((report.reads, report.writes), (3, 1));
+        assert_eq!(report.levels[0], LevelStats { name: "L1".to_owned(), hits: 1, misses: 3 });
+        assert_eq!(report.levels[1], LevelStats { name: "L2".to_owned(), hits: 1, misses: 2 });
+        assert_eq!(report.memory_reads, 1);
+        assert_eq!(report.stall_cycles, 96 + 8);
+
+        let unused = LevelStats { name: "L3".to_owned(), hits: 0, misses: 0 };
+        assert_eq!((unused.hit_rate(), unused.miss_rate()), (0.0, 0.0));
+    }
+
+    #[test]
+    fn test_fill() {
+        let mut simulator = CacheSimulator::new(single_level(64, 8, Replacement::Lru)).unwrap();
+        simulator.fill(16);
+        // Only the last 8 words are left
+        simulator.access(15, MemOp::Read);
+        simulator.access(7, MemOp::Read);
+        assert_eq!(simulator.report().levels[0], LevelStats { name: "L1".to_owned(), hits: 1, misses: 1 });
+    }
+
+    #[test]
+    fn test_random_replacement() {
+        let log = random_log(5000, 256);
+        let run = |replacement| {
+            let mut simulator = CacheSimulator::new(single_level(1024, 8, replacement)).unwrap();
+            simulator.simulate(&log, 0);
+            simulator.report()
+        };
+
+        let random = run(Replacement::Random);
+        assert_eq!(random, run(Replacement::Random));
+        // Half of the uniformly accessed words fit
+        assert!((random.levels[0].hit_rate() - 0.5).abs() < 0.05, "{}", random);
+        assert!((run(Replacement::Lru).levels[0].hit_rate() - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(matches!(CacheSimulator::new(single_level(24, 2, Replacement::Lru)), Err(Error::InvalidCacheConfig(_))));
+        assert!(matches!(CacheSimulator::new(single_level(32, 0, Replacement::Lru)), Err(Error::InvalidCacheConfig(_))));
+        assert!(CacheSimulator::new(CacheConfig { levels: Vec::new(), ..single_level(8, 1, Replacement::Lru) }).is_err());
+    }
+
+    #[test]
+    fn test_presets() {
+        for config in CacheConfig::presets() {
+            CacheSimulator::new(config.clone()).unwrap();
+            let shared = config.per_thread(1024);
+            CacheSimulator::new(shared.clone()).unwrap();
+            assert!(shared.levels.iter().all(|level| level.size % shared.line_size == 0));
+        }
+        assert_eq!(CacheConfig::ada_sm().per_thread(32).levels[0].size, 4 * KIB);
+    }
+}
@@ -0,0 +1,224 @@
+// Memory coalescing of hashes running in lockstep as the lanes of a GPU warp
+//
+// Each hash observed is a lane, and every `width` hashes make a warp, as with `WarpSimulator`.
+// All the lanes make the same accesses in the same order, so the k-th access of each lane
+// is part of the same warp request. A request needs one memory transaction per distinct
+// segment accessed by its lanes, 1 being fully coalesced and `width` fully scattered.
+// Lanes whose hash ended or failed don't take part in the remaining requests.
+
+use std::{collections::BTreeMap, fmt};
+
+use super::MemOp;
+use crate::observer::{Observer, Site};
+
+// Sizes of the memory transactions, in bytes
+pub const SEGMENT_SIZES: [usize; 3] = [32, 64, 128];
+
+pub struct CoalescingSimulator {
+    width: usize,
+    // site and operation of each access in program order, the same for all the lanes
+    accesses: Vec<(Site, MemOp)>,
+    // scratchpad indexes accessed by the lanes of the current warp
+    lanes: Vec<Vec<u32>>,
+    report: CoalescingReport,
+}
+
+impl CoalescingSimulator {
+    pub fn new(width: usize) -> Self {
+        assert!(width > 0, "a warp needs at least one lane");
+        Self {
+            width,
+            accesses: Vec::new(),
+            lanes: Vec::with_capacity(width),
+            report: CoalescingReport { width, ..Default::default() },
+        }
+    }
+
+    fn start_lane(&mut self) {
+        // The last lane of a full warp may have failed before its end
+        self.end_lane();
+        self.lanes.push(Vec::new());
+    }
+
+    fn end_lane(&mut self) {
+        if self.lanes.len() == self.width {
+            self.run_warp();
+        }
+    }
+
+    fn add_access(&mut self, index: usize, site: Site, op: MemOp) {
+        let Some(lane) = self.lanes.last_mut() else {
+            return;
+        };
+
+        if lane.len() == self.accesses.len() {
+            self.accesses.push((site, op));
+        }
+        debug_assert_eq!(self.accesses[lane.len()], (site, op), "lanes accesses differ");
+        lane.push(index as u32);
+    }
+
+    fn run_warp(&mut self) {
+        let requests = self.lanes.iter().map(Vec::len).max().unwrap_or(0);
+        let mut segments = Vec::with_capacity(self.width);
+        for (request, access) in self.accesses[..requests].iter().enumerate() {
+            let stats = self.report.sites.entry(*access).or_default();
+            stats.requests += 1;
+            for (size, transactions) in SEGMENT_SIZES.iter().zip(stats.transactions.iter_mut()) {
+                segments.clear();
+                segments.extend(self.lanes.iter().filter_map(|lane| lane.get(request)).map(|&index| index as usize * 8 / size));
+                segments.sort_unstable();
+                segments.dedup();
+                *transactions += segments.len() as u64;
+            }
+            stats.lanes += self.lanes.iter().filter(|lane| lane.len() > request).count() as u64;
+        }
+
+        self.report.warps += 1;
+        self.lanes.clear();
+    }
+
+    // Report of the full warps, the lanes of an incomplete warp are not included
+    pub fn report(&self) -> &CoalescingReport {
+        &self.report
+    }
+}
+
+impl Observer for CoalescingSimulator {
+    fn on_start(&mut self) {
+        self.start_lane();
+    }
+
+    fn on_mem_read_at(&mut self, index: usize, site: Site) {
+        self.add_access(index, site, MemOp::Read);
+    }
+
+    fn on_mem_write_at(&mut self, index: usize, site: Site) {
+        self.add_access(index, site, MemOp::Write);
+    }
+
+    fn on_finish(&mut self) {
+        self.end_lane();
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SiteStats {
+    // Warp accesses
+    pub requests: u64,
+    // Lanes taking part in the requests
+    pub lanes: u64,
+    // Transactions for each of the `SEGMENT_SIZES`
+    pub transactions: [u64; 3],
+}
+
+impl SiteStats {
+    // Average transactions per request for each of the `SEGMENT_SIZES`
+    pub fn transactions_per_request(&self) -> [f64; 3] {
+        self.transactions.map(|transactions| if self.requests == 0 { 0.0 } else { transactions as f64 / self.requests as f64 })
+    }
+
+    fn add(&mut self, other: &SiteStats) {
+        self.requests += other.requests;
+        self.lanes += other.lanes;
+        for (transactions, other) in self.transactions.iter_mut().zip(other.transactions.iter()) {
+            *transactions += other;
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoalescingReport {
+    pub width: usize,
+    pub warps: u64,
+    pub sites: BTreeMap<(Site, MemOp), SiteStats>,
+}
+
+impl CoalescingReport {
+    pub fn site(&self, site: Site, op: MemOp) -> Option<&SiteStats> {
+        self.sites.get(&(site, op))
+    }
+
+    // Stats of all the sites together
+    pub fn total(&self) -> SiteStats {
+        let mut total = SiteStats::default();
+        for stats in self.sites.values() {
+            total.add(stats);
+        }
+        total
+    }
+}
+
+impl fmt::Display for CoalescingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} warps of {} lanes, transactions per request with {:?} bytes segments",
+            self.warps, self.width, SEGMENT_SIZES
+        )?;
+
+        let mut write_row = |name: &str, stats: &SiteStats| {
+            let [small, medium, large] = stats.transactions_per_request();
+            writeln!(f, "  {:<22} {:>7.2} {:>7.2} {:>7.2} ({} requests)", name, small, medium, large, stats.requests)
+        };
+        for ((site, op), stats) in &self.sites {
+            let op = match op {
+                MemOp::Read => "read",
+                MemOp::Write => "write",
+            };
+            write_row(&format!("{} {}", site.name(), op), stats)?;
+        }
+        write_row("all", &self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lane(simulator: &mut CoalescingSimulator, accesses: &[(usize, Site)]) {
+        simulator.on_start();
+        for &(index, site) in accesses {
+            simulator.on_mem_read_at(index, site);
+        }
+        simulator.on_mem_write_at(accesses[0].0, Site::T);
+        simulator.on_finish();
+    }
+
+    #[test]
+    fn test_coalescing() {
+        let mut simulator = CoalescingSimulator::new(4);
+        // 4 words per 32 bytes segment
+        lane(&mut simulator, &[(0, Site::A), (7, Site::R)]);
+        lane(&mut simulator, &[(1, Site::A), (7, Site::R)]);
+        lane(&mut simulator, &[(4, Site::A), (7, Site::R)]);
+        assert_eq!(simulator.report().warps, 0);
+        lane(&mut simulator, &[(16, Site::A), (7, Site::R)]);
+
+        let report = simulator.report();
+        assert_eq!(report.warps, 1);
+        assert_eq!(report.sites.len(), 3);
+
+        let a = report.site(Site::A, MemOp::Read).unwrap();
+        assert_eq!(a, report.site(Site::T, MemOp::Write).unwrap());
+        assert_eq!(a, &SiteStats { requests: 1, lanes: 4, transactions: [3, 2, 2] });
+        assert_eq!(report.site(Site::R, MemOp::Read).unwrap().transactions_per_request(), [1.0; 3]);
+        assert_eq!(report.total(), SiteStats { requests: 3, lanes: 12, transactions: [7, 5, 5] });
+    }
+
+    #[test]
+    fn test_failed_lane() {
+        let mut simulator = CoalescingSimulator::new(2);
+        lane(&mut simulator, &[(0, Site::A), (100, Site::B)]);
+        // The second lane fails after its first access
+        simulator.on_start();
+        simulator.on_mem_read_at(50, Site::A);
+        simulator.on_start();
+        simulator.on_finish();
+
+        let report = simulator.report();
+        assert_eq!(report.warps, 1);
+        assert_eq!(report.site(Site::A, MemOp::Read).unwrap(), &SiteStats { requests: 1, lanes: 2, transactions: [2, 2, 2] });
+        assert_eq!(report.site(Site::B, MemOp::Read).unwrap(), &SiteStats { requests: 1, lanes: 1, transactions: [1, 1, 1] });
+    }
+}
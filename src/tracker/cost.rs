@@ -0,0 +1,490 @@
+// Expensive operations and critical path latency of the stage 3 of each hash
+//
+// `CostCounter` counts the operations reported by the `on_op` hook and the scratchpad
+// accesses, random or sequential depending on their site.
+// The critical path follows the dependencies of `result` through the iterations: the work
+// off this path, such as the sequential reads, the stores and the v3 `index_t` update chain,
+// is assumed to run in parallel. No other stall is modeled, so the critical path is
+// a lower bound of the stage 3 latency with the given latencies.
+
+use std::fmt;
+
+use super::MemOp;
+use crate::observer::{Observer, Op, Site};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Version {
+    V2,
+    V3,
+}
+
+impl Version {
+    // Sites whose index doesn't depend on the data
+    fn is_sequential(self, site: Site) -> bool {
+        match self {
+            Self::V2 => matches!(site, Site::Start | Site::R | Site::T | Site::J),
+            Self::V3 => matches!(site, Site::Start | Site::R),
+        }
+    }
+}
+
+// Latencies in cycles, the presets are approximate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyTable {
+    pub name: String,
+    // Add, xor, rotate, compare
+    pub alu: u64,
+    pub mul64: u64,
+    pub mul128: u64,
+    pub div_rem_64: u64,
+    pub div_rem_128: u64,
+    pub isqrt: u64,
+    pub modular_power_step: u64,
+    pub aes_round: u64,
+    pub sequential_read: u64,
+    pub random_read: u64,
+}
+
+impl LatencyTable {
+    // Recent x86-64 core with AES-NI, the scratchpad fitting in its L2 cache
+    pub fn cpu() -> Self {
+        Self {
+            name: "CPU".to_owned(),
+            alu: 1,
+            mul64: 3,
+            mul128: 4,
+            div_rem_64: 14,
+            div_rem_128: 40,
+            isqrt: 25,
+            modular_power_step: 12,
+            aes_round: 4,
+            sequential_read: 4,
+            random_read: 14,
+        }
+    }
+
+    // GPU lane: 64 bits arithmetic is emulated with 32 bits instructions,
+    // AES uses tables in shared memory and the scratchpad is in L2 or device memory
+    pub fn gpu() -> Self {
+        Self {
+            name: "GPU".to_owned(),
+            alu: 4,
+            mul64: 20,
+            mul128: 40,
+            div_rem_64: 150,
+            div_rem_128: 400,
+            isqrt: 120,
+            modular_power_step: 120,
+            aes_round: 80,
+            sequential_read: 30,
+            random_read: 250,
+        }
+    }
+
+    // v3 `map_index`: shift and xor, multiplication and multiply-high
+    fn map_index(&self) -> u64 {
+        self.alu + self.mul64 + self.mul128
+    }
+
+    // From the operands to the value of the branch
+    fn branch(&self, version: Version, branch: u8, divided: bool) -> u64 {
+        match (version, branch) {
+            (Version::V2, 0..=2) => 2 * self.alu,
+            // combine(a + i, isqrt(b + j)) in parallel with murmurhash3, then the remainder
+            (Version::V3, 0) => (2 * self.alu + self.isqrt).max(2 * self.mul64 + 3 * self.alu) + self.div_rem_128,
+            // isqrt(b | 2), remainder and rotation, then the product with isqrt(a + j)
+            (Version::V3, 1) => 2 * self.alu + self.isqrt + self.div_rem_64 + self.mul64,
+            // isqrt(a + i) and isqrt(c + j) in parallel
+            (Version::V3, 2) => 2 * self.alu + self.isqrt + self.mul64,
+            (_, 3 | 4 | 7 | 8) => self.alu + self.mul64,
+            (_, 5 | 6) => 2 * self.alu,
+            (_, 9) => 2 * self.mul64,
+            (_, 10 | 12) => self.alu + self.div_rem_128,
+            // Division if the operands comparison allows it
+            (_, 11 | 13) => self.alu + if divided { self.div_rem_128 } else { self.alu },
+            (_, 14) => self.mul128,
+            _ => self.alu + self.mul128,
+        }
+    }
+
+    // Inner iteration from `result` to the next one
+    fn inner(&self, version: Version, branch: u8, divided: bool) -> u64 {
+        let branch = self.branch(version, branch, divided);
+        match version {
+            // index_a and index_b both depend on result, reduced by a constant multiplication
+            Version::V2 => self.alu + self.mul64 + self.random_read + self.alu + branch + 2 * self.alu,
+            // index_b depends on a
+            Version::V3 => 2 * (self.map_index() + self.random_read) + 3 * self.alu + branch + 2 * self.alu,
+        }
+    }
+
+    // Start of a scratchpad iteration, from its addresses to the first `result`
+    fn head(&self, version: Version) -> u64 {
+        match version {
+            Version::V2 => self.random_read + self.aes_round + self.alu,
+            Version::V3 => 2 * (self.map_index() + self.random_read) + self.aes_round + self.alu,
+        }
+    }
+
+    // End of a scratchpad iteration, from the last `result` to the next addresses
+    fn tail(&self, version: Version, modular_power_steps: u64) -> u64 {
+        match version {
+            Version::V2 => self.isqrt,
+            // isqrt(addr_a) after the modular power, isqrt(result) runs in parallel
+            Version::V3 => modular_power_steps * self.modular_power_step + self.isqrt + 2 * self.mul64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OpCounts {
+    pub div_rem_128: u64,
+    pub div_rem_64: u64,
+    pub mul_128: u64,
+    pub isqrt: u64,
+    pub modular_power_steps: u64,
+    pub aes_rounds: u64,
+    pub random_reads: u64,
+    pub random_writes: u64,
+    pub sequential_reads: u64,
+    pub sequential_writes: u64,
+}
+
+impl OpCounts {
+    fn add_op(&mut self, op: Op, count: u64) {
+        let counter = match op {
+            Op::DivRem128 => &mut self.div_rem_128,
+            Op::DivRem64 => &mut self.div_rem_64,
+            Op::Mul128 => &mut self.mul_128,
+            Op::Isqrt => &mut self.isqrt,
+            Op::ModularPowerStep => &mut self.modular_power_steps,
+            Op::AesRound => &mut self.aes_rounds,
+        };
+        *counter += count;
+    }
+
+    fn add(&mut self, other: &OpCounts) {
+        self.div_rem_128 += other.div_rem_128;
+        self.div_rem_64 += other.div_rem_64;
+        self.mul_128 += other.mul_128;
+        self.isqrt += other.isqrt;
+        self.modular_power_steps += other.modular_power_steps;
+        self.aes_rounds += other.aes_rounds;
+        self.random_reads += other.random_reads;
+        self.random_writes += other.random_writes;
+        self.sequential_reads += other.sequential_reads;
+        self.sequential_writes += other.sequential_writes;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashCost {
+    pub ops: OpCounts,
+    // Critical path latency with each latency table of the counter
+    pub critical_path: Vec<u64>,
+    // Part of the critical path in each branch, for each latency table
+    pub branch_path: Vec<[u64; 16]>,
+}
+
+impl HashCost {
+    fn new(tables: usize) -> Self {
+        Self {
+            ops: OpCounts::default(),
+            critical_path: vec![0; tables],
+            branch_path: vec![[0; 16]; tables],
+        }
+    }
+}
+
+pub struct CostCounter {
+    version: Version,
+    tables: Vec<LatencyTable>,
+    // finished hashes
+    hashes: Vec<HashCost>,
+    current: Option<HashCost>,
+    // branch of the current inner iteration and whether it divided
+    iteration: Option<(u8, bool)>,
+    // modular power steps of the current scratchpad iteration
+    modular_power_steps: u64,
+}
+
+impl CostCounter {
+    pub fn new(version: Version, tables: Vec<LatencyTable>) -> Self {
+        Self {
+            version,
+            tables,
+            hashes: Vec::new(),
+            current: None,
+            iteration: None,
+            modular_power_steps: 0,
+        }
+    }
+
+    pub fn get_tables(&self) -> &[LatencyTable] {
+        &self.tables
+    }
+
+    // Cost of each finished hash, the hashes that failed are not included
+    pub fn get_hashes(&self) -> &[HashCost] {
+        &self.hashes
+    }
+
+    fn add_path(&mut self, latency: impl Fn(&LatencyTable) -> u64) {
+        if let Some(current) = &mut self.current {
+            for (path, table) in current.critical_path.iter_mut().zip(&self.tables) {
+                *path += latency(table);
+            }
+        }
+    }
+
+    fn end_iteration(&mut self) {
+        let (Some((branch, divided)), Some(current)) = (self.iteration.take(), &mut self.current) else {
+            return;
+        };
+
+        let version = self.version;
+        for ((path, branch_path), table) in current.critical_path.iter_mut().zip(&mut current.branch_path).zip(&self.tables) {
+            *path += table.inner(version, branch, divided);
+            branch_path[branch as usize] += table.branch(version, branch, divided);
+        }
+    }
+
+    fn end_scratchpad_iteration(&mut self) {
+        let (version, steps) = (self.version, std::mem::take(&mut self.modular_power_steps));
+        self.add_path(|table| table.tail(version, steps));
+    }
+
+    fn add_mem_op(&mut self, site: Site, op: MemOp) {
+        let sequential = self.version.is_sequential(site);
+        if let Some(current) = &mut self.current {
+            let counter = match (op, sequential) {
+                (MemOp::Read, false) => &mut current.ops.random_reads,
+                (MemOp::Write, false) => &mut current.ops.random_writes,
+                (MemOp::Read, true) => &mut current.ops.sequential_reads,
+                (MemOp::Write, true) => &mut current.ops.sequential_writes,
+            };
+            *counter += 1;
+        }
+    }
+
+    pub fn report(&self) -> CostReport {
+        let mut report = CostReport {
+            version: self.version,
+            tables: self.tables.iter().map(|table| table.name.clone()).collect(),
+            hashes: self.hashes.len() as u64,
+            ops: OpCounts::default(),
+            critical_path: vec![0; self.tables.len()],
+            branch_path: vec![[0; 16]; self.tables.len()],
+        };
+
+        for hash in &self.hashes {
+            report.ops.add(&hash.ops);
+            for (path, hash_path) in report.critical_path.iter_mut().zip(&hash.critical_path) {
+                *path += hash_path;
+            }
+            for (branch_path, hash_branch_path) in report.branch_path.iter_mut().zip(&hash.branch_path) {
+                for (path, hash_path) in branch_path.iter_mut().zip(hash_branch_path) {
+                    *path += hash_path;
+                }
+            }
+        }
+
+        report
+    }
+}
+
+impl Observer for CostCounter {
+    fn on_start(&mut self) {
+        // The previous hash may have failed before its end
+        self.current = Some(HashCost::new(self.tables.len()));
+        self.iteration = None;
+        self.modular_power_steps = 0;
+        // Both start words are read at once
+        self.add_path(|table| table.sequential_read);
+    }
+
+    fn on_iteration(&mut self, i: usize, j: usize) {
+        self.end_iteration();
+        if j == 0 {
+            if i > 0 {
+                self.end_scratchpad_iteration();
+            }
+            let version = self.version;
+            self.add_path(|table| table.head(version));
+        }
+    }
+
+    fn on_branch(&mut self, branch: u8) {
+        self.iteration = Some((branch, false));
+    }
+
+    fn on_mem_read_at(&mut self, _index: usize, site: Site) {
+        self.add_mem_op(site, MemOp::Read);
+    }
+
+    fn on_mem_write_at(&mut self, _index: usize, site: Site) {
+        self.add_mem_op(site, MemOp::Write);
+    }
+
+    fn on_op(&mut self, op: Op, count: u64) {
+        if let Some(current) = &mut self.current {
+            current.ops.add_op(op, count);
+        }
+
+        match op {
+            // The inner loop only divides in the branches
+            Op::DivRem128 => if let Some((_, divided)) = &mut self.iteration {
+                *divided = true;
+            },
+            Op::ModularPowerStep => self.modular_power_steps += count,
+            _ => {},
+        }
+    }
+
+    fn on_finish(&mut self) {
+        self.end_iteration();
+        self.end_scratchpad_iteration();
+        if let Some(current) = self.current.take() {
+            self.hashes.push(current);
+        }
+    }
+}
+
+// Totals over the finished hashes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostReport {
+    pub version: Version,
+    // Names of the latency tables
+    pub tables: Vec<String>,
+    pub hashes: u64,
+    pub ops: OpCounts,
+    pub critical_path: Vec<u64>,
+    pub branch_path: Vec<[u64; 16]>,
+}
+
+impl CostReport {
+    // Average critical path latency of a hash for each latency table
+    pub fn critical_path_per_hash(&self) -> Vec<f64> {
+        self.critical_path.iter().map(|&path| per_hash(path, self.hashes)).collect()
+    }
+}
+
+fn per_hash(count: u64, hashes: u64) -> f64 {
+    if hashes == 0 {
+        return 0.0;
+    }
+    count as f64 / hashes as f64
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ops = &self.ops;
+        let average = |count| per_hash(count, self.hashes);
+
+        writeln!(f, "{:?}: {} hashes, per hash:", self.version, self.hashes)?;
+        writeln!(f, "  u128 div/rem: {:.1}, u64 div/rem: {:.1}, u128 mul: {:.1}", average(ops.div_rem_128), average(ops.div_rem_64), average(ops.mul_128))?;
+        writeln!(f, "  isqrt: {:.1}, modular power steps: {:.1}, AES rounds: {:.1}", average(ops.isqrt), average(ops.modular_power_steps), average(ops.aes_rounds))?;
+        writeln!(
+            f,
+            "  random reads: {:.1}, random writes: {:.1}, sequential reads: {:.1}, sequential writes: {:.1}",
+            average(ops.random_reads), average(ops.random_writes), average(ops.sequential_reads), average(ops.sequential_writes)
+        )?;
+
+        for ((name, path), branch_path) in self.tables.iter().zip(&self.critical_path).zip(&self.branch_path) {
+            let percent = |cycles: u64| if *path == 0 { 0.0 } else { cycles as f64 * 100.0 / *path as f64 };
+            writeln!(
+                f,
+                "  critical path with {} latencies: {:.0} cycles, {:.1}% in the branches, {:.1}% in the branches 0 to 2",
+                name,
+                average(*path),
+                percent(branch_path.iter().sum()),
+                percent(branch_path[..3].iter().sum())
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One scratchpad iteration of two inner iterations
+    fn run(counter: &mut CostCounter, branches: [u8; 2], divide: bool) {
+        counter.on_start();
+        counter.on_mem_read_at(0, Site::Start);
+        for (j, branch) in branches.into_iter().enumerate() {
+            counter.on_iteration(0, j);
+            counter.on_mem_read_at(1, Site::A);
+            counter.on_mem_read_at(2, Site::R);
+            counter.on_branch(branch);
+            if divide {
+                counter.on_op(Op::DivRem128, 1);
+            }
+            counter.on_mem_write_at(3, Site::T);
+        }
+        counter.on_op(Op::ModularPowerStep, 5);
+        counter.on_finish();
+    }
+
+    #[test]
+    fn test_counts() {
+        let mut counter = CostCounter::new(Version::V3, Vec::new());
+        run(&mut counter, [13, 13], true);
+
+        let ops = &counter.get_hashes()[0].ops;
+        assert_eq!(ops.div_rem_128, 2);
+        assert_eq!(ops.modular_power_steps, 5);
+        assert_eq!((ops.random_reads, ops.sequential_reads), (2, 3));
+        assert_eq!((ops.random_writes, ops.sequential_writes), (2, 0));
+
+        // The T site is sequential in v2
+        let mut counter = CostCounter::new(Version::V2, Vec::new());
+        run(&mut counter, [13, 13], true);
+        assert_eq!(counter.get_hashes()[0].ops.sequential_writes, 2);
+    }
+
+    #[test]
+    fn test_critical_path() {
+        let tables = vec![
+            LatencyTable { random_read: 1, ..Default::default() },
+            LatencyTable { div_rem_128: 1, ..Default::default() },
+            LatencyTable { modular_power_step: 1, ..Default::default() },
+        ];
+        let mut counter = CostCounter::new(Version::V3, tables.clone());
+        run(&mut counter, [13, 13], true);
+        run(&mut counter, [13, 2], false);
+        // Failed hash
+        counter.on_start();
+        counter.on_iteration(0, 0);
+
+        let hashes = counter.get_hashes();
+        assert_eq!(hashes.len(), 2);
+        // Two reads per head and per inner iteration
+        assert_eq!(hashes[0].critical_path, [6, 2, 5]);
+        // The branch 13 only divides if its operands allow it
+        assert_eq!(hashes[1].critical_path, [6, 0, 5]);
+        assert_eq!(hashes[0].branch_path[1][13], 2);
+
+        let mut counter = CostCounter::new(Version::V2, tables);
+        run(&mut counter, [13, 13], true);
+        // A single read per head and per inner iteration, no modular power
+        assert_eq!(counter.get_hashes()[0].critical_path, [3, 2, 0]);
+    }
+
+    #[test]
+    fn test_report() {
+        let mut counter = CostCounter::new(Version::V2, vec![LatencyTable::cpu(), LatencyTable::gpu()]);
+        run(&mut counter, [0, 10], true);
+        run(&mut counter, [1, 10], true);
+
+        let report = counter.report();
+        assert_eq!(report.hashes, 2);
+        assert_eq!(report.ops.div_rem_128, 4);
+        assert_eq!(report.critical_path_per_hash().len(), 2);
+        assert!(report.critical_path_per_hash()[1] > report.critical_path_per_hash()[0]);
+        assert_eq!(report.branch_path[0][10], 2 * (1 + 40));
+        assert!(report.to_string().contains("critical path with GPU latencies"));
+    }
+}
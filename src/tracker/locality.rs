@@ -0,0 +1,360 @@
+// Time-ordered log of the scratchpad accesses and the locality figures computed from it
+// Distances are counted per hash: the scratchpad is filled again by the stage 1 of each hash.
+
+use std::{collections::BTreeMap, fmt, io::Write};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Error, MemOp};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Access {
+    pub index: u32,
+    pub op: MemOp,
+}
+
+// Accesses of the tracked hashes in program order
+// A v2 or v3 hash makes close to a million accesses, so the log takes several MB per hash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccessLog {
+    accesses: Vec<Access>,
+    // start of each hash in `accesses`
+    hashes: Vec<usize>,
+}
+
+impl AccessLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start_hash(&mut self) {
+        self.hashes.push(self.accesses.len());
+    }
+
+    pub fn push(&mut self, index: usize, op: MemOp) {
+        if self.hashes.is_empty() {
+            self.start_hash();
+        }
+        self.accesses.push(Access { index: index as u32, op });
+    }
+
+    pub fn get_accesses(&self) -> &[Access] {
+        &self.accesses
+    }
+
+    // Accesses of each hash
+    pub fn hashes(&self) -> impl Iterator<Item = &[Access]> {
+        self.hashes.iter()
+            .zip(self.hashes.iter().skip(1).copied().chain(Some(self.accesses.len())))
+            .map(|(&start, end)| &self.accesses[start..end])
+    }
+
+    // Add the hashes of another log after the ones of this log
+    pub fn append(&mut self, other: &AccessLog) {
+        let offset = self.accesses.len();
+        self.hashes.extend(other.hashes.iter().map(|start| start + offset));
+        self.accesses.extend_from_slice(&other.accesses);
+    }
+
+    // Write the log as CSV with the `hash,index,op` columns
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writeln!(writer, "hash,index,op")?;
+        for (hash, accesses) in self.hashes().enumerate() {
+            for access in accesses {
+                let op = match access.op {
+                    MemOp::Read => "read",
+                    MemOp::Write => "write",
+                };
+                writeln!(writer, "{},{},{}", hash, access.index, op)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Fenwick tree counting the positions marked in a prefix
+struct Fenwick(Vec<i64>);
+
+impl Fenwick {
+    fn add(&mut self, position: usize, value: i64) {
+        let mut i = position + 1;
+        while i < self.0.len() {
+            self.0[i] += value;
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // Sum of the positions before `end`
+    fn sum(&self, end: usize) -> i64 {
+        let mut sum = 0;
+        let mut i = end;
+        while i > 0 {
+            sum += self.0[i];
+            i -= i & i.wrapping_neg();
+        }
+        sum
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Locality {
+    hashes: u64,
+    accesses: u64,
+    // accesses by LRU stack distance: the number of distinct indexes
+    // accessed since the previous access to the same index
+    reuse_distances: BTreeMap<u64, u64>,
+    // first accesses to an index in a hash, which have no reuse distance
+    cold_accesses: u64,
+    // index difference between each access and the previous one
+    strides: BTreeMap<i64, u64>,
+    // reads by number of accesses since the last write to the same index
+    read_after_write: BTreeMap<u64, u64>,
+    // reads of an index not written yet in the hash
+    reads_before_write: u64,
+}
+
+impl Locality {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_log(log: &AccessLog) -> Self {
+        let mut locality = Self::new();
+        for accesses in log.hashes() {
+            locality.add_hash(accesses);
+        }
+        locality
+    }
+
+    // Add the accesses of a single hash
+    pub fn add_hash(&mut self, accesses: &[Access]) {
+        let size = accesses.iter().map(|access| access.index as usize + 1).max().unwrap_or(0);
+        let mut last_access = vec![None; size];
+        let mut last_write = vec![None; size];
+        // Marks the time of the last access of each index
+        let mut marks = Fenwick(vec![0; accesses.len() + 1]);
+
+        self.hashes += 1;
+        self.accesses += accesses.len() as u64;
+        for (time, access) in accesses.iter().enumerate() {
+            let index = access.index as usize;
+            match last_access[index].replace(time) {
+                Some(previous) => {
+                    let distance = marks.sum(time) - marks.sum(previous + 1);
+                    *self.reuse_distances.entry(distance as u64).or_default() += 1;
+                    marks.add(previous, -1);
+                },
+                None => self.cold_accesses += 1,
+            }
+            marks.add(time, 1);
+
+            if time > 0 {
+                let stride = access.index as i64 - accesses[time - 1].index as i64;
+                *self.strides.entry(stride).or_default() += 1;
+            }
+
+            match access.op {
+                MemOp::Read => match last_write[index] {
+                    Some(write) => *self.read_after_write.entry((time - write) as u64).or_default() += 1,
+                    None => self.reads_before_write += 1,
+                },
+                MemOp::Write => last_write[index] = Some(time),
+            }
+        }
+    }
+
+    pub fn get_hashes(&self) -> u64 {
+        self.hashes
+    }
+
+    pub fn get_accesses(&self) -> u64 {
+        self.accesses
+    }
+
+    pub fn get_reuse_distances(&self) -> &BTreeMap<u64, u64> {
+        &self.reuse_distances
+    }
+
+    pub fn get_cold_accesses(&self) -> u64 {
+        self.cold_accesses
+    }
+
+    pub fn get_strides(&self) -> &BTreeMap<i64, u64> {
+        &self.strides
+    }
+
+    pub fn get_read_after_write(&self) -> &BTreeMap<u64, u64> {
+        &self.read_after_write
+    }
+
+    pub fn get_reads_before_write(&self) -> u64 {
+        self.reads_before_write
+    }
+
+    // Fraction of the accesses hitting a fully associative LRU cache of `words` u64s,
+    // which is empty at the start of each hash
+    pub fn lru_hit_rate(&self, words: u64) -> f64 {
+        if self.accesses == 0 {
+            return 0.0;
+        }
+
+        let hits: u64 = self.reuse_distances.range(..words).map(|(_, count)| count).sum();
+        hits as f64 / self.accesses as f64
+    }
+
+    pub fn merge(&mut self, other: &Locality) {
+        self.hashes += other.hashes;
+        self.accesses += other.accesses;
+        self.cold_accesses += other.cold_accesses;
+        self.reads_before_write += other.reads_before_write;
+        for (distance, count) in &other.reuse_distances {
+            *self.reuse_distances.entry(*distance).or_default() += count;
+        }
+        for (stride, count) in &other.strides {
+            *self.strides.entry(*stride).or_default() += count;
+        }
+        for (distance, count) in &other.read_after_write {
+            *self.read_after_write.entry(*distance).or_default() += count;
+        }
+    }
+
+    // Write the reuse distances as CSV with the `distance,count` columns
+    // The cold accesses are not included
+    pub fn write_reuse_distances_csv<W: Write>(&self, writer: W) -> Result<(), Error> {
+        write_histogram_csv(writer, "distance", &self.reuse_distances)
+    }
+
+    // Write the strides as CSV with the `stride,count` columns
+    pub fn write_strides_csv<W: Write>(&self, writer: W) -> Result<(), Error> {
+        write_histogram_csv(writer, "stride", &self.strides)
+    }
+
+    // Write the read after write distances as CSV with the `distance,count` columns
+    // The reads before any write are not included
+    pub fn write_read_after_write_csv<W: Write>(&self, writer: W) -> Result<(), Error> {
+        write_histogram_csv(writer, "distance", &self.read_after_write)
+    }
+}
+
+fn write_histogram_csv<W: Write, K: fmt::Display>(mut writer: W, key: &str, histogram: &BTreeMap<K, u64>) -> Result<(), Error> {
+    writeln!(writer, "{},count", key)?;
+    for (key, count) in histogram {
+        writeln!(writer, "{},{}", key, count)?;
+    }
+
+    Ok(())
+}
+
+// Cache sizes of the summary, in KiB
+const SUMMARY_CACHE_SIZES: [u64; 5] = [32, 64, 256, 512, 1024];
+
+impl fmt::Display for Locality {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |count: u64, total: u64| if total == 0 { 0.0 } else { count as f64 * 100.0 / total as f64 };
+
+        writeln!(f, "{} accesses in {} hashes, {:.2}% cold", self.accesses, self.hashes, percent(self.cold_accesses, self.accesses))?;
+        for size in SUMMARY_CACHE_SIZES {
+            writeln!(f, "LRU hit rate with {} KiB: {:.2}%", size, self.lru_hit_rate(size * 1024 / 8) * 100.0)?;
+        }
+
+        let strides: u64 = self.strides.values().sum();
+        let sequential: u64 = self.strides.range(-1..=1).map(|(_, count)| count).sum();
+        writeln!(f, "Strides of -1, 0 or 1: {:.2}%", percent(sequential, strides))?;
+
+        let reads = self.reads_before_write + self.read_after_write.values().sum::<u64>();
+        let close: u64 = self.read_after_write.range(..=16).map(|(_, count)| count).sum();
+        writeln!(f, "Reads of a written index: {:.2}%, within 16 accesses: {:.2}%", percent(reads - self.reads_before_write, reads), percent(close, reads))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(hashes: &[&[(usize, MemOp)]]) -> AccessLog {
+        let mut log = AccessLog::new();
+        for accesses in hashes {
+            log.start_hash();
+            for &(index, op) in accesses.iter() {
+                log.push(index, op);
+            }
+        }
+        log
+    }
+
+    #[test]
+    fn test_reuse_distances() {
+        use MemOp::*;
+        let log = log(&[&[(0, Read), (1, Read), (2, Read), (1, Read), (0, Read), (0, Write)]]);
+        let locality = Locality::from_log(&log);
+
+        assert_eq!(locality.get_accesses(), 6);
+        assert_eq!(locality.get_cold_accesses(), 3);
+        // 1 after {2}, 0 after {1, 2} and 0 right after itself
+        assert_eq!(locality.get_reuse_distances(), &BTreeMap::from([(0, 1), (1, 1), (2, 1)]));
+        assert_eq!(locality.lru_hit_rate(1), 1.0 / 6.0);
+        assert_eq!(locality.lru_hit_rate(3), 3.0 / 6.0);
+        assert_eq!(locality.get_strides(), &BTreeMap::from([(-1, 2), (0, 1), (1, 2)]));
+    }
+
+    #[test]
+    fn test_read_after_write() {
+        use MemOp::*;
+        let log = log(&[&[(3, Read), (3, Write), (5, Write), (3, Read), (5, Read), (3, Read)]]);
+        let locality = Locality::from_log(&log);
+
+        assert_eq!(locality.get_reads_before_write(), 1);
+        assert_eq!(locality.get_read_after_write(), &BTreeMap::from([(2, 2), (4, 1)]));
+    }
+
+    #[test]
+    fn test_hashes() {
+        use MemOp::*;
+        let mut log = log(&[&[(1, Write), (1, Read)], &[]]);
+        log.append(&self::log(&[&[(1, Read), (2, Read)]]));
+        assert_eq!(log.hashes().map(|accesses| accesses.len()).collect::<Vec<_>>(), [2, 0, 2]);
+
+        // Nothing is reused across hashes
+        let locality = Locality::from_log(&log);
+        assert_eq!(locality.get_hashes(), 3);
+        assert_eq!(locality.get_cold_accesses(), 3);
+        assert_eq!(locality.get_reads_before_write(), 2);
+        assert_eq!(locality.get_strides(), &BTreeMap::from([(0, 1), (1, 1)]));
+
+        let mut merged = locality.clone();
+        merged.merge(&locality);
+        assert_eq!(merged.get_accesses(), 8);
+        assert_eq!(merged.get_reuse_distances(), &BTreeMap::from([(0, 2)]));
+        assert_eq!(merged.get_read_after_write(), &BTreeMap::from([(1, 2)]));
+
+        let mut csv = Vec::new();
+        log.write_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "hash,index,op\n0,1,write\n0,1,read\n2,1,read\n2,2,read\n");
+
+        let mut csv = Vec::new();
+        merged.write_strides_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "stride,count\n0,2\n1,2\n");
+    }
+
+    #[test]
+    fn test_stack_distance_naive() {
+        use rand::{rngs::StdRng, Rng, SeedableRng};
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let accesses: Vec<(usize, MemOp)> = (0..2000).map(|_| (rng.gen_range(0..64), MemOp::Read)).collect();
+        let locality = Locality::from_log(&log(&[&accesses]));
+
+        // Compare with an explicit LRU stack
+        let mut stack: Vec<usize> = Vec::new();
+        let mut expected = BTreeMap::new();
+        for &(index, _) in &accesses {
+            if let Some(position) = stack.iter().position(|&i| i == index) {
+                *expected.entry(position as u64).or_insert(0u64) += 1;
+                stack.remove(position);
+            }
+            stack.insert(0, index);
+        }
+        assert_eq!(locality.get_reuse_distances(), &expected);
+    }
+}
@@ -0,0 +1,498 @@
+// Observers of the stage 3 of v2 and v3, enabled by the `tracker-core` feature
+//
+// `OpsTracker` counts the branches, the transitions from a branch to the next one, the runs of
+// a repeated branch and the reads and writes of each scratchpad index. It can be saved as JSON,
+// merged with other saved trackers of the same version and written as CSV, see the `track` example.
+// With `with_access_log`, it also logs the accesses in program order for `locality` and `cache`.
+// The `warp`, `coalescing` and `cost` observers model the hashes running on a GPU and their latency.
+
+use std::io::{Read, Write};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use crate::observer::Observer;
+
+// Charts require the `tracker` feature
+#[cfg(feature = "tracker")]
+mod plot;
+pub mod cache;
+pub mod coalescing;
+pub mod cost;
+pub mod locality;
+pub mod warp;
+#[cfg(all(test, any(feature = "v2", feature = "v3")))]
+pub(crate) mod test_helpers;
+
+use locality::{AccessLog, Locality};
+
+// Version of the JSON format written by `OpsTracker::write_json`
+pub const FORMAT: u32 = 1;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Unsupported tracker format: {0}")]
+    UnsupportedFormat(u32),
+    #[error("Scratchpad size mismatch: {0} != {1}")]
+    SizeMismatch(usize, usize),
+    #[error("Invalid cache configuration: {0}")]
+    InvalidCacheConfig(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MemOp {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemTracker {
+    pub read: u64,
+    pub write: u64,
+}
+
+// Track the operations used in each iteration
+// This is used to verify that we have a good distribution
+// in branches and memory operations
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpsTracker {
+    // number of hashes tracked
+    hashes: u64,
+    // branches id used at each iteration
+    branches: [usize; 16],
+    // memory operations used at each iteration
+    // first Vec represents the scratchpad with each index
+    // inner Vec represents the memory operations used at each index
+    mem_ops: Vec<MemTracker>,
+    // number of times each branch (row) is followed by each branch (column) in a hash
+    #[serde(default)]
+    transitions: [[u64; 16]; 16],
+    // number of runs of the same branch by length, starting at 1
+    #[serde(default)]
+    run_lengths: Vec<u64>,
+    // branch of the current hash and length of its current run
+    #[serde(skip)]
+    run: Option<(u8, usize)>,
+    // time-ordered accesses, only recorded if enabled and not saved
+    #[serde(skip)]
+    access_log: Option<AccessLog>,
+}
+
+// Saved tracker, tagged with the format version
+#[derive(Serialize)]
+struct FileRef<'a> {
+    format: u32,
+    #[serde(flatten)]
+    tracker: &'a OpsTracker,
+}
+
+#[derive(Deserialize)]
+struct File {
+    format: u32,
+    #[serde(flatten)]
+    tracker: OpsTracker,
+}
+
+impl OpsTracker {
+    pub fn new(scratchpad: usize) -> Self {
+        Self {
+            hashes: 0,
+            branches: [0; 16],
+            mem_ops: vec![Default::default(); scratchpad],
+            transitions: [[0; 16]; 16],
+            run_lengths: Vec::new(),
+            run: None,
+            access_log: None,
+        }
+    }
+
+    // Also record the time-ordered accesses, to compute their locality
+    pub fn with_access_log(mut self) -> Self {
+        self.access_log = Some(AccessLog::new());
+        self
+    }
+
+    pub fn add_branch(&mut self, branch: u8) {
+        self.branches[branch as usize] += 1;
+
+        self.run = match self.run {
+            Some((previous, length)) => {
+                self.transitions[previous as usize][branch as usize] += 1;
+                if previous == branch {
+                    Some((branch, length + 1))
+                } else {
+                    self.add_run(length);
+                    Some((branch, 1))
+                }
+            },
+            None => Some((branch, 1)),
+        };
+    }
+
+    fn add_run(&mut self, length: usize) {
+        if self.run_lengths.len() < length {
+            self.run_lengths.resize(length, 0);
+        }
+        self.run_lengths[length - 1] += 1;
+    }
+
+    // End the branch sequence of the current hash, transitions are not tracked across hashes
+    pub fn end_hash(&mut self) {
+        if let Some((_, length)) = self.run.take() {
+            self.add_run(length);
+        }
+    }
+
+    pub fn add_mem_op(&mut self, index: usize, mem_op: MemOp) {
+        let tracker = &mut self.mem_ops[index];
+        match mem_op {
+            MemOp::Read => tracker.read += 1,
+            MemOp::Write => tracker.write += 1,
+        }
+
+        if let Some(access_log) = &mut self.access_log {
+            access_log.push(index, mem_op);
+        }
+    }
+
+    pub fn get_hashes(&self) -> u64 {
+        self.hashes
+    }
+
+    pub fn get_branches(&self) -> &[usize; 16] {
+        &self.branches
+    }
+
+    pub fn get_mem_ops(&self) -> &Vec<MemTracker> {
+        &self.mem_ops
+    }
+
+    pub fn get_transitions(&self) -> &[[u64; 16]; 16] {
+        &self.transitions
+    }
+
+    pub fn get_run_lengths(&self) -> &[u64] {
+        &self.run_lengths
+    }
+
+    pub fn get_access_log(&self) -> Option<&AccessLog> {
+        self.access_log.as_ref()
+    }
+
+    // Locality of the logged accesses, if the access log is enabled
+    pub fn locality(&self) -> Option<Locality> {
+        self.access_log.as_ref().map(Locality::from_log)
+    }
+
+    // Entropy rate of the branch sequence in bits per branch, 4 at most
+    // The sequence is estimated as a Markov chain from the transitions:
+    // H = -sum(p(i) * p(j | i) * log2(p(j | i)))
+    pub fn branch_entropy_rate(&self) -> f64 {
+        let total: u64 = self.transitions.iter().flatten().sum();
+        if total == 0 {
+            return 0.0;
+        }
+
+        let mut entropy = 0.0;
+        for row in &self.transitions {
+            let row_total: u64 = row.iter().sum();
+            for &count in row.iter().filter(|&&count| count > 0) {
+                let p = count as f64 / row_total as f64;
+                entropy -= row_total as f64 / total as f64 * p * p.log2();
+            }
+        }
+
+        entropy
+    }
+
+    // Add the operations of another tracker of the same scratchpad size
+    // Its logged accesses are only added if both trackers log them
+    pub fn merge(&mut self, other: &OpsTracker) -> Result<(), Error> {
+        if self.mem_ops.len() != other.mem_ops.len() {
+            return Err(Error::SizeMismatch(self.mem_ops.len(), other.mem_ops.len()));
+        }
+
+        self.hashes += other.hashes;
+        for (branch, other) in self.branches.iter_mut().zip(other.branches.iter()) {
+            *branch += other;
+        }
+        for (mem_op, other) in self.mem_ops.iter_mut().zip(other.mem_ops.iter()) {
+            mem_op.read += other.read;
+            mem_op.write += other.write;
+        }
+        for (row, other) in self.transitions.iter_mut().zip(other.transitions.iter()) {
+            for (count, other) in row.iter_mut().zip(other.iter()) {
+                *count += other;
+            }
+        }
+        if self.run_lengths.len() < other.run_lengths.len() {
+            self.run_lengths.resize(other.run_lengths.len(), 0);
+        }
+        for (count, other) in self.run_lengths.iter_mut().zip(other.run_lengths.iter()) {
+            *count += other;
+        }
+        if let (Some(access_log), Some(other)) = (&mut self.access_log, &other.access_log) {
+            access_log.append(other);
+        }
+
+        Ok(())
+    }
+
+    // Save the tracker as JSON, to be loaded by `read_json`
+    pub fn write_json<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer(writer, &FileRef { format: FORMAT, tracker: self })?;
+        Ok(())
+    }
+
+    pub fn read_json<R: Read>(reader: R) -> Result<Self, Error> {
+        let file: File = serde_json::from_reader(reader)?;
+        if file.format != FORMAT {
+            return Err(Error::UnsupportedFormat(file.format));
+        }
+
+        Ok(file.tracker)
+    }
+
+    // Write the branch usage as CSV with the `branch,count` columns
+    pub fn write_branches_csv<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writeln!(writer, "branch,count")?;
+        for (branch, count) in self.branches.iter().enumerate() {
+            writeln!(writer, "{},{}", branch, count)?;
+        }
+
+        Ok(())
+    }
+
+    // Write the branch transitions as CSV with the `from,to,count` columns
+    pub fn write_transitions_csv<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writeln!(writer, "from,to,count")?;
+        for (from, row) in self.transitions.iter().enumerate() {
+            for (to, count) in row.iter().enumerate() {
+                writeln!(writer, "{},{},{}", from, to, count)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    // Write the runs of the same branch as CSV with the `length,count` columns
+    pub fn write_run_lengths_csv<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writeln!(writer, "length,count")?;
+        for (length, count) in self.run_lengths.iter().enumerate() {
+            writeln!(writer, "{},{}", length + 1, count)?;
+        }
+
+        Ok(())
+    }
+
+    // Write the memory operations as CSV with the `index,read,write` columns
+    pub fn write_mem_ops_csv<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writeln!(writer, "index,read,write")?;
+        for (index, ops) in self.mem_ops.iter().enumerate() {
+            writeln!(writer, "{},{},{}", index, ops.read, ops.write)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Observer for OpsTracker {
+    fn on_start(&mut self) {
+        // The previous hash may have failed before its end
+        self.end_hash();
+        self.hashes += 1;
+        if let Some(access_log) = &mut self.access_log {
+            access_log.start_hash();
+        }
+    }
+
+    fn on_branch(&mut self, branch: u8) {
+        self.add_branch(branch);
+    }
+
+    fn on_mem_read(&mut self, index: usize) {
+        self.add_mem_op(index, MemOp::Read);
+    }
+
+    fn on_mem_write(&mut self, index: usize) {
+        self.add_mem_op(index, MemOp::Write);
+    }
+
+    fn on_finish(&mut self) {
+        self.end_hash();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tracker() -> OpsTracker {
+        let mut tracker = OpsTracker::new(4);
+        tracker.on_start();
+        tracker.on_branch(3);
+        tracker.on_branch(15);
+        tracker.on_mem_read(1);
+        tracker.on_mem_write(1);
+        tracker.on_mem_write(3);
+        tracker.on_finish();
+        tracker
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let tracker = tracker();
+        let mut json = Vec::new();
+        tracker.write_json(&mut json).unwrap();
+        assert_eq!(OpsTracker::read_json(json.as_slice()).unwrap(), tracker);
+
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.starts_with(r#"{"format":1,"hashes":1,"branches":[0,0,0,1,"#), "{}", json);
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        let json = r#"{"format":2,"hashes":0,"branches":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"mem_ops":[]}"#;
+        assert!(matches!(OpsTracker::read_json(json.as_bytes()), Err(Error::UnsupportedFormat(2))));
+    }
+
+    #[test]
+    fn test_read_without_transitions() {
+        let json = r#"{"format":1,"hashes":0,"branches":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0],"mem_ops":[]}"#;
+        let tracker = OpsTracker::read_json(json.as_bytes()).unwrap();
+        assert_eq!(tracker.get_transitions(), &[[0; 16]; 16]);
+        assert!(tracker.get_run_lengths().is_empty());
+    }
+
+    #[test]
+    fn test_transitions() {
+        let mut tracker = OpsTracker::new(4);
+        for branches in [[1, 1, 1, 2, 2, 1], [1, 1, 2, 2, 2, 2]] {
+            tracker.on_start();
+            for branch in branches {
+                tracker.on_branch(branch);
+            }
+            tracker.on_finish();
+        }
+
+        let transitions = tracker.get_transitions();
+        assert_eq!(transitions[1][1], 3);
+        assert_eq!(transitions[1][2], 2);
+        assert_eq!(transitions[2][1], 1);
+        assert_eq!(transitions[2][2], 4);
+        // No transition between the hashes
+        assert_eq!(transitions.iter().flatten().sum::<u64>(), 10);
+        assert_eq!(tracker.get_run_lengths(), [1, 2, 1, 1]);
+
+        let mut csv = Vec::new();
+        tracker.write_run_lengths_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "length,count\n1,1\n2,2\n3,1\n4,1\n");
+
+        let mut csv = Vec::new();
+        tracker.write_transitions_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 257);
+        assert!(csv.contains("\n1,2,2\n"));
+    }
+
+    #[test]
+    fn test_failed_hash_run() {
+        let mut tracker = OpsTracker::new(4);
+        tracker.on_start();
+        tracker.on_branch(5);
+        // The hash failed, the next one ends its run
+        tracker.on_start();
+        tracker.on_branch(5);
+        tracker.on_finish();
+
+        assert_eq!(tracker.get_transitions()[5][5], 0);
+        assert_eq!(tracker.get_run_lengths(), [2]);
+    }
+
+    #[test]
+    fn test_branch_entropy_rate() {
+        let mut tracker = OpsTracker::new(4);
+        assert_eq!(tracker.branch_entropy_rate(), 0.0);
+
+        // Predictable sequence
+        for branch in (0..16).cycle().take(1000) {
+            tracker.on_branch(branch);
+        }
+        assert_eq!(tracker.branch_entropy_rate(), 0.0);
+
+        tracker.transitions = [[1; 16]; 16];
+        assert!((tracker.branch_entropy_rate() - 4.0).abs() < 1e-12);
+
+        // Two equally likely successors for each branch
+        tracker.transitions = [[0; 16]; 16];
+        for from in 0..16 {
+            tracker.transitions[from][from] = 10;
+            tracker.transitions[from][(from + 1) % 16] = 10;
+        }
+        assert!((tracker.branch_entropy_rate() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut merged = tracker();
+        merged.merge(&tracker()).unwrap();
+        assert_eq!(merged.get_hashes(), 2);
+        assert_eq!(merged.get_branches()[3], 2);
+        assert_eq!(merged.get_mem_ops()[1], MemTracker { read: 2, write: 2 });
+        assert_eq!(merged.get_mem_ops()[3], MemTracker { read: 0, write: 2 });
+        assert_eq!(merged.get_transitions()[3][15], 2);
+        assert_eq!(merged.get_run_lengths(), [4]);
+
+        assert!(matches!(merged.merge(&OpsTracker::new(5)), Err(Error::SizeMismatch(4, 5))));
+    }
+
+    #[test]
+    fn test_access_log() {
+        assert!(tracker().get_access_log().is_none());
+        assert!(tracker().locality().is_none());
+
+        let mut tracker = OpsTracker::new(4).with_access_log();
+        for _ in 0..2 {
+            tracker.on_start();
+            tracker.on_mem_read(1);
+            tracker.on_mem_write(1);
+            tracker.on_mem_read(3);
+            tracker.on_finish();
+        }
+
+        let access_log = tracker.get_access_log().unwrap();
+        assert_eq!(access_log.hashes().count(), 2);
+        assert_eq!(access_log.get_accesses()[2], locality::Access { index: 3, op: MemOp::Read });
+
+        let locality = tracker.locality().unwrap();
+        assert_eq!(locality.get_accesses(), 6);
+        assert_eq!(locality.get_cold_accesses(), 4);
+
+        let mut merged = tracker.clone();
+        merged.merge(&tracker).unwrap();
+        assert_eq!(merged.get_access_log().unwrap().hashes().count(), 4);
+
+        // Not saved
+        let mut json = Vec::new();
+        tracker.write_json(&mut json).unwrap();
+        assert!(OpsTracker::read_json(json.as_slice()).unwrap().get_access_log().is_none());
+    }
+
+    #[test]
+    fn test_csv() {
+        let tracker = tracker();
+        let mut csv = Vec::new();
+        tracker.write_mem_ops_csv(&mut csv).unwrap();
+        assert_eq!(String::from_utf8(csv).unwrap(), "index,read,write\n0,0,0\n1,1,1\n2,0,0\n3,0,1\n");
+
+        let mut csv = Vec::new();
+        tracker.write_branches_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert_eq!(csv.lines().count(), 17);
+        assert!(csv.starts_with("branch,count\n0,0\n1,0\n2,0\n3,1\n"));
+    }
+}
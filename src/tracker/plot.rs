@@ -11,57 +11,70 @@ use plotters::{
     }
 };
 
-#[derive(Debug, Clone, Copy)]
-pub enum MemOp {
-    Read,
-    Write,
-}
+use super::OpsTracker;
 
-#[derive(Debug, Clone, Copy, Default)]
-pub struct MemTracker {
-    pub read: u64,
-    pub write: u64,
-}
+// Charts of the tracked operations
+impl OpsTracker {
+    /// Generate a heatmap of the probability of each branch (column) to follow each branch (row)
+    pub fn generate_transition_heatmap(&self, output_path: &str) -> Result<(), anyhow::Error> {
+        // Probabilities by row, a uniform sequence gives 1/16 everywhere
+        let probabilities: Vec<[f64; 16]> = self.transitions
+            .iter()
+            .map(|row| {
+                let total = row.iter().sum::<u64>().max(1) as f64;
+                row.map(|count| count as f64 / total)
+            })
+            .collect();
 
-// Track the operations used in each iteration
-// This is used to verify that we have a good distribution
-// in branches and memory operations
-#[derive(Debug)]
-pub struct OpsTracker {
-    // branches id used at each iteration
-    branches: [usize; 16],
-    // memory operations used at each iteration
-    // first Vec represents the scratchpad with each index
-    // inner Vec represents the memory operations used at each index
-    mem_ops: Vec<MemTracker>,
-}
+        let max_val = probabilities
+            .iter()
+            .flatten()
+            .cloned()
+            .fold(0.0_f64, f64::max)
+            .max(f64::MIN_POSITIVE);
 
-impl OpsTracker {
-    pub fn new(scratchpad: usize) -> Self {
-        Self {
-            branches: [0; 16],
-            mem_ops: vec![Default::default(); scratchpad],
-        }
-    }
+        let root = BitMapBackend::new(output_path, (1000, 1000)).into_drawing_area();
+        root.fill(&WHITE)?;
 
-    pub fn add_branch(&mut self, branch: u8) {
-        self.branches[branch as usize] += 1;
-    }
+        let mut chart = ChartBuilder::on(&root)
+            .caption(format!("Branch Transitions (entropy rate {:.4} bits)", self.branch_entropy_rate()), ("sans-serif", 30))
+            .margin(20)
+            .x_label_area_size(40)
+            .y_label_area_size(60)
+            .build_cartesian_2d(0f64..16f64, 16f64..0f64)?;
 
-    pub fn add_mem_op(&mut self, index: usize, mem_op: MemOp) {
-        let tracker = &mut self.mem_ops[index];
-        match mem_op {
-            MemOp::Read => tracker.read += 1,
-            MemOp::Write => tracker.write += 1,
-        }
-    }
+        chart
+            .configure_mesh()
+            .disable_mesh()
+            .x_labels(16)
+            .y_labels(16)
+            .x_label_formatter(&|x| format!("{}", *x as usize))
+            .y_label_formatter(&|y| format!("{}", *y as usize))
+            .x_desc("Next Branch ID")
+            .y_desc("Branch ID")
+            .axis_desc_style(("sans-serif", 20))
+            .draw()?;
 
-    pub fn get_branches(&self) -> &[usize; 16] {
-        &self.branches
-    }
+        let label_style = TextStyle::from(("sans-serif", 12).into_font())
+            .pos(Pos::new(HPos::Center, VPos::Center));
+
+        for (from, row) in probabilities.iter().enumerate() {
+            for (to, &p) in row.iter().enumerate() {
+                // White for 0, dark blue for the highest probability
+                let shade = 1.0 - p / max_val;
+                let color = RGBColor((30.0 + 225.0 * shade) as u8, (120.0 + 135.0 * shade) as u8, (200.0 + 55.0 * shade) as u8);
+                let (x, y) = (to as f64, from as f64);
+                chart.draw_series(std::iter::once(Rectangle::new([(x, y), (x + 1.0, y + 1.0)], color.filled())))?;
+                chart.draw_series(std::iter::once(Text::new(
+                    format!("{:.1}", p * 100.0),
+                    (x + 0.5, y + 0.5),
+                    label_style.clone(),
+                )))?;
+            }
+        }
 
-    pub fn get_mem_ops(&self) -> &Vec<MemTracker> {
-        &self.mem_ops
+        root.present()?;
+        Ok(())
     }
 
     /// Generate a percentage-based heatmap of branch usage
@@ -307,4 +320,4 @@ pub fn generate_memory_usage_graph(
     Ok(())
 }
 
-}
\ No newline at end of file
+}
@@ -0,0 +1,25 @@
+// Observer checks shared by the v2 and v3 tests, which pass their small parameters hash
+
+use crate::{observer::Observer, Error, Hash};
+
+use super::warp::{WarpReport, WarpSimulator};
+
+// Hash 64 inputs, two warps of 32 lanes, inputs without a valid hash are observed up to the error
+pub(crate) fn hash_warps<O: Observer>(observer: &mut O, mut hash: impl FnMut(&[u8], &mut O) -> Result<Hash, Error>) {
+    let mut input = [0u8; 112];
+    for i in 0..64u8 {
+        input[0] = i;
+        let _ = hash(&input, observer);
+    }
+}
+
+// Both versions are close to 16 independent uniform branches
+pub(crate) fn check_warp_divergence(hash: impl FnMut(&[u8], &mut WarpSimulator) -> Result<Hash, Error>) {
+    let mut simulator = WarpSimulator::new(32);
+    hash_warps(&mut simulator, hash);
+
+    let report = simulator.report();
+    assert_eq!(report.warps, 2);
+    assert!((report.serialization_factor() - WarpReport::uniform_serialization_factor(32)).abs() < 0.3, "{}", report);
+    assert!(report.lane_utilization() < 0.1, "{}", report);
+}
@@ -0,0 +1,198 @@
+// Branch divergence of hashes running in lockstep as the lanes of a GPU warp
+//
+// Each hash observed is a lane, and every `width` hashes make a warp. At each inner
+// iteration, a warp runs the branch of each of its distinct `branch_idx` one after the
+// other, with the lanes taking another branch masked. Lanes whose hash ended or failed
+// are idle until the longest lane of the warp is done.
+// Only the branch itself diverges: the scratchpad accesses and address computations
+// around it run for all the lanes at once.
+
+use std::fmt;
+
+use crate::observer::Observer;
+
+pub struct WarpSimulator {
+    width: usize,
+    // branches of the lanes of the current warp
+    lanes: Vec<Vec<u8>>,
+    report: WarpReport,
+}
+
+impl WarpSimulator {
+    pub fn new(width: usize) -> Self {
+        assert!(width > 0, "a warp needs at least one lane");
+        Self {
+            width,
+            lanes: Vec::with_capacity(width),
+            report: WarpReport { width, ..Default::default() },
+        }
+    }
+
+    // Add a lane running the given branches, the warp runs once it is full
+    pub fn add_lane(&mut self, branches: &[u8]) {
+        self.start_lane();
+        self.lanes.last_mut().expect("lane").extend_from_slice(branches);
+        self.end_lane();
+    }
+
+    fn start_lane(&mut self) {
+        // The last lane of a full warp may have failed before its end
+        self.end_lane();
+        self.lanes.push(Vec::new());
+    }
+
+    fn end_lane(&mut self) {
+        if self.lanes.len() == self.width {
+            self.run_warp();
+        }
+    }
+
+    fn run_warp(&mut self) {
+        let steps = self.lanes.iter().map(Vec::len).max().unwrap_or(0);
+        self.report.warps += 1;
+        self.report.steps += steps as u64;
+        for step in 0..steps {
+            let mut branches = 0u16;
+            let mut active = 0;
+            for lane in self.lanes.iter().filter_map(|lane| lane.get(step)) {
+                branches |= 1 << lane;
+                active += 1;
+            }
+
+            let distinct = branches.count_ones() as usize;
+            self.report.passes += distinct as u64;
+            self.report.active_lanes += active;
+            self.report.distinct_branches[distinct - 1] += 1;
+        }
+        self.lanes.clear();
+    }
+
+    // Report of the full warps, the lanes of an incomplete warp are not included
+    pub fn report(&self) -> &WarpReport {
+        &self.report
+    }
+}
+
+impl Observer for WarpSimulator {
+    fn on_start(&mut self) {
+        self.start_lane();
+    }
+
+    fn on_branch(&mut self, branch: u8) {
+        if let Some(lane) = self.lanes.last_mut() {
+            lane.push(branch);
+        }
+    }
+
+    fn on_finish(&mut self) {
+        self.end_lane();
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WarpReport {
+    pub width: usize,
+    pub warps: u64,
+    // Inner iterations run by the warps
+    pub steps: u64,
+    // Branches run by the warps, one per distinct branch of each step
+    pub passes: u64,
+    // Lanes running a branch over all the passes
+    pub active_lanes: u64,
+    // Steps by number of distinct branches, starting at 1
+    pub distinct_branches: [u64; 16],
+}
+
+impl WarpReport {
+    // Average number of branches run one after the other at each step, 1 without divergence
+    pub fn serialization_factor(&self) -> f64 {
+        if self.steps == 0 {
+            return 0.0;
+        }
+        self.passes as f64 / self.steps as f64
+    }
+
+    // Fraction of the lanes doing useful work over all the passes
+    pub fn lane_utilization(&self) -> f64 {
+        if self.passes == 0 {
+            return 0.0;
+        }
+        self.active_lanes as f64 / (self.passes * self.width as u64) as f64
+    }
+
+    // Serialization factor if each lane picked one of the 16 branches at random:
+    // the expected number of distinct branches among `width` uniform picks
+    pub fn uniform_serialization_factor(width: usize) -> f64 {
+        16.0 * (1.0 - (15.0f64 / 16.0).powi(width as i32))
+    }
+}
+
+impl fmt::Display for WarpReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} warps of {} lanes, {} steps", self.warps, self.width, self.steps)?;
+        writeln!(
+            f,
+            "Serialization factor: {:.3} ({:.3} for uniform branches)",
+            self.serialization_factor(),
+            Self::uniform_serialization_factor(self.width)
+        )?;
+        write!(f, "Lane utilization: {:.2}%", self.lane_utilization() * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warp() {
+        let mut simulator = WarpSimulator::new(2);
+        simulator.add_lane(&[1, 2, 3]);
+        assert_eq!(simulator.report().warps, 0);
+        simulator.add_lane(&[1, 5]);
+
+        let report = simulator.report();
+        assert_eq!(report.warps, 1);
+        assert_eq!(report.steps, 3);
+        // {1}, {2, 5}, then {3} with an idle lane
+        assert_eq!(report.passes, 4);
+        assert_eq!(report.active_lanes, 5);
+        assert_eq!(&report.distinct_branches[..3], [2, 1, 0]);
+        assert_eq!(report.serialization_factor(), 4.0 / 3.0);
+        assert_eq!(report.lane_utilization(), 5.0 / 8.0);
+    }
+
+    #[test]
+    fn test_observer() {
+        let mut simulator = WarpSimulator::new(3);
+        for branches in [[0, 15], [0, 15], [0, 15], [4, 4]] {
+            simulator.on_start();
+            for branch in branches {
+                simulator.on_branch(branch);
+            }
+            simulator.on_finish();
+        }
+
+        // The last lane is pending
+        let report = simulator.report();
+        assert_eq!((report.warps, report.steps, report.passes), (1, 2, 2));
+        assert_eq!(report.serialization_factor(), 1.0);
+        assert_eq!(report.lane_utilization(), 1.0);
+
+        // A failed lane doesn't end the warp, the next one does
+        simulator.on_start();
+        simulator.on_branch(4);
+        simulator.on_start();
+        simulator.on_branch(9);
+        simulator.on_finish();
+        assert_eq!(simulator.report().warps, 2);
+        assert_eq!(simulator.report().passes, 5);
+    }
+
+    #[test]
+    fn test_uniform_serialization_factor() {
+        assert_eq!(WarpReport::uniform_serialization_factor(1), 1.0);
+        assert!((WarpReport::uniform_serialization_factor(32) - 13.9714).abs() < 1e-4);
+        assert!((WarpReport::uniform_serialization_factor(64) - 15.7428).abs() < 1e-4);
+    }
+}
@@ -1,7 +1,6 @@
-use aes::cipher::generic_array::GenericArray;
 use tiny_keccak::keccakp;
 
-use crate::{Hash, HASH_SIZE, Error, scratchpad::ScratchPad as ScratchPadInternal};
+use crate::{Hash, HASH_SIZE, Error, aes_round, scratchpad::{self, ScratchPad as ScratchPadInternal}};
 
 // These are tweakable parameters
 pub const MEMORY_SIZE: usize = 32768;
@@ -76,16 +75,16 @@ impl AlignedInput {
 }
 
 #[inline(always)]
-fn stage_1(input: &mut [u64; KECCAK_WORDS], scratch_pad: &mut [u64; MEMORY_SIZE], a: (usize, usize), b: (usize, usize)) {
+fn stage_1<const M: usize>(input: &mut [u64; KECCAK_WORDS], scratch_pad: &mut [u64; M], a: (usize, usize), b: (usize, usize)) {
     for i in a.0..=a.1 {
         keccakp(input);
 
         let mut rand_int: u64 = 0;
         for j in b.0..=b.1 {
-            let pair_idx = (j + 1) % KECCAK_WORDS;
-            let pair_idx2 = (j + 2) % KECCAK_WORDS;
+            let pair_idx = j.wrapping_add(1) % KECCAK_WORDS;
+            let pair_idx2 = j.wrapping_add(2) % KECCAK_WORDS;
 
-            let target_idx = i * KECCAK_WORDS + j;
+            let target_idx = i.wrapping_mul(KECCAK_WORDS).wrapping_add(j);
             let a = input[j] ^ rand_int;
             // Branching
             let left = input[pair_idx];
@@ -108,46 +107,75 @@ fn stage_1(input: &mut [u64; KECCAK_WORDS], scratch_pad: &mut [u64; MEMORY_SIZE]
 // This function is used to hash the input using the generated scratch pad
 // NOTE: The scratchpad is completely overwritten in stage 1  and can be reused without any issues
 pub fn xelis_hash(input: &mut [u8; BYTES_ARRAY_INPUT], scratch_pad: &mut ScratchPad) -> Result<Hash, Error> {
+    xelis_hash_words(input, scratch_pad.as_mut_slice())
+}
+
+// Same as `xelis_hash` but using a scratchpad owned by the caller, such as a C buffer
+pub fn xelis_hash_words(input: &mut [u8; BYTES_ARRAY_INPUT], scratch_pad: &mut [u64; MEMORY_SIZE]) -> Result<Hash, Error> {
+    hash::<MEMORY_SIZE, SCRATCHPAD_ITERS>(input, scratch_pad)
+}
+
+// Swap the two u32 halves of each word on big-endian targets
+// so the u32 view of the scratchpad starts with the low half of each word
+#[inline(always)]
+fn swap_halves(words: &mut [u64]) {
+    if cfg!(target_endian = "big") {
+        for word in words.iter_mut() {
+            *word = word.rotate_left(32);
+        }
+    }
+}
+
+// Hash the input using a scratchpad of `M` u64s and `STAGE_3_ITERS` stage 3 iterations
+// `M` must be a power of two as the stage 3 addresses are masked with it,
+// and not a multiple of KECCAK_WORDS as the last stage 1 run fills its remainder
+fn hash<const M: usize, const STAGE_3_ITERS: usize>(input: &mut [u8; BYTES_ARRAY_INPUT], scratch_pad: &mut [u64; M]) -> Result<Hash, Error> {
+    const { assert!(M.is_power_of_two()) };
+    const { assert!(M % KECCAK_WORDS != 0) };
+
     let int_input: &mut [u64; KECCAK_WORDS] = bytemuck::try_from_bytes_mut(input)
         .map_err(|e| Error::CastError(e))?;
+    // The input bytes are the little-endian representation of the words
+    scratchpad::from_le_words(int_input);
 
     // stage 1
-    let scratch_pad = scratch_pad.as_mut_slice();
-    stage_1(int_input, scratch_pad, (0, STAGE_1_MAX - 1), (0, KECCAK_WORDS - 1));
-    stage_1(int_input, scratch_pad, (STAGE_1_MAX, STAGE_1_MAX), (0, 17));
+    let stage_1_max = M / KECCAK_WORDS;
+    stage_1(int_input, scratch_pad, (0, stage_1_max - 1), (0, KECCAK_WORDS - 1));
+    stage_1(int_input, scratch_pad, (stage_1_max, stage_1_max), (0, (M % KECCAK_WORDS) - 1));
 
     // stage 2
     let mut slots: [u32; SLOT_LENGTH] = [0; SLOT_LENGTH];
-    // this is equal to MEMORY_SIZE, just in u32 format
-    let small_pad: &mut [u32; MEMORY_SIZE * 2] = bytemuck::try_cast_slice_mut(scratch_pad)
-        .map_err(|e| Error::CastError(e))?
-        .try_into()
-        .map_err(|_| Error::FormatError)?;
+    // this is equal to M, just in u32 format
+    swap_halves(scratch_pad);
+    let small_pad: &mut [u32] = bytemuck::try_cast_slice_mut(scratch_pad)
+        .map_err(|e| Error::CastError(e))?;
 
-    slots.copy_from_slice(&small_pad[small_pad.len() - SLOT_LENGTH..]);
+    slots.copy_from_slice(&small_pad[(M * 2) - SLOT_LENGTH..]);
 
     let mut indices: [u16; SLOT_LENGTH] = [0; SLOT_LENGTH];
     for _ in 0..ITERS {
         for j in 0..small_pad.len() / SLOT_LENGTH {
+            let offset = j.wrapping_mul(SLOT_LENGTH);
+
             // Initialize indices and precompute the total sum of small pad
             let mut total_sum: u32 = 0;
             for k in 0..SLOT_LENGTH {
                 indices[k] = k as u16;
                 if slots[k] >> 31 == 0 {
-                    total_sum = total_sum.wrapping_add(small_pad[j * SLOT_LENGTH + k]);
+                    total_sum = total_sum.wrapping_add(small_pad[offset.wrapping_add(k)]);
                 } else {
-                    total_sum = total_sum.wrapping_sub(small_pad[j * SLOT_LENGTH + k]);
+                    total_sum = total_sum.wrapping_sub(small_pad[offset.wrapping_add(k)]);
                 }
             }
 
             for slot_idx in (0..SLOT_LENGTH).rev() {
-                let index_in_indices = (small_pad[j * SLOT_LENGTH + slot_idx] % (slot_idx as u32 + 1)) as usize;
+                let index_in_indices = (small_pad[offset.wrapping_add(slot_idx)] % (slot_idx as u32).wrapping_add(1)) as usize;
                 let index = indices[index_in_indices] as usize;
                 indices[index_in_indices] = indices[slot_idx];
 
                 let mut local_sum = total_sum;
                 let s1 = (slots[index] >> 31) as i32;
-                let pad_value = small_pad[j * SLOT_LENGTH + index];
+                let pad_value = small_pad[offset.wrapping_add(index)];
                 if s1 == 0 {
                     local_sum = local_sum.wrapping_sub(pad_value);
                 } else {
@@ -159,38 +187,43 @@ pub fn xelis_hash(input: &mut [u8; BYTES_ARRAY_INPUT], scratch_pad: &mut Scratch
 
                 // Update the total sum
                 let s2 = (slots[index] >> 31) as i32;
-                total_sum = total_sum.wrapping_sub(2u32.wrapping_mul(small_pad[(j * SLOT_LENGTH).wrapping_add(index)].wrapping_mul((-s1).wrapping_add(s2) as u32)));
+                total_sum = total_sum.wrapping_sub(2u32.wrapping_mul(pad_value.wrapping_mul(s1.wrapping_neg().wrapping_add(s2) as u32)));
             }
         }
     }
 
-    small_pad[(MEMORY_SIZE * 2) - SLOT_LENGTH..].copy_from_slice(&slots);
+    small_pad[(M * 2) - SLOT_LENGTH..].copy_from_slice(&slots);
+    swap_halves(scratch_pad);
 
     // stage 3
-    let key = GenericArray::from([0u8; 16]);
-    let mut block = GenericArray::from([0u8; 16]);
+    let key = [0u8; aes_round::BLOCK_SIZE];
+    let mut block = [0u8; aes_round::BLOCK_SIZE];
+
+    // Addresses are in [0, M): 0x7FFF and a shift of 15 bits for the default size
+    let addr_mask = (M - 1) as u64;
+    let addr_shift = M.trailing_zeros();
 
-    let mut addr_a = (scratch_pad[MEMORY_SIZE - 1] >> 15) & 0x7FFF;
-    let mut addr_b = scratch_pad[MEMORY_SIZE - 1] & 0x7FFF;
+    let mut addr_a = (scratch_pad[M - 1] >> addr_shift) & addr_mask;
+    let mut addr_b = scratch_pad[M - 1] & addr_mask;
 
     let mut mem_buffer_a: [u64; BUFFER_SIZE] = [0; BUFFER_SIZE];
     let mut mem_buffer_b: [u64; BUFFER_SIZE] = [0; BUFFER_SIZE];
 
     for i in 0..BUFFER_SIZE as u64 {
-        mem_buffer_a[i as usize] = scratch_pad[((addr_a + i) % MEMORY_SIZE as u64) as usize];
-        mem_buffer_b[i as usize] = scratch_pad[((addr_b + i) % MEMORY_SIZE as u64) as usize];
+        mem_buffer_a[i as usize] = scratch_pad[(addr_a.wrapping_add(i) % M as u64) as usize];
+        mem_buffer_b[i as usize] = scratch_pad[(addr_b.wrapping_add(i) % M as u64) as usize];
     }
 
     let mut final_result = [0; HASH_SIZE];
 
-    for i in 0..SCRATCHPAD_ITERS {
+    for i in 0..STAGE_3_ITERS {
         let mem_a = mem_buffer_a[i % BUFFER_SIZE];
         let mem_b = mem_buffer_b[i % BUFFER_SIZE];
 
         block[..8].copy_from_slice(&mem_b.to_le_bytes());
         block[8..].copy_from_slice(&mem_a.to_le_bytes());
 
-        aes::hazmat::cipher_round(&mut block, &key);
+        aes_round::cipher_round(&mut block, &key);
 
         let hash1 = u64::from_le_bytes(block[0..8].try_into().map_err(|_| Error::FormatError)?);
         let hash2 = mem_a ^ mem_b;
@@ -198,11 +231,11 @@ pub fn xelis_hash(input: &mut [u8; BYTES_ARRAY_INPUT], scratch_pad: &mut Scratch
         let mut result = !(hash1 ^ hash2);
 
         for j in 0..HASH_SIZE {
-            let a = mem_buffer_a[(j + i) % BUFFER_SIZE];
-            let b = mem_buffer_b[(j + i) % BUFFER_SIZE];
+            let a = mem_buffer_a[j.wrapping_add(i) % BUFFER_SIZE];
+            let b = mem_buffer_b[j.wrapping_add(i) % BUFFER_SIZE];
 
             // more branching
-            let v = match (result >> (j * 2)) & 0xf {
+            let v = match result.wrapping_shr((j as u32).wrapping_mul(2)) & 0xf {
                 0 => result.rotate_left(j as u32) ^ b,
                 1 => !(result.rotate_left(j as u32) ^ a),
                 2 => !(result ^ a),
@@ -225,16 +258,16 @@ pub fn xelis_hash(input: &mut [u8; BYTES_ARRAY_INPUT], scratch_pad: &mut Scratch
             result = v;
         }
 
-        addr_b = result & 0x7FFF;
+        addr_b = result & addr_mask;
         mem_buffer_a[i % BUFFER_SIZE] = result;
         mem_buffer_b[i % BUFFER_SIZE] = scratch_pad[addr_b as usize];
 
-        addr_a = (result >> 15) & 0x7FFF;
+        addr_a = (result >> addr_shift) & addr_mask;
         scratch_pad[addr_a as usize] = result;
 
-        let index = SCRATCHPAD_ITERS - i - 1;
+        let index = STAGE_3_ITERS.wrapping_sub(i).wrapping_sub(1);
         if index < 4 {
-            final_result[index * 8..(SCRATCHPAD_ITERS - i) * 8].copy_from_slice(&result.to_be_bytes());
+            final_result[index.wrapping_mul(8)..index.wrapping_add(1).wrapping_mul(8)].copy_from_slice(&result.to_be_bytes());
         }
     }
 
@@ -243,8 +276,22 @@ pub fn xelis_hash(input: &mut [u8; BYTES_ARRAY_INPUT], scratch_pad: &mut Scratch
 
 #[cfg(test)]
 mod tests {
+    use rand::{rngs::StdRng, RngCore, SeedableRng};
     use super::*;
 
+    // Reduced parameters, small enough to run under Miri
+    // `cargo miri test --target s390x-unknown-linux-gnu` also checks them on a big-endian target
+    const SMALL_MEMORY_SIZE: usize = 1024;
+    const SMALL_ITERS: usize = 100;
+
+    fn small_hash(input: &[u8], scratch_pad: &mut ScratchPadInternal<SMALL_MEMORY_SIZE>) -> Hash {
+        let mut aligned = AlignedInput::default();
+        let slice = aligned.as_mut_slice().unwrap();
+        slice[..input.len()].copy_from_slice(input);
+
+        hash::<SMALL_MEMORY_SIZE, SMALL_ITERS>(slice, scratch_pad.as_mut_slice()).unwrap()
+    }
+
     fn test_input(input: &mut [u8; BYTES_ARRAY_INPUT], expected_hash: Hash) {
         let mut scratch_pad = ScratchPad::default();
         let hash = xelis_hash(input, &mut scratch_pad).unwrap();
@@ -252,6 +299,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_zero_input() {
         let mut input = [0u8; 200];
         let expected_hash = [
@@ -264,6 +312,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_xelis_input() {
         let mut input = [0u8; BYTES_ARRAY_INPUT];
 
@@ -278,6 +327,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_scratch_pad() {
         let mut scratch_pad = ScratchPad::default();
         let mut input = AlignedInput::default();
@@ -291,9 +341,75 @@ mod tests {
         assert_eq!(hash, expected_hash);
     }
 
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_adversarial_inputs_no_panic() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        let mut scratch_pad = ScratchPadInternal::default();
+        let mut input = [0u64; KECCAK_WORDS];
+        for _ in 0..10_000 {
+            crate::scratchpad::fill_adversarial(&mut input, &mut rng);
+            small_hash(bytemuck::cast_slice(&input), &mut scratch_pad);
+        }
+    }
+
     #[test]
     fn test_bytes_alignment() {
         let alignment = std::mem::align_of::<Bytes8Alignment>();
         assert_eq!(alignment, 8);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_small_params() {
+        let mut scratch_pad = ScratchPadInternal::default();
+        let zero_hash = [
+            214, 134, 84, 148, 37, 110, 130, 105, 154, 194, 102, 54, 89, 176, 246, 72,
+            175, 58, 171, 202, 94, 180, 5, 248, 53, 171, 142, 197, 236, 193, 101, 243,
+        ];
+        let custom_hash = [
+            195, 162, 142, 109, 91, 246, 48, 77, 73, 0, 55, 228, 203, 172, 149, 157,
+            15, 98, 95, 144, 205, 77, 223, 193, 28, 127, 35, 126, 70, 253, 70, 187,
+        ];
+
+        assert_eq!(small_hash(&[0u8; BYTES_ARRAY_INPUT], &mut scratch_pad), zero_hash);
+        assert_eq!(small_hash(b"xelis-hashing-algorithm", &mut scratch_pad), custom_hash);
+        // Reused scratchpad
+        assert_eq!(small_hash(&[0u8; BYTES_ARRAY_INPUT], &mut scratch_pad), zero_hash);
+    }
+
+    #[test]
+    fn test_aligned_input() {
+        let mut input = AlignedInput::default();
+        assert_eq!(input.size(), BYTES_ARRAY_INPUT);
+        assert_eq!(input.as_mut_ptr() as usize % ALIGNMENT, 0);
+
+        let ptr = input.as_mut_ptr();
+        input.as_mut_slice().unwrap()[BYTES_ARRAY_INPUT - 1] = 0xFF;
+        assert_eq!(input.as_slice().unwrap().as_ptr(), ptr as *const u8);
+        assert_eq!(input.as_slice().unwrap()[BYTES_ARRAY_INPUT - 1], 0xFF);
+    }
+
+    #[test]
+    fn test_unaligned_input() {
+        let mut words = [0u64; KECCAK_WORDS + 1];
+        let bytes: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+        let input: &mut [u8; BYTES_ARRAY_INPUT] = (&mut bytes[1..BYTES_ARRAY_INPUT + 1]).try_into().unwrap();
+
+        let mut scratch_pad = ScratchPadInternal::<SMALL_MEMORY_SIZE>::default();
+        let result = hash::<SMALL_MEMORY_SIZE, SMALL_ITERS>(input, scratch_pad.as_mut_slice());
+        assert!(matches!(result, Err(Error::CastError(_))));
+    }
+
+    #[test]
+    fn test_small_params_reused_scratchpad() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        let mut scratch_pad = ScratchPadInternal::default();
+        let mut input = [0u8; BYTES_ARRAY_INPUT];
+
+        for _ in 0..4 {
+            rng.fill_bytes(&mut input);
+            let hash = small_hash(&input, &mut scratch_pad);
+            assert_eq!(hash, small_hash(&input, &mut ScratchPadInternal::default()));
+        }
+    }
+}
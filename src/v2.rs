@@ -1,4 +1,3 @@
-use aes::cipher::generic_array::GenericArray;
 use blake3::hash as blake3_hash;
 use chacha20::{
     cipher::{KeyIvInit, StreamCipher},
@@ -6,38 +5,38 @@ use chacha20::{
 };
 
 use crate::{
-    scratchpad::ScratchPad as ScratchPadInternal,
+    aes_round,
+    math,
+    observer::{NoopObserver, Observer, Op, Site},
+    scratchpad::{self, ScratchPad as ScratchPadInternal},
     Error,
     Hash,
     HASH_SIZE
 };
 
-#[cfg(feature = "tracker")]
-use crate::tracker::{OpsTracker, MemOp};
-
 // These are tweakable parameters
 // Memory size is the size of the scratch pad in u64s
 // In bytes, this is equal to ~ 440 kB
-const MEMORY_SIZE: usize = 429 * 128;
+pub const MEMORY_SIZE: usize = 429 * 128;
 
 // Scratchpad iterations in stage 3
-const SCRATCHPAD_ITERS: usize = 3;
+pub const SCRATCHPAD_ITERS: usize = 3;
 // Buffer size for stage 3 (inner loop iterations)
-const BUFFER_SIZE: usize = MEMORY_SIZE / 2;
+pub const BUFFER_SIZE: usize = MEMORY_SIZE / 2;
 
 // Stage 1 config
 const CHUNK_SIZE: usize = 32;
 const NONCE_SIZE: usize = 12;
-const MEMORY_SIZE_BYTES: usize = MEMORY_SIZE * 8;
+pub const MEMORY_SIZE_BYTES: usize = MEMORY_SIZE * 8;
 
 // Stage 3 AES key
-const KEY: [u8; 16] = *b"xelishash-pow-v2";
+const KEY: aes_round::Block = *b"xelishash-pow-v2";
 
 pub type ScratchPad = ScratchPadInternal<MEMORY_SIZE>;
 
 // Combine two u64 into a u128
 #[inline(always)]
-pub(crate) fn combine_u64(high: u64, low: u64) -> u128 {
+pub fn combine_u64(high: u64, low: u64) -> u128 {
     (high as u128) << 64 | low as u128
 }
 
@@ -45,8 +44,8 @@ pub(crate) fn combine_u64(high: u64, low: u64) -> u128 {
 // This stage is responsible for generating the scratch pad
 // The scratch pad is generated using ChaCha8 with a custom nonce
 // that is updated after each iteration
-pub(crate) fn stage_1<const M: usize, const OUTPUT_SIZE: usize>(input: &[u8], scratch_pad: &mut ScratchPadInternal<M>) -> Result<(), Error> {
-    let bytes = scratch_pad.as_mut_bytes::<OUTPUT_SIZE>()?;
+pub(crate) fn stage_1<const M: usize, const OUTPUT_SIZE: usize>(input: &[u8], scratch_pad: &mut [u64; M]) -> Result<(), Error> {
+    let bytes = scratchpad::as_mut_bytes::<OUTPUT_SIZE>(scratch_pad)?;
 
     // Reset the scratchpad to 0
     // This is done to ensure that the scratchpad is clean
@@ -66,7 +65,7 @@ pub(crate) fn stage_1<const M: usize, const OUTPUT_SIZE: usize>(input: &[u8], sc
         // Concatenate the input hash with the chunk
         let mut tmp = [0u8; HASH_SIZE * 2];
         tmp[0..HASH_SIZE].copy_from_slice(&input_hash);
-        tmp[HASH_SIZE..HASH_SIZE + chunk.len()].copy_from_slice(chunk);
+        tmp[HASH_SIZE..HASH_SIZE.wrapping_add(chunk.len())].copy_from_slice(chunk);
 
         // Hash it to not trust the input
         input_hash = blake3_hash(&tmp).into();
@@ -74,18 +73,23 @@ pub(crate) fn stage_1<const M: usize, const OUTPUT_SIZE: usize>(input: &[u8], sc
         let mut cipher = ChaCha8::new(&input_hash.into(), &nonce.into());
 
         // Calculate the remaining size and how much to generate this iteration
-        let remaining_output_size = OUTPUT_SIZE - output_offset;
+        let remaining_output_size = OUTPUT_SIZE.wrapping_sub(output_offset);
         // Remaining chunks
-        let chunks_left = num_chunks - chunk_index;
+        let chunks_left = num_chunks.wrapping_sub(chunk_index);
         let chunk_output_size = remaining_output_size / chunks_left;
         let current_output_size = remaining_output_size.min(chunk_output_size);
 
         // Apply the keystream to the output
-        let offset = chunk_index * current_output_size;
-        let part = &mut bytes[offset..offset+current_output_size];
+        // Depending on the input length, the part may not fit in the output
+        // or be too small to generate the next nonce: such input has no valid hash
+        let offset = chunk_index.wrapping_mul(current_output_size);
+        let part = offset.checked_add(current_output_size)
+            .filter(|_| current_output_size >= NONCE_SIZE)
+            .and_then(|end| bytes.get_mut(offset..end))
+            .ok_or(Error::InvalidInputLength(input.len()))?;
         cipher.apply_keystream(part);
 
-        output_offset += current_output_size;
+        output_offset = output_offset.wrapping_add(current_output_size);
 
         // Update the nonce with the last NONCE_SIZE bytes of temp_output
         let nonce_start = current_output_size.saturating_sub(NONCE_SIZE);
@@ -94,50 +98,104 @@ pub(crate) fn stage_1<const M: usize, const OUTPUT_SIZE: usize>(input: &[u8], sc
         nonce.copy_from_slice(&part[nonce_start..]);
     }
 
+    // The keystream bytes are the little-endian representation of the words
+    scratchpad::from_le_words(scratch_pad);
+
     Ok(())
 }
 
+// Compute the value of the selected branch in the stage 3 inner loop
+// Its expensive operations are reported to the observer
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]
+pub(crate) fn branch(branch_idx: u8, a: u64, b: u64, c: u64, result: u64, r: usize, i: usize, j: usize, observer: &mut impl Observer) -> u64 {
+    match branch_idx {
+        0 => c.rotate_left(i.wrapping_mul(j) as u32) ^ b,
+        1 => c.rotate_right(i.wrapping_mul(j) as u32) ^ a,
+        2 => a ^ b ^ c,
+        3 => a.wrapping_add(b).wrapping_mul(c),
+        4 => b.wrapping_sub(c).wrapping_mul(a),
+        5 => c.wrapping_sub(a).wrapping_add(b),
+        6 => a.wrapping_sub(b).wrapping_add(c),
+        7 => b.wrapping_mul(c).wrapping_add(a),
+        8 => c.wrapping_mul(a).wrapping_add(b),
+        9 => a.wrapping_mul(b).wrapping_mul(c),
+        10 => {
+            let t1 = combine_u64(a, b);
+            let t2 = (c | 1) as u128;
+            observer.on_op(Op::DivRem128, 1);
+            math::rem_u128_to_u64(t1, t2)
+        },
+        11 => {
+            let t1 = combine_u64(b, c);
+            let t2 = combine_u64(result.rotate_left(r as u32), a | 2);
+            observer.on_op(Op::DivRem128, 1);
+            math::rem_u128_to_u64(t1, t2)
+        },
+        12 => {
+            let t1 = combine_u64(c, a);
+            let t2 = (b | 4) as u128;
+            observer.on_op(Op::DivRem128, 1);
+            math::div_u128_to_u64(t1, t2)
+        },
+        13 => {
+            let t1 = combine_u64(result.rotate_left(r as u32), b);
+            let t2 = combine_u64(a, c | 8);
+            if t1 > t2 {
+                observer.on_op(Op::DivRem128, 1);
+                math::div_u128_to_u64(t1, t2)
+            } else {a^b}
+        },
+        14 => {
+            let t1 = combine_u64(b, a);
+            let t2 = c as u128;
+            observer.on_op(Op::Mul128, 1);
+            (t1.wrapping_mul(t2) >> 64) as u64
+        },
+        15 => {
+            let t1 = combine_u64(a, c);
+            let t2 = combine_u64(result.rotate_right(r as u32), b);
+            observer.on_op(Op::Mul128, 1);
+            (t1.wrapping_mul(t2) >> 64) as u64
+        },
+        _ => unreachable!(),
+    }
+}
+
 // Stage 3 of the hashing algorithm
 // This stage is responsible for hashing the scratch pad
 // Its goal is to have lot of random memory accesses
 // and some branching to make it hard to optimize on GPUs
 // it shouldn't be possible to parallelize this stage
-pub(crate) fn stage_3(scratch_pad: &mut [u64; MEMORY_SIZE], #[cfg(feature = "tracker")] tracker: &mut OpsTracker) -> Result<(), Error> {
-    let key = GenericArray::from(KEY);
-    let mut block = GenericArray::from([0u8; 16]);
-    let buffer_size = BUFFER_SIZE as u64;
+pub(crate) fn stage_3<const M: usize, const B: usize, const ITERS: usize>(scratch_pad: &mut [u64; M], observer: &mut impl Observer) -> Result<(), Error> {
+    let mut block = [0u8; aes_round::BLOCK_SIZE];
+    let buffer_size = B as u64;
 
     // Create two new slices for each half
-    let (mem_buffer_a, mem_buffer_b) = scratch_pad.as_mut_slice().split_at_mut(BUFFER_SIZE);
+    let (mem_buffer_a, mem_buffer_b) = scratch_pad.as_mut_slice().split_at_mut(B);
 
-    let mut addr_a = mem_buffer_b[BUFFER_SIZE-1];
-    let mut addr_b = mem_buffer_a[BUFFER_SIZE-1] >> 32;
-
-    #[cfg(feature = "tracker")]
-    {
-        tracker.add_mem_op(BUFFER_SIZE-1, MemOp::Read);
-        tracker.add_mem_op(MEMORY_SIZE-1, MemOp::Read);
-    }
+    observer.on_start();
+    let mut addr_a = mem_buffer_b[B-1];
+    let mut addr_b = mem_buffer_a[B-1] >> 32;
+    observer.on_mem_read_at(M-1, Site::Start);
+    observer.on_mem_read_at(B-1, Site::Start);
 
     let mut r: usize = 0;
 
-    for i in 0..SCRATCHPAD_ITERS {
+    for i in 0..ITERS {
         let index_a = (addr_a % buffer_size) as usize;
         let index_b = (addr_b % buffer_size) as usize;
 
         let mem_a = mem_buffer_a[index_a];
         let mem_b = mem_buffer_b[index_b];
-
-        #[cfg(feature = "tracker")]
-        {
-            tracker.add_mem_op(index_a, MemOp::Read);
-            tracker.add_mem_op(BUFFER_SIZE + index_b, MemOp::Read);
-        }
+        observer.on_mem_read_at(index_a, Site::IterationA);
+        observer.on_mem_read_at(B.wrapping_add(index_b), Site::IterationB);
 
         block[..8].copy_from_slice(&mem_b.to_le_bytes());
         block[8..].copy_from_slice(&mem_a.to_le_bytes());
 
-        aes::hazmat::cipher_round(&mut block, &key);
+        aes_round::cipher_round(&mut block, &KEY);
+        observer.on_op(Op::AesRound, 1);
 
         let hash1 = u64::from_le_bytes(block[0..8]
             .try_into()
@@ -146,134 +204,87 @@ pub(crate) fn stage_3(scratch_pad: &mut [u64; MEMORY_SIZE], #[cfg(feature = "tra
         let hash2 = mem_a ^ mem_b;
         let mut result = !(hash1 ^ hash2);
 
-        for j in 0..BUFFER_SIZE {
+        for j in 0..B {
+            observer.on_iteration(i, j);
+
             let index_a = (result % buffer_size) as usize;
             let index_b = (!result.rotate_right(r as u32) % buffer_size) as usize;
 
-            #[cfg(feature = "tracker")]
-            {
-                tracker.add_mem_op(index_a, MemOp::Read);
-                tracker.add_mem_op(BUFFER_SIZE + index_b, MemOp::Read);
-            }
-
             let a = mem_buffer_a[index_a];
             let b = mem_buffer_b[index_b];
+            observer.on_mem_read_at(index_a, Site::A);
+            observer.on_mem_read_at(B.wrapping_add(index_b), Site::B);
 
-            #[cfg(feature = "tracker")]
-            {
-                // This is the same index in scratchpad
-                tracker.add_mem_op(r, MemOp::Read);
-            }
-
-            let c = if r < BUFFER_SIZE {mem_buffer_a[r]} else {mem_buffer_b[r-BUFFER_SIZE]};
-            r = if r < MEMORY_SIZE-1 {r+1} else {0};
+            // This is the same index in scratchpad
+            let c = if r < B {mem_buffer_a[r]} else {mem_buffer_b[r.wrapping_sub(B)]};
+            observer.on_mem_read_at(r, Site::R);
+            r = if r < M-1 {r.wrapping_add(1)} else {0};
 
             let branch_idx = (result.rotate_left(c as u32) & 0xf) as u8;
-            #[cfg(feature = "tracker")]
-            {
-                tracker.add_branch(branch_idx);
-            }
+            observer.on_branch(branch_idx);
 
-            let v = result ^ match branch_idx {
-                0 => c.rotate_left(i.wrapping_mul(j) as u32) ^ b,
-                1 => c.rotate_right(i.wrapping_mul(j) as u32) ^ a,
-                2 => a ^ b ^ c,
-                3 => a.wrapping_add(b).wrapping_mul(c),
-                4 => b.wrapping_sub(c).wrapping_mul(a),
-                5 => c.wrapping_sub(a).wrapping_add(b),
-                6 => a.wrapping_sub(b).wrapping_add(c),
-                7 => b.wrapping_mul(c).wrapping_add(a),
-                8 => c.wrapping_mul(a).wrapping_add(b),
-                9 => a.wrapping_mul(b).wrapping_mul(c),
-                10 => {
-                    let t1 = combine_u64(a, b);
-                    let t2 = (c | 1) as u128;
-                    t1.wrapping_rem(t2) as u64
-                },
-                11 => {
-                    let t1 = combine_u64(b, c);
-                    let t2 = combine_u64(result.rotate_left(r as u32), a | 2);
-                    t1.wrapping_rem(t2) as u64
-                },
-                12 => {
-                    let t1 = combine_u64(c, a);
-                    let t2 = (b | 4) as u128;
-                    t1.wrapping_div(t2) as u64
-                },
-                13 => {
-                    let t1 = combine_u64(result.rotate_left(r as u32), b);
-                    let t2 = combine_u64(a, c | 8);
-                    if t1 > t2 {t1.wrapping_div(t2) as u64} else {a^b}
-                },
-                14 => {
-                    let t1 = combine_u64(b, a);
-                    let t2 = c as u128;
-                    (t1.wrapping_mul(t2) >> 64) as u64
-                },
-                15 => {
-                    let t1 = combine_u64(a, c);
-                    let t2 = combine_u64(result.rotate_right(r as u32), b);
-                    (t1.wrapping_mul(t2) >> 64) as u64
-                },
-                _ => unreachable!(),
-            };
+            let v = result ^ branch(branch_idx, a, b, c, result, r, i, j, observer);
 
             result = v.rotate_left(1);
 
-            #[cfg(feature = "tracker")]
-            {
-                tracker.add_mem_op(BUFFER_SIZE-j-1, MemOp::Write);
-                tracker.add_mem_op(BUFFER_SIZE+j, MemOp::Write);
-            }
-
-            let t = mem_buffer_a[BUFFER_SIZE-j-1] ^ result;
-            mem_buffer_a[BUFFER_SIZE-j-1] = t;
+            let index_t = B.wrapping_sub(j).wrapping_sub(1);
+            let t = mem_buffer_a[index_t] ^ result;
+            mem_buffer_a[index_t] = t;
             mem_buffer_b[j] ^= t.rotate_right(result as u32);
+            observer.on_mem_read_at(index_t, Site::T);
+            observer.on_mem_write_at(index_t, Site::T);
+            observer.on_mem_read_at(B.wrapping_add(j), Site::J);
+            observer.on_mem_write_at(B.wrapping_add(j), Site::J);
         }
         addr_a = result;
-        addr_b = isqrt(result);
+        // The Newton iteration first used here divided by zero for this result, as the Go port still does:
+        // there is no valid hash for this input and an error is returned
+        if result == u64::MAX {
+            return Err(Error::IsqrtOverflow);
+        }
+        addr_b = math::isqrt(result);
+        observer.on_op(Op::Isqrt, 1);
     }
+    observer.on_finish();
 
     Ok(())
 }
 
 // Stage 4 hash the whole scratchpad using Blake3 to prevent any shortcut in
 // the scratchpad computation
+// The words are hashed in little-endian, the scratchpad is left in this representation
 #[inline]
-pub(crate) fn stage_4(scratch_pad: &[u64]) -> Result<Hash, Error> {
+pub(crate) fn stage_4(scratch_pad: &mut [u64]) -> Result<Hash, Error> {
+    scratchpad::to_le_words(scratch_pad);
     let bytes: &[u8] = bytemuck::try_cast_slice(scratch_pad)
         .map_err(Error::CastError)?;
 
     Ok(blake3_hash(bytes).into())
 }
 
-fn isqrt(n: u64) -> u64 {
-    if n < 2 {
-        return n;
-    }
-
-    let mut x = n;
-    let mut y = (x.wrapping_add(1)) >> 1;
+// This function is used to hash the input using the generated scratch pad
+// NOTE: The scratchpad is completely overwritten in stage 1  and can be reused without any issues
+pub fn xelis_hash(input: &[u8], scratch_pad: &mut ScratchPad) -> Result<Hash, Error> {
+    xelis_hash_words(input, scratch_pad.as_mut_slice())
+}
 
-    while y < x {
-        x = y;
-        y = (x.wrapping_add(n.wrapping_div(x))) >> 1;
-    }
+// Same as `xelis_hash` but using a scratchpad owned by the caller, such as a C buffer
+pub fn xelis_hash_words(input: &[u8], scratch_pad: &mut [u64; MEMORY_SIZE]) -> Result<Hash, Error> {
+    hash_observed(input, scratch_pad, &mut NoopObserver)
+}
 
-    x
+// Same as `xelis_hash` but reporting the stage 3 execution to the observer
+pub fn xelis_hash_observed(input: &[u8], scratch_pad: &mut ScratchPad, observer: &mut impl Observer) -> Result<Hash, Error> {
+    hash_observed(input, scratch_pad.as_mut_slice(), observer)
 }
 
-// This function is used to hash the input using the generated scratch pad
-// NOTE: The scratchpad is completely overwritten in stage 1  and can be reused without any issues
-pub fn xelis_hash(input: &[u8], scratch_pad: &mut ScratchPad, #[cfg(feature = "tracker")] distribution: &mut OpsTracker) -> Result<Hash, Error> {
+fn hash_observed(input: &[u8], scratch_pad: &mut [u64; MEMORY_SIZE], observer: &mut impl Observer) -> Result<Hash, Error> {
     stage_1::<MEMORY_SIZE, MEMORY_SIZE_BYTES>(input, scratch_pad)?;
 
-    let scratch_pad = scratch_pad.as_mut_slice();
-
     // stage 2 got removed as it got completely optimized on GPUs
 
     // stage 3
-    stage_3(scratch_pad, #[cfg(feature = "tracker")] distribution)?;
+    stage_3::<MEMORY_SIZE, BUFFER_SIZE, SCRATCHPAD_ITERS>(scratch_pad, observer)?;
 
     // final stage 4
     stage_4(scratch_pad)
@@ -281,32 +292,49 @@ pub fn xelis_hash(input: &[u8], scratch_pad: &mut ScratchPad, #[cfg(feature = "t
 
 #[cfg(test)]
 mod tests {
-    use rand::{rngs::OsRng, RngCore};
+    use rand::{rngs::{OsRng, StdRng}, Rng, RngCore, SeedableRng};
     use std::time::Instant;
     use super::*;
 
+    // Reduced parameters, small enough to run under Miri
+    // `cargo miri test --target s390x-unknown-linux-gnu` also checks them on a big-endian target
+    const SMALL_MEMORY_SIZE: usize = 256;
+
+    fn small_hash(input: &[u8], scratch_pad: &mut ScratchPadInternal<SMALL_MEMORY_SIZE>) -> Result<Hash, Error> {
+        small_hash_observed(input, scratch_pad, &mut NoopObserver)
+    }
+
+    fn small_hash_observed(input: &[u8], scratch_pad: &mut ScratchPadInternal<SMALL_MEMORY_SIZE>, observer: &mut impl Observer) -> Result<Hash, Error> {
+        let scratch_pad = scratch_pad.as_mut_slice();
+        stage_1::<SMALL_MEMORY_SIZE, { SMALL_MEMORY_SIZE * 8 }>(input, scratch_pad)?;
+        stage_3::<SMALL_MEMORY_SIZE, { SMALL_MEMORY_SIZE / 2 }, SCRATCHPAD_ITERS>(scratch_pad, observer)?;
+        stage_4(scratch_pad)
+    }
+
     const ITERATIONS: usize = 1000;
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_reused_scratchpad() {
         let mut scratch_pad = ScratchPad::default();
         let mut input = [0u8; 112];
         OsRng.fill_bytes(&mut input);
 
         // Do a first hash
-        let expected_hash = xelis_hash(&input, &mut scratch_pad, #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap();
+        let expected_hash = xelis_hash(&input, &mut scratch_pad).unwrap();
 
         // Do a second hash with dirty scratch pad but same input
-        let hash = xelis_hash(&input, &mut scratch_pad, #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap();
+        let hash = xelis_hash(&input, &mut scratch_pad).unwrap();
         assert_eq!(hash, expected_hash);
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_zero_hash() {
         let mut scratch_pad = ScratchPad::default();
-        let mut input = [0u8; 112];
+        let input = [0u8; 112];
 
-        let hash = xelis_hash(&mut input, &mut scratch_pad, #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap();
+        let hash = xelis_hash(&input, &mut scratch_pad).unwrap();
         let expected_hash = [
             126, 219, 112, 240, 116, 133, 115, 144, 39, 40, 164,
             105, 30, 158, 45, 126, 64, 67, 238, 52, 200, 35,
@@ -317,6 +345,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_xelis_stages() {
         let mut input = [0u8; 112];
         OsRng.fill_bytes(&mut input);
@@ -325,13 +354,13 @@ mod tests {
         let instant = Instant::now();
         for i in 0..ITERATIONS {
             input[0] = i as u8;
-            std::hint::black_box(stage_1::<MEMORY_SIZE, MEMORY_SIZE_BYTES>(&mut input, &mut scratch_pad).unwrap());
+            std::hint::black_box(stage_1::<MEMORY_SIZE, MEMORY_SIZE_BYTES>(&input, scratch_pad.as_mut_slice())).unwrap();
         }
         println!("Stage 1 took: {} microseconds", instant.elapsed().as_micros() / ITERATIONS as u128);
 
         let instant = Instant::now();
         for _ in 0..ITERATIONS {
-            std::hint::black_box(stage_3(scratch_pad.as_mut_slice(), #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap());
+            std::hint::black_box(stage_3::<MEMORY_SIZE, BUFFER_SIZE, SCRATCHPAD_ITERS>(scratch_pad.as_mut_slice(), &mut NoopObserver)).unwrap();
         }
         println!("Stage 3 took: {} microseconds", instant.elapsed().as_micros() / ITERATIONS as u128);
 
@@ -343,6 +372,7 @@ mod tests {
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_verify_output() {
         let input = [
             172, 236, 108, 212, 181, 31, 109, 45, 44, 242, 54, 225, 143, 133,
@@ -356,7 +386,7 @@ mod tests {
         ];
 
         let mut scratch_pad = ScratchPad::default();
-        let hash = xelis_hash(&input, &mut scratch_pad, #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap();
+        let hash = xelis_hash(&input, &mut scratch_pad).unwrap();
 
         let expected_hash = [
             199, 114, 154, 28, 4, 164, 196, 178, 117, 17, 148,
@@ -367,18 +397,297 @@ mod tests {
         assert_eq!(hash, expected_hash);
     }
 
+    // Operands chosen to overflow in debug builds if any arithmetic isn't explicit
+    const ADVERSARIAL_VALUES: [u64; 10] = [0, 1, 2, 3, 8, 1 << 32, 1 << 63, u64::MAX - 8, u64::MAX - 1, u64::MAX];
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_branches_no_panic() {
+        let counters = [(0, 0, 0), (63, 1, 64), (MEMORY_SIZE - 1, SCRATCHPAD_ITERS - 1, BUFFER_SIZE - 1)];
+        for branch_idx in 0..16 {
+            for a in ADVERSARIAL_VALUES {
+                for b in ADVERSARIAL_VALUES {
+                    for c in ADVERSARIAL_VALUES {
+                        for result in ADVERSARIAL_VALUES {
+                            for (r, i, j) in counters {
+                                std::hint::black_box(branch(branch_idx, a, b, c, result, r, i, j, &mut NoopObserver));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..1_000_000 {
+            let (a, b, c, result) = (rng.gen(), rng.gen(), rng.gen(), rng.gen());
+            let r = rng.gen_range(0..MEMORY_SIZE);
+            let i = rng.gen_range(0..SCRATCHPAD_ITERS);
+            let j = rng.gen_range(0..BUFFER_SIZE);
+            for branch_idx in 0..16 {
+                std::hint::black_box(branch(branch_idx, a, b, c, result, r, i, j, &mut NoopObserver));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_input_lengths_no_panic() {
+        let mut scratch_pad = ScratchPad::default();
+        let lengths = [0, 1, 31, 32, 33]
+            .into_iter()
+            .chain((2..=64).map(|chunks| chunks * 32))
+            .chain([1000, 4096, 65_536, MEMORY_SIZE_BYTES, MEMORY_SIZE_BYTES * 4]);
+
+        for len in lengths {
+            let input = vec![0xFF; len];
+            match xelis_hash(&input, &mut scratch_pad) {
+                Ok(_) | Err(Error::InvalidInputLength(_)) => {},
+                Err(e) => panic!("unexpected error for length {}: {}", len, e),
+            }
+        }
+
+        // 5 chunks don't fit in the scratchpad
+        let res = xelis_hash(&[0u8; 150], &mut scratch_pad);
+        assert!(matches!(res, Err(Error::InvalidInputLength(150))));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_adversarial_inputs_no_panic() {
+        // Stage 3 alone on a tiny scratchpad is cheap enough to run a million times in debug
+        const TINY_MEMORY_SIZE: usize = 16;
+
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        let mut words = [0u64; TINY_MEMORY_SIZE];
+        for _ in 0..1_000_000 {
+            scratchpad::fill_adversarial(&mut words, &mut rng);
+            // Adversarial words can reach a 2^64 - 1 result, which has no valid hash
+            match stage_3::<TINY_MEMORY_SIZE, { TINY_MEMORY_SIZE / 2 }, SCRATCHPAD_ITERS>(&mut words, &mut NoopObserver) {
+                Ok(()) | Err(Error::IsqrtOverflow) => {},
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        // Full hashes of adversarial inputs
+        let mut scratch_pad = ScratchPadInternal::<SMALL_MEMORY_SIZE>::default();
+        let mut input = [0u64; 14];
+        for _ in 0..10_000 {
+            scratchpad::fill_adversarial(&mut input, &mut rng);
+            let input: &[u8] = bytemuck::cast_slice(&input);
+            small_hash(input, &mut scratch_pad).unwrap();
+        }
+    }
+
     #[test]
     #[cfg(feature = "tracker")]
     fn test_distribution() {
         let mut scratch_pad = ScratchPad::default();
         let mut input = [0u8; 112];
-        let mut distribution = OpsTracker::new(MEMORY_SIZE);
+        let mut distribution = crate::tracker::OpsTracker::new(MEMORY_SIZE);
         for _ in 0..ITERATIONS {
             OsRng.fill_bytes(&mut input);
-            let _ = xelis_hash(&input, &mut scratch_pad, &mut distribution).unwrap();
+            let _ = xelis_hash_observed(&input, &mut scratch_pad, &mut distribution).unwrap();
         }
 
         distribution.generate_branch_distribution("branch_v2.png").unwrap();
+        distribution.generate_transition_heatmap("transitions_v2.png").unwrap();
         distribution.generate_memory_usage_graph("memory_v2.png", 100).unwrap();
+
+        let report = crate::analysis::analyze_tracker(&distribution, crate::analysis::DEFAULT_ALPHA, crate::analysis::V2_KNOWN_FAILURES);
+        println!("{}", report);
+        println!("Branch entropy rate: {:.4} bits", distribution.branch_entropy_rate());
+        assert!(report.passed(), "{}", report);
+    }
+
+    #[test]
+    fn test_small_params() {
+        let mut scratch_pad = ScratchPadInternal::default();
+        let zero_hash = [
+            28, 197, 201, 156, 207, 121, 38, 202, 228, 111, 248, 79, 237, 50, 89, 42,
+            22, 28, 96, 164, 136, 131, 29, 51, 9, 109, 28, 250, 75, 11, 3, 125,
+        ];
+        let custom_hash = [
+            181, 63, 114, 16, 218, 62, 159, 88, 119, 87, 228, 62, 54, 84, 245, 132,
+            15, 16, 134, 137, 38, 219, 183, 102, 239, 43, 88, 85, 80, 46, 98, 167,
+        ];
+
+        assert_eq!(small_hash(&[0u8; 112], &mut scratch_pad).unwrap(), zero_hash);
+        assert_eq!(small_hash(b"xelis-hashing-algorithm", &mut scratch_pad).unwrap(), custom_hash);
+        // Reused scratchpad
+        assert_eq!(small_hash(&[0u8; 112], &mut scratch_pad).unwrap(), zero_hash);
+    }
+
+    #[test]
+    fn test_small_params_reused_scratchpad() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        let mut scratch_pad = ScratchPadInternal::default();
+        let mut input = [0u8; 112];
+
+        for _ in 0..4 {
+            rng.fill_bytes(&mut input);
+            let hash = small_hash(&input, &mut scratch_pad).unwrap();
+            assert_eq!(hash, small_hash(&input, &mut ScratchPadInternal::default()).unwrap());
+        }
+    }
+
+    struct CountingObserver {
+        memory_size: usize,
+        iterations: usize,
+        starts: usize,
+        finishes: usize,
+        branches: usize,
+        reads: usize,
+        writes: usize,
+    }
+
+    impl CountingObserver {
+        fn new(memory_size: usize) -> Self {
+            Self { memory_size, iterations: 0, starts: 0, finishes: 0, branches: 0, reads: 0, writes: 0 }
+        }
+    }
+
+    impl Observer for CountingObserver {
+        fn on_iteration(&mut self, i: usize, j: usize) {
+            let buffer_size = self.memory_size / 2;
+            assert_eq!((i, j), (self.iterations / buffer_size, self.iterations % buffer_size));
+            self.iterations += 1;
+        }
+
+        fn on_branch(&mut self, branch: u8) {
+            assert!(branch < 16);
+            self.branches += 1;
+        }
+
+        fn on_mem_read(&mut self, index: usize) {
+            assert!(index < self.memory_size);
+            self.reads += 1;
+        }
+
+        fn on_mem_write(&mut self, index: usize) {
+            assert!(index < self.memory_size);
+            self.writes += 1;
+        }
+
+        fn on_start(&mut self) {
+            self.starts += 1;
+        }
+
+        fn on_finish(&mut self) {
+            self.finishes += 1;
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        const B: usize = SMALL_MEMORY_SIZE / 2;
+
+        let mut scratch_pad = ScratchPadInternal::default();
+        let mut observer = CountingObserver::new(SMALL_MEMORY_SIZE);
+        let hash = small_hash_observed(&[0u8; 112], &mut scratch_pad, &mut observer).unwrap();
+        assert_eq!(hash, small_hash(&[0u8; 112], &mut scratch_pad).unwrap());
+
+        assert_eq!(observer.iterations, SCRATCHPAD_ITERS * B);
+        assert_eq!(observer.starts, 1);
+        assert_eq!(observer.finishes, 1);
+        assert_eq!(observer.branches, SCRATCHPAD_ITERS * B);
+        assert_eq!(observer.reads, 2 + SCRATCHPAD_ITERS * (2 + 5 * B));
+        assert_eq!(observer.writes, SCRATCHPAD_ITERS * 2 * B);
+    }
+
+    #[test]
+    #[cfg(feature = "tracker-core")]
+    fn test_locality() {
+        const B: usize = SMALL_MEMORY_SIZE / 2;
+        let (reads, writes) = (2 + SCRATCHPAD_ITERS * (2 + 5 * B), SCRATCHPAD_ITERS * 2 * B);
+
+        let mut tracker = crate::tracker::OpsTracker::new(SMALL_MEMORY_SIZE).with_access_log();
+        small_hash_observed(&[0u8; 112], &mut ScratchPadInternal::default(), &mut tracker).unwrap();
+
+        let locality = tracker.locality().unwrap();
+        assert_eq!(locality.get_accesses() as usize, reads + writes);
+        assert_eq!(locality.get_reads_before_write() + locality.get_read_after_write().values().sum::<u64>(), reads as u64);
+        assert!(locality.get_cold_accesses() as usize <= SMALL_MEMORY_SIZE);
     }
-}
\ No newline at end of file
+
+    #[test]
+    #[cfg(feature = "tracker-core")]
+    fn test_warp_divergence() {
+        let mut scratch_pad = ScratchPadInternal::default();
+        crate::tracker::test_helpers::check_warp_divergence(|input, simulator| small_hash_observed(input, &mut scratch_pad, simulator));
+    }
+
+    #[test]
+    #[cfg(feature = "tracker-core")]
+    fn test_coalescing() {
+        use crate::tracker::{coalescing::CoalescingSimulator, test_helpers::hash_warps, MemOp};
+
+        let mut scratch_pad = ScratchPadInternal::default();
+        let mut simulator = CoalescingSimulator::new(32);
+        hash_warps(&mut simulator, |input, simulator| small_hash_observed(input, &mut scratch_pad, simulator));
+
+        let report = simulator.report();
+        assert_eq!(report.sites.len(), 10);
+        // All the lanes access the same r
+        assert_eq!(report.site(Site::R, MemOp::Read).unwrap().transactions_per_request(), [1.0; 3]);
+        // 32 random words of a 64 segments scratchpad are in 25 segments on average
+        assert!(report.site(Site::A, MemOp::Read).unwrap().transactions_per_request()[0] > 20.0, "{}", report);
+        // All the lanes access the same j
+        assert_eq!(report.site(Site::J, MemOp::Write).unwrap().transactions_per_request(), [1.0; 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "tracker-core")]
+    fn test_op_counts() {
+        use crate::tracker::cost::{CostCounter, LatencyTable, Version};
+
+        const B: u64 = SMALL_MEMORY_SIZE as u64 / 2;
+        const ITERS: u64 = SCRATCHPAD_ITERS as u64;
+
+        // Only the random reads cost a cycle
+        let mut counter = CostCounter::new(Version::V2, vec![LatencyTable { random_read: 1, ..Default::default() }]);
+        small_hash_observed(&[0u8; 112], &mut ScratchPadInternal::default(), &mut counter).unwrap();
+
+        let hash = &counter.get_hashes()[0];
+        let ops = &hash.ops;
+        assert_eq!(ops.aes_rounds, ITERS);
+        assert_eq!(ops.isqrt, ITERS);
+        assert_eq!(ops.modular_power_steps, 0);
+        assert_eq!(ops.random_reads, 2 * ITERS + 2 * ITERS * B);
+        assert_eq!(ops.sequential_reads, 2 + 3 * ITERS * B);
+        assert_eq!((ops.random_writes, ops.sequential_writes), (0, 2 * ITERS * B));
+        // A single read at the start of each scratchpad iteration and of each inner iteration
+        assert_eq!(hash.critical_path, [ITERS * (1 + B)]);
+    }
+
+    #[test]
+    fn test_isqrt_overflow() {
+        struct Branches(Vec<u8>);
+
+        impl Observer for Branches {
+            fn on_branch(&mut self, branch: u8) {
+                self.0.push(branch);
+            }
+        }
+
+        // Found by a seeded search over scratchpads [p, 0, q, !p]: the first iteration writes its
+        // result to mem_buffer_a[1], which the second one reads back as c. It takes the branch 2
+        // and reads p and !p, so a ^ b ^ c ^ result is 2^64 - 1: the Newton isqrt previously used,
+        // like the one of the Go port, divides by zero on it
+        let p = 0x91c1391e71debacb;
+        let mut scratch_pad = [p, 0, 0x87d478812d451f15, !p];
+        let mut branches = Branches(Vec::new());
+        assert!(matches!(stage_3::<4, 2, 1>(&mut scratch_pad, &mut branches), Err(Error::IsqrtOverflow)));
+        assert_eq!(branches.0.last(), Some(&2));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_observed_hash() {
+        let mut scratch_pad = ScratchPad::default();
+        let input = [0u8; 112];
+        let expected = xelis_hash(&input, &mut scratch_pad).unwrap();
+        assert_eq!(xelis_hash_observed(&input, &mut scratch_pad, &mut NoopObserver).unwrap(), expected);
+        assert_eq!(xelis_hash_observed(&input, &mut scratch_pad, &mut CountingObserver::new(MEMORY_SIZE)).unwrap(), expected);
+    }
+}
@@ -1,19 +1,23 @@
-use aes::cipher::generic_array::GenericArray;
-use crate::{v2, Error, Hash, scratchpad::ScratchPad as ScratchPadInternal};
-
-#[cfg(feature = "tracker")]
-use crate::tracker::*;
+use crate::{
+    v2,
+    aes_round,
+    math,
+    observer::{NoopObserver, Observer, Op, Site},
+    Error,
+    Hash,
+    scratchpad::ScratchPad as ScratchPadInternal
+};
 
 // These are tweakable parameters
 // Memory size is the size of the scratch pad in u64s
 // In bytes, this is equal to ~ 544 kB
-const MEMORY_SIZE: usize = 531 * 128;
-const MEMORY_SIZE_BYTES: usize = MEMORY_SIZE * 8;
-const SCRATCHPAD_ITERS: usize = 2;
-const BUFFER_SIZE: usize = MEMORY_SIZE / 2;
+pub const MEMORY_SIZE: usize = 531 * 128;
+pub const MEMORY_SIZE_BYTES: usize = MEMORY_SIZE * 8;
+pub const SCRATCHPAD_ITERS: usize = 2;
+pub const BUFFER_SIZE: usize = MEMORY_SIZE / 2;
 
 // Stage 3 AES key
-const KEY: [u8; 16] = *b"xelishash-pow-v3";
+const KEY: aes_round::Block = *b"xelishash-pow-v3";
 
 pub type ScratchPad = ScratchPadInternal<MEMORY_SIZE>;
 
@@ -32,7 +36,13 @@ const fn murmurhash3(mut seed: u64) -> u64 {
 }
 
 #[inline(always)]
-pub fn map_index(mut x: u64) -> usize {
+pub fn map_index(x: u64) -> usize {
+    map_index_sized::<BUFFER_SIZE>(x)
+}
+
+// Map the value to an index of a buffer of `B` u64s
+#[inline(always)]
+fn map_index_sized<const B: usize>(mut x: u64) -> usize {
 	/* MurmurHash3-like finalizer + multiply-high reduction.
 	* The finalizer avalanches the input seed; the mulhi step maps
 	* uniformly into [0, BUFSIZE) with minimal modulo bias.
@@ -40,7 +50,7 @@ pub fn map_index(mut x: u64) -> usize {
     x ^= x >> 33;
     x = x.wrapping_mul(0xff51afd7ed558ccd);
 
-    ((x as u128) * (BUFFER_SIZE as u128) >> 64) as usize
+    ((x as u128).wrapping_mul(B as u128) >> 64) as usize
 }
 
 #[inline(always)]
@@ -49,79 +59,167 @@ pub fn pick_half(seed: u64) -> bool {
     (murmurhash3(seed) & (1u64 << 58)) != 0
 }
 
+// Lower bound from which the original floating-point
+// isqrt overflowed while correcting its approximation
+// This is (2^32 - 1)^2, where isqrt returned 2^32
+const ISQRT_OVERFLOW_LOW: u64 = 0xFFFF_FFFE_0000_0001;
+// Lower bound from which the f64 conversion is rounded to 2^64
+// and the approximation overflowed twice, isqrt returned 2^32 + 1
+const ISQRT_OVERFLOW_HIGH: u64 = 0xFFFF_FFFF_FFFF_FC00;
+
+// Integer square root as defined by the v3 consensus
+// It was first implemented using a f64 approximation followed by a correction step
+// that wrapped for n >= (2^32 - 1)^2 (also in the C and Go ports).
+// Below that bound, the result is the exact integer square root.
+// The wrapped results for the highest values are part of the consensus and are kept.
 #[inline(always)]
 pub fn isqrt(n: u64) -> u64 {
-    if n < 2 {
-        return n;
-    }
-
-    // Compute floating-point square root as an approximation
-    let approx = (n as f64).sqrt() as u64;
-
-    // Verify and adjust if necessary
-    if approx * approx > n {
-        approx - 1
-    } else if (approx + 1) * (approx + 1) <= n {
-        approx + 1
+    if n >= ISQRT_OVERFLOW_HIGH {
+        (1 << 32) + 1
+    } else if n >= ISQRT_OVERFLOW_LOW {
+        1 << 32
     } else {
-        approx
+        math::isqrt(n)
     }
 }
 
-const fn modular_power(mut base: u64, mut exp: u64, mod_: u64) -> u64 {
-    let mut result: u64 = 1;
-    // Ensure base is within the range of mod
-    base %= mod_;
+// Compute the addresses used by the next stage 3 iteration
+// `result` is used as the modulus of the modular power:
+// if it is zero, there is no valid hash for this input and an error is returned
+#[inline(always)]
+fn next_addresses(addr_a: u64, addr_b: u64, result: u64, r: usize, observer: &mut impl Observer) -> Result<(u64, u64), Error> {
+    let addr_a = math::modular_power(addr_a, addr_b, result)
+        .ok_or(Error::ZeroModulus)?;
+    // One step per bit of the exponent
+    observer.on_op(Op::ModularPowerStep, (u64::BITS - addr_b.leading_zeros()) as u64);
+    let addr_b = isqrt(result).wrapping_mul((r as u64).wrapping_add(1)).wrapping_mul(isqrt(addr_a));
+    observer.on_op(Op::Isqrt, 2);
+
+    Ok((addr_a, addr_b))
+}
 
-    while exp > 0 {
-        // If exp is odd, multiply base with result
-        if exp & 1 == 1 {
-            result = ((result as u128 * base as u128) % mod_ as u128) as u64;
+// Compute the value of the selected branch in the stage 3 inner loop
+// Its expensive operations are reported to the observer
+#[allow(clippy::too_many_arguments)]
+#[inline(always)]
+pub(crate) fn branch(branch_idx: u8, a: u64, b: u64, c: u64, result: u64, r: usize, i: usize, j: usize, observer: &mut impl Observer) -> u64 {
+    match branch_idx {
+        // combine_u64((a + i), isqrt(b + j)) % (murmurhash3(c ^ result ^ i ^ j) | 1)
+        0 => {
+            let t1 = v2::combine_u64(
+                a.wrapping_add(i as u64),
+                isqrt(b.wrapping_add(j as u64)),
+            );
+            let denom = murmurhash3(c ^ result ^ i as u64 ^ j as u64) | 1;
+            observer.on_op(Op::Isqrt, 1);
+            observer.on_op(Op::DivRem128, 1);
+            math::rem_u128_to_u64(t1, denom as u128)
         }
-
-        // Square the base and reduce by mod
-        base = ((base as u128 * base as u128) % mod_ as u128) as u64;
-        exp /= 2;
+        // ROTL((c + i) % isqrt(b | 2), i + j) * isqrt(a + j)
+        1 => {
+            let t1 = c.wrapping_add(i as u64).wrapping_rem(isqrt(b | 2));
+            let t2 = t1.rotate_left((i.wrapping_add(j)) as u32);
+            let t3 = isqrt(a.wrapping_add(j as u64));
+            observer.on_op(Op::Isqrt, 2);
+            observer.on_op(Op::DivRem64, 1);
+            t2.wrapping_mul(t3)
+        }
+        // (isqrt(a + i) * isqrt(c + j)) ^ (b + i + j)
+        2 => {
+            let t1 = isqrt(a.wrapping_add(i as u64));
+            let t2 = isqrt(c.wrapping_add(j as u64));
+            let t3 = t1.wrapping_mul(t2);
+            observer.on_op(Op::Isqrt, 2);
+            t3 ^ b.wrapping_add(i as u64).wrapping_add(j as u64)
+        }
+        // (a + b) * c
+        3 => a.wrapping_add(b).wrapping_mul(c),
+        // (b - c) * a
+        4 => b.wrapping_sub(c).wrapping_mul(a),
+        // c - a + b
+        5 => c.wrapping_sub(a).wrapping_add(b),
+        // a - b + c
+        6 => a.wrapping_sub(b).wrapping_add(c),
+        // b * c + a
+        7 => b.wrapping_mul(c).wrapping_add(a),
+        // c * a + b
+        8 => c.wrapping_mul(a).wrapping_add(b),
+        // a * b * c
+        9 => a.wrapping_mul(b).wrapping_mul(c),
+        10 => {
+            let t1 = v2::combine_u64(a, b);
+            let t2 = (c | 1) as u128;
+            observer.on_op(Op::DivRem128, 1);
+            math::rem_u128_to_u64(t1, t2)
+        },
+        11 => {
+            let t1 = v2::combine_u64(b, c);
+            let t2 = v2::combine_u64(result.rotate_left(r as u32), a | 2);
+            if t2 > t1 { c } else {
+                observer.on_op(Op::DivRem128, 1);
+                math::rem_u128_to_u64(t1, t2)
+            }
+        },
+        12 => {
+            let t1 = v2::combine_u64(c, a);
+            let t2 = (b | 4) as u128;
+            observer.on_op(Op::DivRem128, 1);
+            math::div_u128_to_u64(t1, t2)
+        },
+        13 => {
+            let t1 = v2::combine_u64(result.rotate_left(r as u32), b);
+            let t2 = v2::combine_u64(a, c | 8);
+            if t1 > t2 {
+                observer.on_op(Op::DivRem128, 1);
+                math::div_u128_to_u64(t1, t2)
+            } else {a^b}
+        },
+        14 => {
+            let t1 = v2::combine_u64(b, a);
+            let t2 = c as u128;
+            observer.on_op(Op::Mul128, 1);
+            (t1.wrapping_mul(t2) >> 64) as u64
+        },
+        15 => {
+            let t1 = v2::combine_u64(a, c);
+            let t2 = v2::combine_u64(result.rotate_right(r as u32), b);
+            observer.on_op(Op::Mul128, 1);
+            (t1.wrapping_mul(t2) >> 64) as u64
+        },
+        _ => unreachable!(),
     }
-
-    result
 }
 
-pub(crate) fn stage_3(scratch_pad: &mut [u64; MEMORY_SIZE], #[cfg(feature = "tracker")] tracker: &mut OpsTracker) -> Result<(), Error> {
-    let key = GenericArray::from(KEY);
-    let mut block = GenericArray::from([0u8; 16]);
+pub(crate) fn stage_3<const M: usize, const B: usize, const ITERS: usize>(scratch_pad: &mut [u64; M], observer: &mut impl Observer) -> Result<(), Error> {
+    let mut block = [0u8; aes_round::BLOCK_SIZE];
 
     // Create two new slices for each half
-    let (mem_buffer_a, mem_buffer_b) = scratch_pad.as_mut_slice().split_at_mut(BUFFER_SIZE);
-
-    let mut addr_a = mem_buffer_b[BUFFER_SIZE-1];
-    let mut addr_b = mem_buffer_a[BUFFER_SIZE-1] >> 32;
+    let (mem_buffer_a, mem_buffer_b) = scratch_pad.as_mut_slice().split_at_mut(B);
 
-    #[cfg(feature = "tracker")]
-    {
-        tracker.add_mem_op(BUFFER_SIZE-1, MemOp::Read);
-        tracker.add_mem_op(MEMORY_SIZE-1, MemOp::Read);
-    }
+    observer.on_start();
+    let mut addr_a = mem_buffer_b[B-1];
+    let mut addr_b = mem_buffer_a[B-1] >> 32;
+    observer.on_mem_read_at(M-1, Site::Start);
+    observer.on_mem_read_at(B-1, Site::Start);
 
     let mut r: usize = 0;
 
-    for i in 0..SCRATCHPAD_ITERS {
-        let index_a = map_index(addr_a);
+    for i in 0..ITERS {
+        let index_a = map_index_sized::<B>(addr_a);
         let mem_a = mem_buffer_a[index_a];
+        observer.on_op(Op::Mul128, 1);
+        observer.on_mem_read_at(index_a, Site::IterationA);
 
-        let index_b = map_index(mem_a ^ addr_b);
+        let index_b = map_index_sized::<B>(mem_a ^ addr_b);
         let mem_b = mem_buffer_b[index_b];
-
-        #[cfg(feature = "tracker")]
-        {
-            tracker.add_mem_op(index_a, MemOp::Read);
-            tracker.add_mem_op(BUFFER_SIZE + index_b, MemOp::Read);
-        }
+        observer.on_op(Op::Mul128, 1);
+        observer.on_mem_read_at(B.wrapping_add(index_b), Site::IterationB);
 
         block[..8].copy_from_slice(&mem_b.to_le_bytes());
         block[8..].copy_from_slice(&mem_a.to_le_bytes());
 
-        aes::hazmat::cipher_round(&mut block, &key);
+        aes_round::cipher_round(&mut block, &KEY);
+        observer.on_op(Op::AesRound, 1);
 
         let hash1 = u64::from_le_bytes(block[..8]
             .try_into()
@@ -133,154 +231,85 @@ pub(crate) fn stage_3(scratch_pad: &mut [u64; MEMORY_SIZE], #[cfg(feature = "tra
 
         let mut result = !(hash1 ^ hash2);
 
-        for j in 0..BUFFER_SIZE {
-            let index_a = map_index(result);
+        for j in 0..B {
+            observer.on_iteration(i, j);
+
+            let index_a = map_index_sized::<B>(result);
             let a = mem_buffer_a[index_a];      
+            observer.on_op(Op::Mul128, 1);
+            observer.on_mem_read_at(index_a, Site::A);
 
-            let index_b = map_index(a ^ !result.rotate_right(r as u32));
+            let index_b = map_index_sized::<B>(a ^ !result.rotate_right(r as u32));
             let b = mem_buffer_b[index_b];
+            observer.on_op(Op::Mul128, 1);
+            observer.on_mem_read_at(B.wrapping_add(index_b), Site::B);
 
-            #[cfg(feature = "tracker")]
-            {
-                tracker.add_mem_op(index_a, MemOp::Read);
-                tracker.add_mem_op(BUFFER_SIZE + index_b, MemOp::Read);
-
-                // This is the same index in scratchpad
-                tracker.add_mem_op(r, MemOp::Read);
-            }
-
-            let c = if r < BUFFER_SIZE {
+            // This is the same index in scratchpad
+            observer.on_mem_read_at(r, Site::R);
+            let c = if r < B {
                 mem_buffer_a[r]
             } else {
-                mem_buffer_b[r-BUFFER_SIZE]
+                mem_buffer_b[r.wrapping_sub(B)]
             };
-            r = if r < MEMORY_SIZE - 1 {
-                r + 1
+            r = if r < M - 1 {
+                r.wrapping_add(1)
             } else {
                 0
             };
 
             let branch_idx = (result.rotate_left(c as u32) & 0xf) as u8;
-            #[cfg(feature = "tracker")]
-            {
-                tracker.add_branch(branch_idx);
-            }
+            observer.on_branch(branch_idx);
 
-            let v = match branch_idx {
-                // combine_u64((a + i), isqrt(b + j)) % (murmurhash3(c ^ result ^ i ^ j) | 1)
-                0 => {
-                    let t1 = v2::combine_u64(
-                        a.wrapping_add(i as u64),
-                        isqrt(b.wrapping_add(j as u64)),
-                    );
-                    let denom = murmurhash3(c ^ result ^ i as u64 ^ j as u64) | 1;
-                    (t1 % (denom as u128)) as u64
-                }
-                // ROTL((c + i) % isqrt(b | 2), i + j) * isqrt(a + j)
-                1 => {
-                    let t1 = c.wrapping_add(i as u64).wrapping_rem(isqrt(b | 2));
-                    let t2 = t1.rotate_left((i.wrapping_add(j)) as u32);
-                    let t3 = isqrt(a.wrapping_add(j as u64));
-                    t2.wrapping_mul(t3)
-                }
-                // (isqrt(a + i) * isqrt(c + j)) ^ (b + i + j)
-                2 => {
-                    let t1 = isqrt(a.wrapping_add(i as u64));
-                    let t2 = isqrt(c.wrapping_add(j as u64));
-                    let t3 = t1.wrapping_mul(t2);
-                    t3 ^ b.wrapping_add(i as u64).wrapping_add(j as u64)
-                }
-                // (a + b) * c
-                3 => a.wrapping_add(b).wrapping_mul(c),
-                // (b - c) * a
-                4 => b.wrapping_sub(c).wrapping_mul(a),
-                // c - a + b
-                5 => c.wrapping_sub(a).wrapping_add(b),
-                // a - b + c
-                6 => a.wrapping_sub(b).wrapping_add(c),
-                // b * c + a
-                7 => b.wrapping_mul(c).wrapping_add(a),
-                // c * a + b
-                8 => c.wrapping_mul(a).wrapping_add(b),
-                // a * b * c
-                9 => a.wrapping_mul(b).wrapping_mul(c),
-                10 => {
-                    let t1 = v2::combine_u64(a, b);
-                    let t2 = (c | 1) as u128;
-                    t1.wrapping_rem(t2) as u64
-                },
-                11 => {
-                    let t1 = v2::combine_u64(b, c);
-                    let t2 = v2::combine_u64(result.rotate_left(r as u32), a | 2);
-                    if t2 > t1 { c } else { t1.wrapping_rem(t2) as u64 }
-                },
-                12 => {
-                    let t1 = v2::combine_u64(c, a);
-                    let t2 = (b | 4) as u128;
-                    t1.wrapping_div(t2) as u64
-                },
-                13 => {
-                    let t1 = v2::combine_u64(result.rotate_left(r as u32), b);
-                    let t2 = v2::combine_u64(a, c | 8);
-                    if t1 > t2 {t1.wrapping_div(t2) as u64} else {a^b}
-                },
-                14 => {
-                    let t1 = v2::combine_u64(b, a);
-                    let t2 = c as u128;
-                    (t1.wrapping_mul(t2) >> 64) as u64
-                },
-                15 => {
-                    let t1 = v2::combine_u64(a, c);
-                    let t2 = v2::combine_u64(result.rotate_right(r as u32), b);
-                    (t1.wrapping_mul(t2) >> 64) as u64
-                },
-                _ => unreachable!(),
-            };
+            let v = branch(branch_idx, a, b, c, result, r, i, j, observer);
 
             let seed = v ^ result;
             result = seed.rotate_left(r as u32);
 
             let use_buffer_b = pick_half(v);
-            let index_t = map_index(seed);
+            let index_t = map_index_sized::<B>(seed);
             let t = if use_buffer_b { mem_buffer_b[index_t] } else { mem_buffer_a[index_t] } ^ result;
+            observer.on_op(Op::Mul128, 1);
+            observer.on_mem_read_at(if use_buffer_b { B.wrapping_add(index_t) } else { index_t }, Site::T);
 
-            let index_a = map_index(t ^ result ^ 0x9e3779b97f4a7c15);
-            let index_b = map_index(index_a as u64 ^ !result ^ 0xd2b74407b1ce6e93);
+            let index_a = map_index_sized::<B>(t ^ result ^ 0x9e3779b97f4a7c15);
+            let index_b = map_index_sized::<B>(index_a as u64 ^ !result ^ 0xd2b74407b1ce6e93);
 
+            // mem_buffer_a[index_a] and mem_buffer_b[index_b] are read and written
             let a = std::mem::replace(&mut mem_buffer_a[index_a], t);
             mem_buffer_b[index_b] ^= a ^ t.rotate_right(i.wrapping_add(j) as u32);
-
-            #[cfg(feature = "tracker")]
-            {
-                if use_buffer_b {
-                    tracker.add_mem_op(BUFFER_SIZE + index_t, MemOp::Read);
-                } else {
-                    tracker.add_mem_op(index_t, MemOp::Read);
-                }
-
-                // mem_buffer_a[index_a] and mem_buffer_b[index_b] are written
-                tracker.add_mem_op(index_a, MemOp::Read);
-                tracker.add_mem_op(index_a, MemOp::Write);
-
-                tracker.add_mem_op(BUFFER_SIZE + index_b, MemOp::Read);
-                tracker.add_mem_op(BUFFER_SIZE + index_b, MemOp::Write);
-            }
+            observer.on_op(Op::Mul128, 2);
+            observer.on_mem_read_at(index_a, Site::UpdateA);
+            observer.on_mem_write_at(index_a, Site::UpdateA);
+            observer.on_mem_read_at(B.wrapping_add(index_b), Site::UpdateB);
+            observer.on_mem_write_at(B.wrapping_add(index_b), Site::UpdateB);
         }
 
-        addr_a = modular_power(addr_a, addr_b, result);
-        addr_b = isqrt(result).wrapping_mul((r as u64).wrapping_add(1)).wrapping_mul(isqrt(addr_a));
+        (addr_a, addr_b) = next_addresses(addr_a, addr_b, result, r, observer)?;
     }
+    observer.on_finish();
 
     Ok(())
 }
 
-pub fn xelis_hash(input: &[u8], scratch_pad: &mut ScratchPad, #[cfg(feature = "tracker")] distribution: &mut OpsTracker) -> Result<Hash, Error> {
-    v2::stage_1::<MEMORY_SIZE, MEMORY_SIZE_BYTES>(input, scratch_pad)?;
+pub fn xelis_hash(input: &[u8], scratch_pad: &mut ScratchPad) -> Result<Hash, Error> {
+    xelis_hash_words(input, scratch_pad.as_mut_slice())
+}
+
+// Same as `xelis_hash` but using a scratchpad owned by the caller, such as a C buffer
+pub fn xelis_hash_words(input: &[u8], scratch_pad: &mut [u64; MEMORY_SIZE]) -> Result<Hash, Error> {
+    hash_observed(input, scratch_pad, &mut NoopObserver)
+}
 
-    let scratch_pad = scratch_pad.as_mut_slice();
+// Same as `xelis_hash` but reporting the stage 3 execution to the observer
+pub fn xelis_hash_observed(input: &[u8], scratch_pad: &mut ScratchPad, observer: &mut impl Observer) -> Result<Hash, Error> {
+    hash_observed(input, scratch_pad.as_mut_slice(), observer)
+}
+
+fn hash_observed(input: &[u8], scratch_pad: &mut [u64; MEMORY_SIZE], observer: &mut impl Observer) -> Result<Hash, Error> {
+    v2::stage_1::<MEMORY_SIZE, MEMORY_SIZE_BYTES>(input, scratch_pad)?;
 
     // stage 3 is customized compared to v2
-    stage_3(scratch_pad, #[cfg(feature = "tracker")] distribution)?;
+    stage_3::<MEMORY_SIZE, BUFFER_SIZE, SCRATCHPAD_ITERS>(scratch_pad, observer)?;
 
     // final stage 4
     v2::stage_4(scratch_pad)
@@ -289,29 +318,46 @@ pub fn xelis_hash(input: &[u8], scratch_pad: &mut ScratchPad, #[cfg(feature = "t
 
 #[cfg(test)]
 mod tests {
-    use rand::{RngCore, rngs::OsRng};
+    use rand::{Rng, RngCore, SeedableRng, rngs::{OsRng, StdRng}};
     use super::*;
 
+    // Reduced parameters, small enough to run under Miri
+    // `cargo miri test --target s390x-unknown-linux-gnu` also checks them on a big-endian target
+    const SMALL_MEMORY_SIZE: usize = 256;
+
+    fn small_hash(input: &[u8], scratch_pad: &mut ScratchPadInternal<SMALL_MEMORY_SIZE>) -> Result<Hash, Error> {
+        small_hash_observed(input, scratch_pad, &mut NoopObserver)
+    }
+
+    fn small_hash_observed(input: &[u8], scratch_pad: &mut ScratchPadInternal<SMALL_MEMORY_SIZE>, observer: &mut impl Observer) -> Result<Hash, Error> {
+        let scratch_pad = scratch_pad.as_mut_slice();
+        v2::stage_1::<SMALL_MEMORY_SIZE, { SMALL_MEMORY_SIZE * 8 }>(input, scratch_pad)?;
+        stage_3::<SMALL_MEMORY_SIZE, { SMALL_MEMORY_SIZE / 2 }, SCRATCHPAD_ITERS>(scratch_pad, observer)?;
+        v2::stage_4(scratch_pad)
+    }
+
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_reused_scratchpad() {
         let mut scratch_pad = ScratchPad::default();
         let mut input = [0u8; 112];
         OsRng.fill_bytes(&mut input);
 
         // Do a first hash
-        let expected_hash = xelis_hash(&input, &mut scratch_pad, #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap();
+        let expected_hash = xelis_hash(&input, &mut scratch_pad).unwrap();
 
         // Do a second hash with dirty scratch pad but same input
-        let hash = xelis_hash(&input, &mut scratch_pad, #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap();
+        let hash = xelis_hash(&input, &mut scratch_pad).unwrap();
         assert_eq!(hash, expected_hash);
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_zero_hash() {
         let mut scratch_pad = ScratchPad::default();
-        let mut input = [0u8; 112];
+        let input = [0u8; 112];
 
-        let hash = xelis_hash(&mut input, &mut scratch_pad, #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap();
+        let hash = xelis_hash(&input, &mut scratch_pad).unwrap();
         let expected_hash = [
             105, 172, 103, 40, 94, 253, 92, 162,
             42, 252, 5, 196, 236, 238, 91, 218,
@@ -323,6 +369,7 @@ mod tests {
     }
  
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_verify_output() {
         let input = [
             172, 236, 108, 212, 181, 31, 109, 45, 44, 242, 54, 225, 143, 133,
@@ -336,7 +383,7 @@ mod tests {
         ];
 
         let mut scratch_pad = ScratchPad::default();
-        let hash = xelis_hash(&input, &mut scratch_pad, #[cfg(feature = "tracker")] &mut OpsTracker::new(MEMORY_SIZE)).unwrap();
+        let hash = xelis_hash(&input, &mut scratch_pad).unwrap();
 
         let expected_hash = [
             242, 8, 176, 222, 203, 27, 104,
@@ -348,6 +395,90 @@ mod tests {
         assert_eq!(hash, expected_hash);
     }
 
+    // Operands chosen to overflow in debug builds if any arithmetic isn't explicit
+    const ADVERSARIAL_VALUES: [u64; 10] = [0, 1, 2, 3, 8, 1 << 32, 1 << 63, u64::MAX - 8, u64::MAX - 1, u64::MAX];
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_branches_no_panic() {
+        let counters = [(0, 0, 0), (63, 1, 64), (MEMORY_SIZE - 1, SCRATCHPAD_ITERS - 1, BUFFER_SIZE - 1)];
+        for branch_idx in 0..16 {
+            for a in ADVERSARIAL_VALUES {
+                for b in ADVERSARIAL_VALUES {
+                    for c in ADVERSARIAL_VALUES {
+                        for result in ADVERSARIAL_VALUES {
+                            for (r, i, j) in counters {
+                                std::hint::black_box(branch(branch_idx, a, b, c, result, r, i, j, &mut NoopObserver));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..1_000_000 {
+            let (a, b, c, result) = (rng.gen(), rng.gen(), rng.gen(), rng.gen());
+            let r = rng.gen_range(0..MEMORY_SIZE);
+            let i = rng.gen_range(0..SCRATCHPAD_ITERS);
+            let j = rng.gen_range(0..BUFFER_SIZE);
+            for branch_idx in 0..16 {
+                std::hint::black_box(branch(branch_idx, a, b, c, result, r, i, j, &mut NoopObserver));
+            }
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_input_lengths_no_panic() {
+        let mut scratch_pad = ScratchPad::default();
+        let lengths = [0, 1, 31, 32, 33]
+            .into_iter()
+            .chain((2..=64).map(|chunks| chunks * 32))
+            .chain([1000, 4096, 65_536, MEMORY_SIZE_BYTES, MEMORY_SIZE_BYTES * 4]);
+
+        for len in lengths {
+            let input = vec![0xFF; len];
+            match xelis_hash(&input, &mut scratch_pad) {
+                Ok(_) | Err(Error::InvalidInputLength(_)) => {},
+                Err(e) => panic!("unexpected error for length {}: {}", len, e),
+            }
+        }
+
+        // 5 chunks don't fit in the scratchpad
+        let res = xelis_hash(&[0u8; 150], &mut scratch_pad);
+        assert!(matches!(res, Err(Error::InvalidInputLength(150))));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_adversarial_inputs_no_panic() {
+        // Stage 3 alone on a tiny scratchpad is cheap enough to run a million times in debug
+        const TINY_MEMORY_SIZE: usize = 16;
+
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        let mut words = [0u64; TINY_MEMORY_SIZE];
+        for _ in 0..1_000_000 {
+            crate::scratchpad::fill_adversarial(&mut words, &mut rng);
+            match stage_3::<TINY_MEMORY_SIZE, { TINY_MEMORY_SIZE / 2 }, SCRATCHPAD_ITERS>(&mut words, &mut NoopObserver) {
+                Ok(()) | Err(Error::ZeroModulus) => {},
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        // Full hashes of adversarial inputs
+        let mut scratch_pad = ScratchPadInternal::<SMALL_MEMORY_SIZE>::default();
+        let mut input = [0u64; 14];
+        for _ in 0..10_000 {
+            crate::scratchpad::fill_adversarial(&mut input, &mut rng);
+            let input: &[u8] = bytemuck::cast_slice(&input);
+            match small_hash(input, &mut scratch_pad) {
+                Ok(_) | Err(Error::ZeroModulus) => {},
+                Err(e) => panic!("unexpected error for {:?}: {}", input, e),
+            }
+        }
+    }
+
     #[test]
     #[cfg(feature = "tracker")]
     fn test_distribution() {
@@ -355,17 +486,24 @@ mod tests {
 
         let mut scratch_pad = ScratchPad::default();
         let mut input = [0u8; 112];
-        let mut distribution = OpsTracker::new(MEMORY_SIZE);
+        let mut distribution = crate::tracker::OpsTracker::new(MEMORY_SIZE);
         for _ in 0..ITERATIONS {
             OsRng.fill_bytes(&mut input);
-            let _ = xelis_hash(&input, &mut scratch_pad, &mut distribution).unwrap();
+            let _ = xelis_hash_observed(&input, &mut scratch_pad, &mut distribution).unwrap();
         }
 
         distribution.generate_branch_distribution("branch_v3.png").unwrap();
+        distribution.generate_transition_heatmap("transitions_v3.png").unwrap();
         distribution.generate_memory_usage_graph("memory_v3.png", 1000).unwrap();
+
+        let report = crate::analysis::analyze_tracker(&distribution, crate::analysis::DEFAULT_ALPHA, crate::analysis::V3_KNOWN_FAILURES);
+        println!("{}", report);
+        println!("Branch entropy rate: {:.4} bits", distribution.branch_entropy_rate());
+        assert!(report.passed(), "{}", report);
     }
 
     #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_pick_half() {
         let mut ones = 0;
         let mut zeros = 0;
@@ -383,7 +521,113 @@ mod tests {
         assert!((ratio - 0.5).abs() < 0.01, "pick_half is not balanced: ratio={}", ratio);
     }
 
+    // Floating-point implementation used before, with the release wrapping semantics
+    fn legacy_isqrt(n: u64) -> u64 {
+        if n < 2 {
+            return n;
+        }
+
+        let approx = (n as f64).sqrt() as u64;
+        if approx.wrapping_mul(approx) > n {
+            approx.wrapping_sub(1)
+        } else if approx.wrapping_add(1).wrapping_mul(approx.wrapping_add(1)) <= n {
+            approx.wrapping_add(1)
+        } else {
+            approx
+        }
+    }
+
     #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_isqrt_legacy_boundaries() {
+        let mut values = vec![0, 1, 2, 3, u64::MAX, u64::MAX - 1];
+        for shift in 0..64 {
+            let v = 1u64 << shift;
+            values.extend([v - 1, v, v + 1]);
+        }
+
+        let squares = (0..1u64 << 32).step_by(65_537)
+            .chain((1u64 << 32) - 65_536..1u64 << 32);
+        for k in squares {
+            let square = k * k;
+            values.extend([square.wrapping_sub(1), square, square.wrapping_add(1)]);
+        }
+
+        // Around the overflow bounds
+        for bound in [ISQRT_OVERFLOW_LOW, ISQRT_OVERFLOW_HIGH] {
+            values.extend(bound - 4096..=bound.saturating_add(4096));
+        }
+        values.extend(u64::MAX - 4096..=u64::MAX);
+
+        for n in values {
+            assert_eq!(isqrt(n), legacy_isqrt(n), "isqrt({})", n);
+        }
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_isqrt_legacy_random() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        for _ in 0..1_000_000 {
+            let n = rng.gen::<u64>();
+            assert_eq!(isqrt(n), legacy_isqrt(n), "isqrt({})", n);
+
+            let n = n >> rng.gen_range(0..64);
+            assert_eq!(isqrt(n), legacy_isqrt(n), "isqrt({})", n);
+
+            // Values in the overflowing range
+            let n = ISQRT_OVERFLOW_LOW + rng.gen_range(0..=u64::MAX - ISQRT_OVERFLOW_LOW);
+            assert_eq!(isqrt(n), legacy_isqrt(n), "isqrt({})", n);
+        }
+    }
+
+    // Exhaustive check of every perfect square and its neighbors
+    // Run it with `cargo test --release -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_isqrt_legacy_all_squares() {
+        for k in 0..1u64 << 32 {
+            let square = k * k;
+            for n in [square.wrapping_sub(1), square, square.wrapping_add(1)] {
+                assert_eq!(isqrt(n), legacy_isqrt(n), "isqrt({})", n);
+            }
+        }
+    }
+
+    #[test]
+    fn test_zero_modulus() {
+        // A zero result at the end of a stage 3 iteration has no valid hash
+        for (addr_a, addr_b) in [(0, 0), (1, 0), (u64::MAX, u64::MAX)] {
+            assert!(matches!(next_addresses(addr_a, addr_b, 0, 0, &mut NoopObserver), Err(Error::ZeroModulus)));
+        }
+
+        let (addr_a, addr_b) = next_addresses(3, 4, 5, 0, &mut NoopObserver).unwrap();
+        assert_eq!(addr_a, 1);
+        assert_eq!(addr_b, 2 * isqrt(addr_a));
+    }
+
+    #[test]
+    fn test_zero_modulus_stage_3() {
+        struct Branches(Vec<u8>);
+
+        impl Observer for Branches {
+            fn on_branch(&mut self, branch: u8) {
+                self.0.push(branch);
+            }
+        }
+
+        // Found by a seeded search over small scratchpads: the first iteration reads a zero word
+        // at index_t, so it writes its result to mem_buffer_a[1]. The second iteration reads
+        // it back as c and takes the branch 11 without division, which returns c: v equals
+        // result and the iteration ends with a zero result
+        let mut scratch_pad = [0, 0x7fbbb5d0c0663c1d, 0, 0];
+        let mut branches = Branches(Vec::new());
+        assert!(matches!(stage_3::<4, 2, 1>(&mut scratch_pad, &mut branches), Err(Error::ZeroModulus)));
+        assert_eq!(branches.0.last(), Some(&11));
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
     fn test_map_index() {
         for _ in 0..10_000_000 {
             let i = OsRng.next_u64();
@@ -395,4 +639,174 @@ mod tests {
         assert!(map_index(0) == 0);
         assert!(map_index(u64::MAX) < BUFFER_SIZE);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_small_params() {
+        let mut scratch_pad = ScratchPadInternal::default();
+        let zero_hash = [
+            164, 67, 108, 157, 10, 99, 155, 138, 80, 151, 11, 155, 229, 118, 187, 193,
+            188, 141, 237, 220, 205, 151, 148, 111, 253, 237, 30, 209, 5, 86, 214, 6,
+        ];
+        let custom_hash = [
+            136, 92, 30, 235, 79, 115, 62, 226, 206, 60, 77, 76, 225, 119, 199, 66,
+            38, 190, 33, 69, 145, 54, 100, 22, 109, 7, 61, 114, 146, 66, 128, 153,
+        ];
+
+        assert_eq!(small_hash(&[0u8; 112], &mut scratch_pad).unwrap(), zero_hash);
+        assert_eq!(small_hash(b"xelis-hashing-algorithm", &mut scratch_pad).unwrap(), custom_hash);
+        // Reused scratchpad
+        assert_eq!(small_hash(&[0u8; 112], &mut scratch_pad).unwrap(), zero_hash);
+    }
+
+    #[test]
+    fn test_small_params_reused_scratchpad() {
+        let mut rng = StdRng::seed_from_u64(0xDEADBEEFCAFEBABE);
+        let mut scratch_pad = ScratchPadInternal::default();
+        let mut input = [0u8; 112];
+
+        for _ in 0..4 {
+            rng.fill_bytes(&mut input);
+            let hash = small_hash(&input, &mut scratch_pad).unwrap();
+            assert_eq!(hash, small_hash(&input, &mut ScratchPadInternal::default()).unwrap());
+        }
+    }
+
+    struct CountingObserver {
+        memory_size: usize,
+        iterations: usize,
+        starts: usize,
+        finishes: usize,
+        branches: usize,
+        reads: usize,
+        writes: usize,
+    }
+
+    impl CountingObserver {
+        fn new(memory_size: usize) -> Self {
+            Self { memory_size, iterations: 0, starts: 0, finishes: 0, branches: 0, reads: 0, writes: 0 }
+        }
+    }
+
+    impl Observer for CountingObserver {
+        fn on_iteration(&mut self, i: usize, j: usize) {
+            let buffer_size = self.memory_size / 2;
+            assert_eq!((i, j), (self.iterations / buffer_size, self.iterations % buffer_size));
+            self.iterations += 1;
+        }
+
+        fn on_branch(&mut self, branch: u8) {
+            assert!(branch < 16);
+            self.branches += 1;
+        }
+
+        fn on_mem_read(&mut self, index: usize) {
+            assert!(index < self.memory_size);
+            self.reads += 1;
+        }
+
+        fn on_mem_write(&mut self, index: usize) {
+            assert!(index < self.memory_size);
+            self.writes += 1;
+        }
+
+        fn on_start(&mut self) {
+            self.starts += 1;
+        }
+
+        fn on_finish(&mut self) {
+            self.finishes += 1;
+        }
+    }
+
+    #[test]
+    fn test_observer() {
+        const B: usize = SMALL_MEMORY_SIZE / 2;
+
+        let mut scratch_pad = ScratchPadInternal::default();
+        let mut observer = CountingObserver::new(SMALL_MEMORY_SIZE);
+        let hash = small_hash_observed(&[0u8; 112], &mut scratch_pad, &mut observer).unwrap();
+        assert_eq!(hash, small_hash(&[0u8; 112], &mut scratch_pad).unwrap());
+
+        assert_eq!(observer.iterations, SCRATCHPAD_ITERS * B);
+        assert_eq!(observer.starts, 1);
+        assert_eq!(observer.finishes, 1);
+        assert_eq!(observer.branches, SCRATCHPAD_ITERS * B);
+        assert_eq!(observer.reads, 2 + SCRATCHPAD_ITERS * (2 + 6 * B));
+        assert_eq!(observer.writes, SCRATCHPAD_ITERS * 2 * B);
+    }
+
+    #[test]
+    #[cfg(feature = "tracker-core")]
+    fn test_locality() {
+        const B: usize = SMALL_MEMORY_SIZE / 2;
+        let (reads, writes) = (2 + SCRATCHPAD_ITERS * (2 + 6 * B), SCRATCHPAD_ITERS * 2 * B);
+
+        let mut tracker = crate::tracker::OpsTracker::new(SMALL_MEMORY_SIZE).with_access_log();
+        small_hash_observed(&[0u8; 112], &mut ScratchPadInternal::default(), &mut tracker).unwrap();
+
+        let locality = tracker.locality().unwrap();
+        assert_eq!(locality.get_accesses() as usize, reads + writes);
+        assert_eq!(locality.get_reads_before_write() + locality.get_read_after_write().values().sum::<u64>(), reads as u64);
+        assert!(locality.get_cold_accesses() as usize <= SMALL_MEMORY_SIZE);
+    }
+
+    #[test]
+    #[cfg(feature = "tracker-core")]
+    fn test_warp_divergence() {
+        let mut scratch_pad = ScratchPadInternal::default();
+        crate::tracker::test_helpers::check_warp_divergence(|input, simulator| small_hash_observed(input, &mut scratch_pad, simulator));
+    }
+
+    #[test]
+    #[cfg(feature = "tracker-core")]
+    fn test_coalescing() {
+        use crate::tracker::{coalescing::CoalescingSimulator, test_helpers::hash_warps, MemOp};
+
+        let mut scratch_pad = ScratchPadInternal::default();
+        let mut simulator = CoalescingSimulator::new(32);
+        hash_warps(&mut simulator, |input, simulator| small_hash_observed(input, &mut scratch_pad, simulator));
+
+        let report = simulator.report();
+        assert_eq!(report.sites.len(), 11);
+        // All the lanes access the same r
+        assert_eq!(report.site(Site::R, MemOp::Read).unwrap().transactions_per_request(), [1.0; 3]);
+        // 32 random words of a 64 segments scratchpad are in 25 segments on average
+        assert!(report.site(Site::A, MemOp::Read).unwrap().transactions_per_request()[0] > 20.0, "{}", report);
+        assert!(report.site(Site::UpdateA, MemOp::Write).is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "tracker-core")]
+    fn test_op_counts() {
+        use crate::tracker::cost::{CostCounter, LatencyTable, Version};
+
+        const B: u64 = SMALL_MEMORY_SIZE as u64 / 2;
+        const ITERS: u64 = SCRATCHPAD_ITERS as u64;
+
+        // Only the random reads cost a cycle
+        let mut counter = CostCounter::new(Version::V3, vec![LatencyTable { random_read: 1, ..Default::default() }]);
+        small_hash_observed(&[0u8; 112], &mut ScratchPadInternal::default(), &mut counter).unwrap();
+
+        let hash = &counter.get_hashes()[0];
+        let ops = &hash.ops;
+        assert_eq!(ops.aes_rounds, ITERS);
+        assert!(ops.modular_power_steps > 0);
+        // Two map_index per scratchpad iteration and five per inner iteration, plus the branches 14 and 15
+        assert!(ops.mul_128 >= 2 * ITERS + 5 * ITERS * B);
+        assert_eq!(ops.random_reads, 2 * ITERS + 5 * ITERS * B);
+        assert_eq!(ops.sequential_reads, 2 + ITERS * B);
+        assert_eq!((ops.random_writes, ops.sequential_writes), (2 * ITERS * B, 0));
+        // The second read depends on the first one
+        assert_eq!(hash.critical_path, [ITERS * (2 + 2 * B)]);
+    }
+
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn test_observed_hash() {
+        let mut scratch_pad = ScratchPad::default();
+        let input = [0u8; 112];
+        let expected = xelis_hash(&input, &mut scratch_pad).unwrap();
+        assert_eq!(xelis_hash_observed(&input, &mut scratch_pad, &mut NoopObserver).unwrap(), expected);
+        assert_eq!(xelis_hash_observed(&input, &mut scratch_pad, &mut CountingObserver::new(MEMORY_SIZE)).unwrap(), expected);
+    }
+}
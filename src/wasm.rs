@@ -0,0 +1,134 @@
+// JavaScript API of the WebAssembly build, enabled by the `wasm` feature
+// Errors are thrown as JavaScript `Error`s.
+// A `ScratchPad` should be reused between hashes to not allocate it each time.
+//
+// The crate is only built as a `cdylib` for the module, the tests run under Node:
+//   cargo test --target wasm32-unknown-unknown --no-default-features --features wasm --lib
+//   cargo rustc --lib --release --target wasm32-unknown-unknown --no-default-features --features wasm --crate-type cdylib
+//   wasm-bindgen --target nodejs --out-dir pkg target/wasm32-unknown-unknown/release/xelis_hash.wasm
+
+use wasm_bindgen::prelude::*;
+
+use crate::{Error, Hash, HASH_SIZE};
+
+// Scratchpad words of an algorithm version
+struct Inner {
+    version: u8,
+    words: Box<[u64]>,
+}
+
+impl Inner {
+    fn new(version: u8) -> Result<Self, JsError> {
+        let size = crate::scratchpad_size(version)
+            .ok_or_else(|| JsError::new(&format!("unsupported version: {}", version)))?;
+
+        Ok(Self { version, words: vec![0; size].into_boxed_slice() })
+    }
+
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn hash(&mut self, input: &[u8]) -> Result<Hash, Error> {
+        crate::hash_any(self.version, input, &mut self.words)
+    }
+}
+
+// Scratchpad of an algorithm version, reused between hashes to not allocate it each time
+#[wasm_bindgen]
+pub struct ScratchPad {
+    inner: Inner,
+}
+
+#[wasm_bindgen]
+impl ScratchPad {
+    #[wasm_bindgen(constructor)]
+    pub fn new(version: u8) -> Result<ScratchPad, JsError> {
+        Ok(Self { inner: Inner::new(version)? })
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn version(&self) -> u8 {
+        self.inner.version()
+    }
+}
+
+// Hash the input using the scratchpad, checking that it is of the expected version
+fn hash(version: u8, input: &[u8], scratch_pad: &mut ScratchPad) -> Result<Result<Hash, Error>, JsError> {
+    if scratch_pad.inner.version() != version {
+        return Err(JsError::new(&format!("expected a v{} scratchpad, got a v{} one", version, scratch_pad.inner.version())));
+    }
+
+    Ok(scratch_pad.inner.hash(input))
+}
+
+#[wasm_bindgen(js_name = hashV2)]
+pub fn hash_v2(input: &[u8], scratch_pad: &mut ScratchPad) -> Result<Vec<u8>, JsError> {
+    Ok(hash(2, input, scratch_pad)??.to_vec())
+}
+
+#[wasm_bindgen(js_name = hashV3)]
+pub fn hash_v3(input: &[u8], scratch_pad: &mut ScratchPad) -> Result<Vec<u8>, JsError> {
+    Ok(hash(3, input, scratch_pad)??.to_vec())
+}
+
+// Check that the input hashes to the expected hash using the scratchpad of its version
+// An input that has no valid hash is never valid
+#[wasm_bindgen]
+pub fn verify(input: &[u8], expected: &[u8], scratch_pad: &mut ScratchPad) -> Result<bool, JsError> {
+    if expected.len() != HASH_SIZE {
+        return Err(JsError::new(&format!("expected a {} bytes hash, got {} bytes", HASH_SIZE, expected.len())));
+    }
+
+    let version = scratch_pad.inner.version();
+    Ok(hash(version, input, scratch_pad)?.is_ok_and(|hash| hash == expected))
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod tests {
+    use wasm_bindgen_test::*;
+    use super::*;
+
+    #[wasm_bindgen_test]
+    fn test_zero_input() {
+        let input = [0u8; 112];
+        let mut scratch_pad = ScratchPad::new(3).unwrap();
+        let hash = hash_v3(&input, &mut scratch_pad).unwrap();
+        assert_eq!(&hash[..4], [105, 172, 103, 40]);
+
+        // Reused scratchpad
+        assert_eq!(hash_v3(&input, &mut scratch_pad).unwrap(), hash);
+        assert!(verify(&input, &hash, &mut scratch_pad).unwrap());
+        assert!(!verify(&input, &[0u8; HASH_SIZE], &mut scratch_pad).unwrap());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_test_vectors() {
+        for (version, content) in [(2, include_str!("../test-vectors/v2.json")), (3, include_str!("../test-vectors/v3.json"))] {
+            let file: serde_json::Value = serde_json::from_str(content).unwrap();
+            let mut scratch_pad = ScratchPad::new(version).unwrap();
+            for vector in file["vectors"].as_array().unwrap() {
+                let repeat = vector.get("repeat").map_or(1, |repeat| repeat.as_u64().unwrap() as usize);
+                let input = hex::decode(vector["input"].as_str().unwrap()).unwrap().repeat(repeat);
+                match vector.get("hash") {
+                    Some(expected) => {
+                        let expected = hex::decode(expected.as_str().unwrap()).unwrap();
+                        assert!(verify(&input, &expected, &mut scratch_pad).unwrap());
+                    },
+                    None => assert!(!verify(&input, &[0u8; HASH_SIZE], &mut scratch_pad).unwrap()),
+                }
+            }
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_invalid_arguments() {
+        assert!(ScratchPad::new(4).is_err());
+
+        let mut scratch_pad = ScratchPad::new(2).unwrap();
+        assert_eq!(scratch_pad.version(), 2);
+        assert!(hash_v3(&[], &mut scratch_pad).is_err());
+        assert!(hash_v2(&[0u8; 150], &mut scratch_pad).is_err());
+        assert!(verify(&[], &[0u8; 31], &mut scratch_pad).is_err());
+    }
+}